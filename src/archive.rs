@@ -0,0 +1,236 @@
+use crate::compress::Compression;
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+use crate::output::Sink;
+use crate::stats::TableStats;
+
+/// Compression of a tar archive, derived from its file name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TarCompression {
+    None,
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
+impl TarCompression {
+    pub(crate) fn from_path(path: &str) -> Result<TarCompression, DumperError> {
+        let compression = if path.ends_with(".tar") {
+            TarCompression::None
+        } else if path.ends_with(".tar.gz") || path.ends_with(".tgz") {
+            TarCompression::Gzip
+        } else if path.ends_with(".tar.zst") {
+            TarCompression::Zstd
+        } else if path.ends_with(".tar.bz2") {
+            TarCompression::Bzip2
+        } else {
+            return Err(DumperError::InvalidConfig(format!(
+                "unsupported tar archive extension '{path}', \
+                 expected .tar, .tar.gz, .tar.zst or .tar.bz2"
+            )));
+        };
+        Ok(compression)
+    }
+}
+
+/// Password the entries of a ZIP archive are encrypted with, AES-256. It is
+/// left out of `Debug` output.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ZipPassword(String);
+
+impl ZipPassword {
+    pub fn new(password: impl Into<String>) -> ZipPassword {
+        ZipPassword(password.into())
+    }
+}
+
+impl std::fmt::Debug for ZipPassword {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ZipPassword(..)")
+    }
+}
+
+/// Temporary file next to `path`, so that it can be renamed into place.
+fn temp_file_beside(path: &std::path::Path) -> Result<tempfile::NamedTempFile, DumperError> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => std::path::Path::new("."),
+    };
+    std::fs::create_dir_all(dir)?;
+    Ok(tempfile::NamedTempFile::new_in(dir)?)
+}
+
+fn zip_entry_options(config: &DumpConfig) -> zip::write::FileOptions<'_, 'static, ()> {
+    let (method, level) = match config.compression {
+        Compression::None => (zip::CompressionMethod::Stored, None),
+        Compression::Gzip => (
+            zip::CompressionMethod::Deflated,
+            config.compress_level.map(|l| l.clamp(1, 9)),
+        ),
+        Compression::Zstd => (
+            zip::CompressionMethod::Zstd,
+            config.compress_level.map(|l| l.clamp(1, 22)),
+        ),
+        // Rejected by DumpConfigBuilder::build.
+        Compression::Lz4 => (zip::CompressionMethod::Stored, None),
+    };
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(method)
+        .compression_level(level.map(i64::from))
+        .large_file(true);
+    match &config.output_zip_password {
+        Some(password) => options.with_aes_encryption(zip::AesMode::Aes256, &password.0),
+        None => options,
+    }
+}
+
+/// Dump the tables one after another into the entries of a ZIP archive.
+///
+/// The archive is written to a temporary file next to `archive_path` and only
+/// renamed into place once every table succeeded. The first failed table
+/// stops the dump, its error is recorded in the returned stats.
+pub(crate) fn dump_zip(
+    config: &DumpConfig,
+    table_names: &[String],
+    archive_path: &str,
+) -> Result<Vec<TableStats>, DumperError> {
+    let archive_path = std::path::Path::new(archive_path);
+    let tmp = temp_file_beside(archive_path)?;
+    let options = zip_entry_options(config);
+    let mut tables = Vec::with_capacity(table_names.len());
+    let mut zip = zip::ZipWriter::new(tmp.reopen()?);
+
+    for table_name in table_names {
+        tracing::info!("Dumping table {table_name} into {}", archive_path.display());
+        let start_time = std::time::Instant::now();
+        let part = config.rows_per_file.map(|_| 0);
+        zip.start_file(crate::output::file_name(config, table_name, part), options)?;
+        let mut next_part = |sink: Sink, _rows: u64, part: usize| match sink {
+            Sink::Zip(mut zip) => {
+                let entry_name = crate::output::file_name(config, table_name, Some(part));
+                zip.start_file(entry_name, options)?;
+                Ok((Sink::Zip(zip), !config.no_header))
+            }
+            _ => unreachable!("write_table returns the sink it was given"),
+        };
+        let result = crate::write_table(
+            config,
+            table_name,
+            Sink::Zip(Box::new(zip)),
+            !config.no_header,
+            Some(&mut next_part),
+            None,
+        );
+        let mut stats = TableStats::new(table_name, start_time);
+        let (sink, written) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                // The archive went with the sink, the temporary file is removed.
+                tracing::error!(table = table_name, error = %e, "Error while handling table {table_name}. {e}");
+                tracing::error!(
+                    "Table {table_name} failed, not writing archive {}",
+                    archive_path.display()
+                );
+                stats.error = Some(e.to_string());
+                tables.push(stats);
+                return Ok(tables);
+            }
+        };
+        zip = match sink {
+            Sink::Zip(zip) => *zip,
+            _ => unreachable!("write_table returns the sink it was given"),
+        };
+        stats.rows = Some(written.rows);
+        stats.max_rowid = written.max_rowid;
+        stats.max_timestamp = written.max_timestamp;
+        tables.push(stats);
+    }
+    zip.finish()?;
+    tmp.persist(archive_path).map_err(|e| e.error)?;
+    tracing::info!("Wrote archive {}", archive_path.display());
+    Ok(tables)
+}
+
+/// Append the files of `dir` to the archive, named relative to `dir`.
+fn append_dir<W: std::io::Write>(
+    builder: &mut tar::Builder<W>,
+    dir: &std::path::Path,
+) -> Result<(), DumperError> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        if entry.file_type()?.is_dir() {
+            builder.append_dir_all(entry.file_name(), entry.path())?;
+        } else {
+            builder.append_path_with_name(entry.path(), entry.file_name())?;
+        }
+    }
+    Ok(())
+}
+
+fn write_tar<W: std::io::Write>(writer: W, dir: &std::path::Path) -> Result<W, DumperError> {
+    let mut builder = tar::Builder::new(writer);
+    append_dir(&mut builder, dir)?;
+    Ok(builder.into_inner()?)
+}
+
+/// Dump the tables into a temporary directory, then pack it into a tar archive.
+///
+/// Unlike ZIP entries, tar needs the size of each file up front, so the
+/// tables are dumped in parallel first and packed once all of them are done.
+/// The entries of a compressed archive are not compressed again, and the
+/// archive is only written when every table succeeded. Failed tables are
+/// recorded in the returned stats.
+pub(crate) async fn dump_tar(
+    config: &DumpConfig,
+    table_names: &[String],
+    archive_path: &str,
+) -> Result<Vec<TableStats>, DumperError> {
+    let compression = TarCompression::from_path(archive_path)?;
+    let archive_path = std::path::Path::new(archive_path);
+    let tmp = temp_file_beside(archive_path)?;
+    let staging = tempfile::tempdir()?;
+
+    let mut staging_config = config.clone();
+    staging_config.output_dir = staging.path().to_string_lossy().into_owned();
+    staging_config.output_tar = None;
+    if compression != TarCompression::None {
+        staging_config.compression = Compression::None;
+        staging_config.zstd_dictionary = None;
+    }
+    let tables = crate::dump_tables(&staging_config, table_names).await;
+    let failed = tables.iter().filter(|table| table.error.is_some()).count();
+    if failed > 0 {
+        tracing::error!(
+            "{failed} tables failed, not writing archive {}",
+            archive_path.display()
+        );
+        return Ok(tables);
+    }
+
+    tracing::info!("Packing {}", archive_path.display());
+    let file = tmp.reopen()?;
+    let level = config.compress_level;
+    match compression {
+        TarCompression::None => {
+            write_tar(file, staging.path())?;
+        }
+        TarCompression::Gzip => {
+            let level = level.unwrap_or(6).clamp(1, 9) as u32;
+            let encoder = flate2::write::GzEncoder::new(file, flate2::Compression::new(level));
+            write_tar(encoder, staging.path())?.finish()?;
+        }
+        TarCompression::Zstd => {
+            let encoder = zstd::Encoder::new(file, level.unwrap_or(3).clamp(1, 22))?;
+            write_tar(encoder, staging.path())?.finish()?;
+        }
+        TarCompression::Bzip2 => {
+            let level = level.unwrap_or(9).clamp(1, 9) as u32;
+            let encoder = bzip2::write::BzEncoder::new(file, bzip2::Compression::new(level));
+            write_tar(encoder, staging.path())?.finish()?;
+        }
+    }
+    tmp.persist(archive_path).map_err(|e| e.error)?;
+    tracing::info!("Wrote archive {}", archive_path.display());
+    Ok(tables)
+}
@@ -0,0 +1,61 @@
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+use crate::formats::Field;
+
+/// Path of `path` relative to the output directory, as written into cells.
+fn relative_to_output(path: &std::path::Path, config: &DumpConfig) -> String {
+    let relative = std::path::absolute(path).ok().and_then(|path| {
+        let base = std::path::absolute(&config.output_dir).ok()?;
+        pathdiff::diff_paths(path, base)
+    });
+    relative.as_deref().unwrap_or(path).display().to_string()
+}
+
+/// Move BLOBs out of the cell. With `blob_dir`, BLOBs of at least
+/// `blob_inline_threshold` bytes, and larger than `max_blob_size` when set, are
+/// written to `<blob_dir>/<table>/<column>/<row_id>.bin` and the cell holds the
+/// path. Without `blob_dir`, BLOBs larger than `max_blob_size` are truncated.
+pub(crate) fn limit_blob(
+    field: Field,
+    config: &DumpConfig,
+    table_name: &str,
+    column: &str,
+    row_id: i64,
+) -> Result<Field, DumperError> {
+    let mut blob = match field {
+        Field::Blob(blob) => blob,
+        field => return Ok(field),
+    };
+    let oversized = config.max_blob_size.is_some_and(|max| blob.len() > max);
+    match &config.blob_dir {
+        Some(dir)
+            if blob.len() >= config.blob_inline_threshold
+                && (oversized || config.max_blob_size.is_none()) =>
+        {
+            let dir = std::path::Path::new(dir)
+                .join(crate::output::path_component(table_name))
+                .join(crate::output::path_component(column));
+            std::fs::create_dir_all(&dir)?;
+            let path = dir.join(format!("{row_id}.bin"));
+            std::fs::write(&path, &blob)?;
+            if oversized {
+                tracing::warn!(
+                    "Table {table_name}: {} byte BLOB in column {column} of row {row_id} written to {}",
+                    blob.len(),
+                    path.display()
+                );
+            }
+            Ok(Field::Text(relative_to_output(&path, config)))
+        }
+        None if oversized => {
+            let max = config.max_blob_size.unwrap_or_default();
+            tracing::warn!(
+                "Table {table_name}: {} byte BLOB in column {column} of row {row_id} truncated to {max} bytes",
+                blob.len()
+            );
+            blob.truncate(max);
+            Ok(Field::Blob(blob))
+        }
+        _ => Ok(Field::Blob(blob)),
+    }
+}
@@ -0,0 +1,95 @@
+use std::io::Write;
+
+use sha2::Digest;
+
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+use crate::output::FileRecord;
+
+/// Hash algorithm of the checksum file written by `checksum`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ChecksumAlgorithm {
+    Md5,
+    Sha1,
+    #[default]
+    Sha256,
+    Sha512,
+}
+
+impl ChecksumAlgorithm {
+    /// Name of the checksum file in the output directory, e.g.
+    /// `checksums.sha256`.
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Md5 => "checksums.md5",
+            ChecksumAlgorithm::Sha1 => "checksums.sha1",
+            ChecksumAlgorithm::Sha256 => "checksums.sha256",
+            ChecksumAlgorithm::Sha512 => "checksums.sha512",
+        }
+    }
+
+    pub(crate) fn hasher(&self) -> Hasher {
+        match self {
+            ChecksumAlgorithm::Md5 => Hasher::Md5(md5::Md5::new()),
+            ChecksumAlgorithm::Sha1 => Hasher::Sha1(sha1::Sha1::new()),
+            ChecksumAlgorithm::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+            ChecksumAlgorithm::Sha512 => Hasher::Sha512(sha2::Sha512::new()),
+        }
+    }
+}
+
+/// Running hash of an output file, updated with every chunk written.
+pub(crate) enum Hasher {
+    Md5(md5::Md5),
+    Sha1(sha1::Sha1),
+    Sha256(sha2::Sha256),
+    Sha512(sha2::Sha512),
+}
+
+impl Hasher {
+    pub(crate) fn update(&mut self, buf: &[u8]) {
+        match self {
+            Hasher::Md5(h) => h.update(buf),
+            Hasher::Sha1(h) => h.update(buf),
+            Hasher::Sha256(h) => h.update(buf),
+            Hasher::Sha512(h) => h.update(buf),
+        }
+    }
+
+    /// Lowercase hex digest.
+    pub(crate) fn finish(self) -> String {
+        match self {
+            Hasher::Md5(h) => hex::encode(h.finalize()),
+            Hasher::Sha1(h) => hex::encode(h.finalize()),
+            Hasher::Sha256(h) => hex::encode(h.finalize()),
+            Hasher::Sha512(h) => hex::encode(h.finalize()),
+        }
+    }
+}
+
+/// Write the checksums of `files` into the checksum file of `algorithm` in
+/// the output directory, in the format read by `sha256sum -c` and friends.
+/// The paths are relative to the output directory.
+pub(crate) fn write(
+    config: &DumpConfig,
+    algorithm: ChecksumAlgorithm,
+    files: &[&FileRecord],
+) -> Result<(), DumperError> {
+    let dir = std::path::Path::new(&config.output_dir);
+    let path = dir.join(algorithm.file_name());
+    let mut out = std::io::BufWriter::new(std::fs::File::create(&path)?);
+    for file in files {
+        let Some(checksum) = &file.checksum else {
+            continue;
+        };
+        let name = file.path.strip_prefix(dir).unwrap_or(&file.path);
+        writeln!(out, "{checksum}  {}", name.display())?;
+    }
+    out.flush()?;
+    tracing::info!(
+        "Wrote checksums of {} files to {}",
+        files.len(),
+        path.display()
+    );
+    Ok(())
+}
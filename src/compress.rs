@@ -0,0 +1,21 @@
+/// Compression applied to each output file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+    Lz4,
+}
+
+impl Compression {
+    /// Suffix appended to the file name, including the leading dot.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+            Compression::Lz4 => ".lz4",
+        }
+    }
+}
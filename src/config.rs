@@ -0,0 +1,1235 @@
+use std::collections::HashMap;
+
+use crate::archive::ZipPassword;
+use crate::checksum::ChecksumAlgorithm;
+use crate::compress::Compression;
+use crate::error::DumperError;
+use crate::filter::TableFilter;
+use crate::formats::{
+    BlobFormat, FloatFormat, LineEnding, MarkdownAlign, OutputFormat, Quoting, SqlDialect,
+};
+use crate::named_query::NamedQuery;
+use crate::pivot::Pivot;
+use crate::redact::RedactColumn;
+use crate::timestamp::EpochUnit;
+
+/// Options controlling what is dumped and how.
+///
+/// Construct it with [`DumpConfig::builder`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct DumpConfig {
+    /// SQLite database file to read.
+    pub db_path: String,
+    /// `PRAGMA name = value` statements run on every connection, see
+    /// [`READ_ONLY_PRAGMAS`](crate::READ_ONLY_PRAGMAS).
+    pub pragmas: Vec<(String, String)>,
+    /// Bytes of the database file SQLite may memory-map, 0 disables it.
+    pub mmap_size: u64,
+    /// Databases attached read-only to every connection, as `(alias, path)`.
+    pub attach: Vec<(String, String)>,
+    /// Older snapshot of the database. When set, only the rows added and
+    /// deleted since it are dumped, to `<table>_added` and `<table>_deleted`.
+    pub compare_db: Option<String>,
+    /// Dump from a compacted copy made with `VACUUM INTO` in a temporary
+    /// directory, deleted after the dump.
+    pub vacuum_first: bool,
+    /// Copy the database with the online backup API to this file and dump
+    /// from the copy, a consistent snapshot even while the database is
+    /// written. The copy is deleted after the dump unless `keep_backup`.
+    pub backup: Option<String>,
+    /// Keep the `backup` copy after the dump.
+    pub keep_backup: bool,
+    /// Value of `PRAGMA cache_size`: pages when positive, KiB when negative.
+    /// `None` keeps SQLite's default.
+    pub cache_size: Option<i64>,
+    /// Directory the table dumps are written to.
+    pub output_dir: String,
+    /// File name of each table relative to `output_dir`. `{table}`, `{date}`,
+    /// `{datetime}` and `{ext}` are substituted, subdirectories are created.
+    pub output_pattern: String,
+    /// Split each table into files of at most this many rows, named
+    /// `<name>_partNNN.<ext>`.
+    pub rows_per_file: Option<u64>,
+    /// Write a JSON manifest of the output files, with their row counts,
+    /// sizes and SHA-256 hashes, to this path.
+    pub output_manifest: Option<String>,
+    /// Write a checksum file of this algorithm into `output_dir`, listing
+    /// every output file.
+    pub checksum: Option<ChecksumAlgorithm>,
+    /// Format of the output files.
+    pub output_format: OutputFormat,
+    /// Maximum number of rows per Parquet row group.
+    pub parquet_row_group_size: usize,
+    /// Dialect of the sql format.
+    pub sql_dialect: SqlDialect,
+    /// Alignment of columns of the markdown format, by column name. Other
+    /// columns have no alignment.
+    pub markdown_align: HashMap<String, MarkdownAlign>,
+    /// CSS class of the `<table>` element of the html format.
+    pub html_class: Option<String>,
+    /// Render each table of the html format through this minijinja
+    /// template file instead of the built-in document.
+    pub html_template: Option<String>,
+    /// CSV field delimiter.
+    pub delimiter: u8,
+    /// Start CSV files with a UTF-8 byte order mark, so that Excel reads
+    /// them as UTF-8.
+    pub csv_bom: bool,
+    /// Terminator of CSV records.
+    pub line_ending: LineEnding,
+    /// Character CSV fields are quoted with.
+    pub quote_char: u8,
+    /// Character escaping the quote character inside quoted CSV fields. The
+    /// quote character itself doubles quotes.
+    pub escape_char: u8,
+    /// When CSV fields are quoted.
+    pub quoting: Quoting,
+    /// Encoding CSV text is written in, UTF-8 when `None`. Characters it
+    /// cannot represent are written as `?`.
+    pub encoding: Option<&'static encoding_rs::Encoding>,
+    /// Write all table dumps into this ZIP archive instead of `output_dir`.
+    /// The archive is only written when every table succeeded, the first
+    /// failed table stops the dump.
+    pub output_zip: Option<String>,
+    /// Encrypt the entries of `output_zip` with AES-256 and this password.
+    pub output_zip_password: Option<ZipPassword>,
+    /// Write the rows of all tables into this one file, with a leading
+    /// `_table` column, instead of one file per table in `output_dir`.
+    pub single_file: Option<String>,
+    /// Write all table dumps into this tar archive instead of `output_dir`.
+    /// The archive compression follows the extension and replaces
+    /// `compression` for the files inside it. The archive is only written
+    /// when every table succeeded.
+    pub output_tar: Option<String>,
+    /// Compression applied to each output file. Parquet files use it as
+    /// their internal codec instead, Snappy when it is `None`.
+    pub compression: Compression,
+    /// Compression level, clamped to the range of the algorithm.
+    pub compress_level: Option<i32>,
+    /// Pre-trained dictionary used for zstd compression.
+    pub zstd_dictionary: Option<Vec<u8>>,
+    /// Which tables to dump.
+    pub tables_filter: TableFilter,
+    /// Dump the results of these queries instead of the tables, each to a
+    /// file named after the query.
+    pub queries: Vec<NamedQuery>,
+    /// Dump views in addition to tables.
+    pub include_views: bool,
+    /// Dump only views.
+    pub views_only: bool,
+    /// Dump the `sqlite_*` internal tables too, e.g. `sqlite_sequence`.
+    pub include_system_tables: bool,
+    /// Write the DDL of each table instead of its rows.
+    pub schema_only: bool,
+    /// With `schema_only`, write all DDL into a single `schema.sql`.
+    pub schema_file: bool,
+    /// Write the indexes of the tables to `_indexes.csv` and
+    /// `_index_columns.csv`. With `schema_only`, also include them in the DDL.
+    /// The metadata files are replaced even with `append`.
+    pub include_indexes: bool,
+    /// Write the triggers of the tables to `_triggers.csv`. With
+    /// `schema_only`, also include them in the DDL.
+    pub include_triggers: bool,
+    /// Write the foreign keys of the tables to `_foreign_keys.csv`.
+    pub include_foreign_keys: bool,
+    /// Columns dumped for single tables, keyed by table name. All columns
+    /// for tables not listed.
+    pub columns: HashMap<String, Vec<String>>,
+    /// Columns left out of every table, compared case-insensitively.
+    pub exclude_columns: Vec<String>,
+    /// Columns left out of single tables, keyed by table name.
+    pub table_exclude_columns: HashMap<String, Vec<String>>,
+    /// Columns written first for single tables, in this order, keyed by table
+    /// name. The other columns follow in their natural order.
+    pub column_order: HashMap<String, Vec<String>>,
+    /// Tables dumped in wide format, keyed by table name: one row per value
+    /// of the timestamp column and one column per distinct key. The values of
+    /// rows with a NULL key are dropped.
+    pub pivot: HashMap<String, Pivot>,
+    /// Integer 0/1 columns written as `false`/`true`, keyed by table name.
+    pub bool_columns: HashMap<String, Vec<String>>,
+    /// Write the text `false`/`true` of `bool_columns` as 0/1 instead.
+    pub bool_as_int: bool,
+    /// Columns whose values are replaced with `[REDACTED]`.
+    pub redact_columns: Vec<RedactColumn>,
+    /// Replace the parts of TEXT values matching this in every table.
+    pub redact_pattern: Option<regex::Regex>,
+    /// Write the SHA-256 hex digest of redacted values instead of
+    /// `[REDACTED]`, so that equal values stay equal.
+    pub redact_hash: bool,
+    /// Write the columns in alphabetical order, ignoring case.
+    pub sort_columns: bool,
+    /// Add the `rowid` as the first column of tables that have one.
+    pub include_rowid: bool,
+    /// Add a `_row_number` column before all others, counting the rows of
+    /// each table from 1.
+    pub row_number: bool,
+    /// Dump only the rows with a rowid above this value, per table.
+    pub since_rowid: HashMap<String, i64>,
+    /// JSON file with the largest rowid dumped of each table. It is read
+    /// into `since_rowid` before the dump and updated after it.
+    pub rowid_state_file: Option<String>,
+    /// Dump only the rows whose timestamp column is above this time, compared
+    /// as an epoch value in `epoch_unit`. Tables without a timestamp column
+    /// are dumped fully.
+    pub since_timestamp: Option<chrono::DateTime<chrono::Utc>>,
+    /// Epoch lower bounds of the timestamp column of single tables, keyed by
+    /// table name. The later of this and `since_timestamp` is used.
+    pub table_since_timestamp: HashMap<String, i64>,
+    /// JSON file with the largest timestamp dumped of each table. It is read
+    /// into `table_since_timestamp` before the dump and updated after it.
+    pub timestamp_state_file: Option<String>,
+    /// SQL predicate applied to every table.
+    pub where_clause: Option<String>,
+    /// SQL predicates applied to single tables, keyed by table name.
+    pub table_where: HashMap<String, String>,
+    /// SQL ordering terms for the rows of each table.
+    pub order_by: Option<String>,
+    /// Order rows by `rowid` before `order_by`.
+    pub order_by_rowid: bool,
+    /// Maximum number of rows dumped per table.
+    pub limit: Option<u64>,
+    /// Number of rows skipped at the start of each table.
+    pub offset: Option<u64>,
+    /// Dump a random sample of this many rows of each table.
+    pub sample: Option<u64>,
+    /// Seed of a repeatable `sample`, for tables with a rowid.
+    pub sample_seed: Option<u32>,
+    /// Skip the CSV header row.
+    pub no_header: bool,
+    /// Append rows to existing output files instead of failing when they exist.
+    /// The header is only written to empty files.
+    pub append: bool,
+    /// Truncate and rewrite existing output files instead of failing when they exist.
+    pub overwrite: bool,
+    /// Show progress bars while dumping.
+    pub progress: bool,
+    /// Stops the dump once cancelled. Tables being written stop at their
+    /// next row and fail with [`DumperError::Cancelled`], tables not started
+    /// yet fail right away.
+    pub cancel: tokio_util::sync::CancellationToken,
+    /// Stop the other tables once this many tables failed: tables being
+    /// written are cancelled and the ones not started yet are not dumped.
+    /// `None` dumps every table whatever the failures.
+    pub max_errors: Option<usize>,
+    /// Rename the output files of tables that fail or are cancelled while
+    /// they are written to `<name>.partial` instead of deleting them.
+    pub keep_partial: bool,
+    /// Dump a table again up to this many times when the database is busy
+    /// or locked. Tables rows are appended to are not retried.
+    pub retry: u32,
+    /// Wait between two attempts of a busy table.
+    pub retry_delay: std::time::Duration,
+    /// Dump the tables one after another instead of one task per table.
+    pub sequential: bool,
+    /// Dump at most this many tables at the same time. `None` dumps all
+    /// tables at once.
+    pub parallel_tables: Option<usize>,
+    /// Text written for SQL NULL in text based formats.
+    pub null_value: String,
+    /// TEXT values longer than this many bytes are truncated.
+    pub max_cell_size: Option<usize>,
+    /// Appended to truncated TEXT values.
+    pub truncation_marker: String,
+    /// BLOB values larger than this many bytes are written to `blob_dir`,
+    /// or truncated without one.
+    pub max_blob_size: Option<usize>,
+    /// Directory BLOBs are written to, one file per value. Only oversized
+    /// BLOBs when `max_blob_size` is set.
+    pub blob_dir: Option<String>,
+    /// With `blob_dir`, BLOBs smaller than this many bytes stay in the cell.
+    pub blob_inline_threshold: usize,
+    /// Encoding of BLOB values. Hex when `None`, base64 in JSON.
+    pub blob_format: Option<BlobFormat>,
+    /// Decimal places of REAL values. Rust's default formatting when `None`.
+    pub float_precision: Option<usize>,
+    /// Notation of REAL values.
+    pub float_format: FloatFormat,
+    /// Write NaN as `nan_value` and infinite REAL values as `inf_value` or
+    /// `-` followed by it in text based formats, instead of `NaN`, `inf` and
+    /// `-inf`.
+    pub normalize_floats: bool,
+    /// Text written for NaN with `normalize_floats`, empty by default.
+    pub nan_value: String,
+    /// Text written for positive infinity with `normalize_floats`, and after
+    /// a `-` for negative infinity. `Inf` by default.
+    pub inf_value: String,
+    /// Columns parsed as timestamps in addition to `sm_timestamp` and `timestamp`.
+    pub timestamp_columns: Vec<String>,
+    /// Unit of integer epoch timestamps.
+    pub epoch_unit: EpochUnit,
+    /// `chrono` format string for parsed timestamps. RFC 3339 when `None`.
+    pub timestamp_format: Option<String>,
+    /// Time zone parsed timestamps are converted to. UTC when `None`.
+    pub timestamp_tz: Option<chrono_tz::Tz>,
+    /// Add a `<column>__<key>` column for each key of JSON object columns,
+    /// found in the first rows of each table.
+    pub flatten_json: bool,
+    /// Levels of nested objects flattened with `flatten_json`.
+    pub flatten_json_depth: usize,
+}
+
+impl Default for DumpConfig {
+    fn default() -> Self {
+        DumpConfig {
+            db_path: String::new(),
+            pragmas: Vec::new(),
+            mmap_size: 0,
+            cache_size: None,
+            attach: Vec::new(),
+            compare_db: None,
+            vacuum_first: false,
+            backup: None,
+            keep_backup: false,
+            output_dir: "sqlite_dump".to_string(),
+            output_pattern: "{table}.{ext}".to_string(),
+            rows_per_file: None,
+            output_manifest: None,
+            checksum: None,
+            output_format: OutputFormat::Csv,
+            parquet_row_group_size: 100_000,
+            sql_dialect: SqlDialect::Sqlite,
+            markdown_align: HashMap::new(),
+            html_class: None,
+            html_template: None,
+            delimiter: b',',
+            csv_bom: false,
+            line_ending: LineEnding::default(),
+            quote_char: b'"',
+            escape_char: b'"',
+            quoting: Quoting::default(),
+            encoding: None,
+            output_zip: None,
+            output_zip_password: None,
+            output_tar: None,
+            single_file: None,
+            compression: Compression::None,
+            compress_level: None,
+            zstd_dictionary: None,
+            tables_filter: TableFilter::default(),
+            queries: Vec::new(),
+            include_views: false,
+            include_system_tables: false,
+            views_only: false,
+            schema_only: false,
+            schema_file: false,
+            include_indexes: false,
+            include_triggers: false,
+            include_foreign_keys: false,
+            columns: HashMap::new(),
+            exclude_columns: Vec::new(),
+            table_exclude_columns: HashMap::new(),
+            column_order: HashMap::new(),
+            pivot: HashMap::new(),
+            bool_columns: HashMap::new(),
+            bool_as_int: false,
+            redact_columns: Vec::new(),
+            redact_pattern: None,
+            redact_hash: false,
+            sort_columns: false,
+            include_rowid: false,
+            row_number: false,
+            since_rowid: HashMap::new(),
+            rowid_state_file: None,
+            since_timestamp: None,
+            table_since_timestamp: HashMap::new(),
+            timestamp_state_file: None,
+            where_clause: None,
+            table_where: HashMap::new(),
+            order_by: None,
+            order_by_rowid: false,
+            limit: None,
+            offset: None,
+            sample: None,
+            sample_seed: None,
+            no_header: false,
+            append: false,
+            overwrite: false,
+            progress: false,
+            cancel: tokio_util::sync::CancellationToken::new(),
+            max_errors: None,
+            keep_partial: false,
+            retry: 0,
+            retry_delay: std::time::Duration::from_secs(1),
+            sequential: false,
+            parallel_tables: None,
+            null_value: "null".to_string(),
+            max_cell_size: None,
+            truncation_marker: "…".to_string(),
+            max_blob_size: None,
+            blob_dir: None,
+            blob_inline_threshold: 0,
+            blob_format: None,
+            float_precision: None,
+            float_format: FloatFormat::default(),
+            normalize_floats: false,
+            nan_value: String::new(),
+            inf_value: "Inf".to_string(),
+            timestamp_columns: Vec::new(),
+            epoch_unit: EpochUnit::default(),
+            timestamp_format: None,
+            timestamp_tz: None,
+            flatten_json: false,
+            flatten_json_depth: 1,
+        }
+    }
+}
+
+impl DumpConfig {
+    /// Start building a config. Only `db_path` is required.
+    pub fn builder() -> DumpConfigBuilder {
+        DumpConfigBuilder::default()
+    }
+
+    /// Compression wrapped around whole output files. Parquet files
+    /// compress their pages themselves.
+    pub(crate) fn file_compression(&self) -> Compression {
+        match self.output_format {
+            OutputFormat::Parquet => Compression::None,
+            _ => self.compression,
+        }
+    }
+}
+
+/// Builder for [`DumpConfig`]. Only `db_path` is required.
+#[derive(Debug, Clone, Default)]
+pub struct DumpConfigBuilder {
+    config: DumpConfig,
+}
+
+impl DumpConfigBuilder {
+    /// Set [`DumpConfig::db_path`].
+    pub fn db_path(mut self, db_path: impl Into<String>) -> Self {
+        self.config.db_path = db_path.into();
+        self
+    }
+
+    /// Set [`DumpConfig::pragmas`].
+    pub fn pragmas(mut self, pragmas: Vec<(String, String)>) -> Self {
+        self.config.pragmas = pragmas;
+        self
+    }
+
+    /// Set [`DumpConfig::mmap_size`].
+    pub fn mmap_size(mut self, mmap_size: u64) -> Self {
+        self.config.mmap_size = mmap_size;
+        self
+    }
+
+    /// Set [`DumpConfig::cache_size`].
+    pub fn cache_size(mut self, cache_size: Option<i64>) -> Self {
+        self.config.cache_size = cache_size;
+        self
+    }
+
+    /// Set [`DumpConfig::attach`].
+    pub fn attach(mut self, attach: Vec<(String, String)>) -> Self {
+        self.config.attach = attach;
+        self
+    }
+
+    /// Set [`DumpConfig::compare_db`].
+    pub fn compare_db(mut self, compare_db: Option<String>) -> Self {
+        self.config.compare_db = compare_db;
+        self
+    }
+
+    /// Set [`DumpConfig::vacuum_first`].
+    pub fn vacuum_first(mut self, vacuum_first: bool) -> Self {
+        self.config.vacuum_first = vacuum_first;
+        self
+    }
+
+    /// Set [`DumpConfig::backup`].
+    pub fn backup(mut self, backup: Option<String>) -> Self {
+        self.config.backup = backup;
+        self
+    }
+
+    /// Set [`DumpConfig::keep_backup`].
+    pub fn keep_backup(mut self, keep_backup: bool) -> Self {
+        self.config.keep_backup = keep_backup;
+        self
+    }
+
+    /// Set [`DumpConfig::output_dir`].
+    pub fn output_dir(mut self, output_dir: impl Into<String>) -> Self {
+        self.config.output_dir = output_dir.into();
+        self
+    }
+
+    /// Set [`DumpConfig::output_pattern`].
+    pub fn output_pattern(mut self, output_pattern: impl Into<String>) -> Self {
+        self.config.output_pattern = output_pattern.into();
+        self
+    }
+
+    /// Set [`DumpConfig::rows_per_file`].
+    pub fn rows_per_file(mut self, rows_per_file: Option<u64>) -> Self {
+        self.config.rows_per_file = rows_per_file;
+        self
+    }
+
+    /// Set [`DumpConfig::output_manifest`].
+    pub fn output_manifest(mut self, output_manifest: Option<String>) -> Self {
+        self.config.output_manifest = output_manifest;
+        self
+    }
+
+    /// Set [`DumpConfig::checksum`].
+    pub fn checksum(mut self, checksum: Option<ChecksumAlgorithm>) -> Self {
+        self.config.checksum = checksum;
+        self
+    }
+
+    /// Set [`DumpConfig::output_format`].
+    pub fn output_format(mut self, output_format: OutputFormat) -> Self {
+        self.config.output_format = output_format;
+        self
+    }
+
+    /// Set [`DumpConfig::parquet_row_group_size`].
+    pub fn parquet_row_group_size(mut self, parquet_row_group_size: usize) -> Self {
+        self.config.parquet_row_group_size = parquet_row_group_size;
+        self
+    }
+
+    /// Set [`DumpConfig::sql_dialect`].
+    pub fn sql_dialect(mut self, sql_dialect: SqlDialect) -> Self {
+        self.config.sql_dialect = sql_dialect;
+        self
+    }
+
+    /// Set [`DumpConfig::markdown_align`].
+    pub fn markdown_align(mut self, markdown_align: HashMap<String, MarkdownAlign>) -> Self {
+        self.config.markdown_align = markdown_align;
+        self
+    }
+
+    /// Set [`DumpConfig::html_class`].
+    pub fn html_class(mut self, html_class: Option<String>) -> Self {
+        self.config.html_class = html_class;
+        self
+    }
+
+    /// Set [`DumpConfig::html_template`].
+    pub fn html_template(mut self, html_template: Option<String>) -> Self {
+        self.config.html_template = html_template;
+        self
+    }
+
+    /// Set [`DumpConfig::delimiter`].
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.config.delimiter = delimiter;
+        self
+    }
+
+    /// Set [`DumpConfig::csv_bom`].
+    pub fn csv_bom(mut self, csv_bom: bool) -> Self {
+        self.config.csv_bom = csv_bom;
+        self
+    }
+
+    /// Set [`DumpConfig::line_ending`].
+    pub fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.config.line_ending = line_ending;
+        self
+    }
+
+    /// Set [`DumpConfig::quote_char`].
+    pub fn quote_char(mut self, quote_char: u8) -> Self {
+        self.config.quote_char = quote_char;
+        self
+    }
+
+    /// Set [`DumpConfig::escape_char`].
+    pub fn escape_char(mut self, escape_char: u8) -> Self {
+        self.config.escape_char = escape_char;
+        self
+    }
+
+    /// Set [`DumpConfig::quoting`].
+    pub fn quoting(mut self, quoting: Quoting) -> Self {
+        self.config.quoting = quoting;
+        self
+    }
+
+    /// Set [`DumpConfig::encoding`].
+    pub fn encoding(mut self, encoding: Option<&'static encoding_rs::Encoding>) -> Self {
+        self.config.encoding = encoding;
+        self
+    }
+
+    /// Set [`DumpConfig::output_zip`].
+    pub fn output_zip(mut self, output_zip: Option<String>) -> Self {
+        self.config.output_zip = output_zip;
+        self
+    }
+
+    /// Set [`DumpConfig::output_zip_password`].
+    pub fn output_zip_password(mut self, output_zip_password: Option<ZipPassword>) -> Self {
+        self.config.output_zip_password = output_zip_password;
+        self
+    }
+
+    /// Set [`DumpConfig::single_file`].
+    pub fn single_file(mut self, single_file: Option<String>) -> Self {
+        self.config.single_file = single_file;
+        self
+    }
+
+    /// Set [`DumpConfig::output_tar`].
+    pub fn output_tar(mut self, output_tar: Option<String>) -> Self {
+        self.config.output_tar = output_tar;
+        self
+    }
+
+    /// Set [`DumpConfig::compression`].
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.config.compression = compression;
+        self
+    }
+
+    /// Set [`DumpConfig::compress_level`].
+    pub fn compress_level(mut self, compress_level: Option<i32>) -> Self {
+        self.config.compress_level = compress_level;
+        self
+    }
+
+    /// Set [`DumpConfig::zstd_dictionary`].
+    pub fn zstd_dictionary(mut self, zstd_dictionary: Option<Vec<u8>>) -> Self {
+        self.config.zstd_dictionary = zstd_dictionary;
+        self
+    }
+
+    /// Set [`DumpConfig::tables_filter`].
+    pub fn tables_filter(mut self, tables_filter: TableFilter) -> Self {
+        self.config.tables_filter = tables_filter;
+        self
+    }
+
+    /// Set [`DumpConfig::queries`].
+    pub fn queries(mut self, queries: Vec<NamedQuery>) -> Self {
+        self.config.queries = queries;
+        self
+    }
+
+    /// Set [`DumpConfig::include_views`].
+    pub fn include_views(mut self, include_views: bool) -> Self {
+        self.config.include_views = include_views;
+        self
+    }
+
+    /// Set [`DumpConfig::include_system_tables`].
+    pub fn include_system_tables(mut self, include_system_tables: bool) -> Self {
+        self.config.include_system_tables = include_system_tables;
+        self
+    }
+
+    /// Set [`DumpConfig::views_only`].
+    pub fn views_only(mut self, views_only: bool) -> Self {
+        self.config.views_only = views_only;
+        self
+    }
+
+    /// Set [`DumpConfig::schema_only`].
+    pub fn schema_only(mut self, schema_only: bool) -> Self {
+        self.config.schema_only = schema_only;
+        self
+    }
+
+    /// Set [`DumpConfig::schema_file`].
+    pub fn schema_file(mut self, schema_file: bool) -> Self {
+        self.config.schema_file = schema_file;
+        self
+    }
+
+    /// Set [`DumpConfig::include_indexes`].
+    pub fn include_indexes(mut self, include_indexes: bool) -> Self {
+        self.config.include_indexes = include_indexes;
+        self
+    }
+
+    /// Set [`DumpConfig::include_triggers`].
+    pub fn include_triggers(mut self, include_triggers: bool) -> Self {
+        self.config.include_triggers = include_triggers;
+        self
+    }
+
+    /// Set [`DumpConfig::include_foreign_keys`].
+    pub fn include_foreign_keys(mut self, include_foreign_keys: bool) -> Self {
+        self.config.include_foreign_keys = include_foreign_keys;
+        self
+    }
+
+    /// Set [`DumpConfig::columns`].
+    pub fn columns(mut self, columns: HashMap<String, Vec<String>>) -> Self {
+        self.config.columns = columns;
+        self
+    }
+
+    /// Set [`DumpConfig::exclude_columns`].
+    pub fn exclude_columns(mut self, exclude_columns: Vec<String>) -> Self {
+        self.config.exclude_columns = exclude_columns;
+        self
+    }
+
+    /// Set [`DumpConfig::table_exclude_columns`].
+    pub fn table_exclude_columns(
+        mut self,
+        table_exclude_columns: HashMap<String, Vec<String>>,
+    ) -> Self {
+        self.config.table_exclude_columns = table_exclude_columns;
+        self
+    }
+
+    /// Set [`DumpConfig::column_order`].
+    pub fn column_order(mut self, column_order: HashMap<String, Vec<String>>) -> Self {
+        self.config.column_order = column_order;
+        self
+    }
+
+    /// Set [`DumpConfig::pivot`].
+    pub fn pivot(mut self, pivot: HashMap<String, Pivot>) -> Self {
+        self.config.pivot = pivot;
+        self
+    }
+
+    /// Set [`DumpConfig::bool_columns`].
+    pub fn bool_columns(mut self, bool_columns: HashMap<String, Vec<String>>) -> Self {
+        self.config.bool_columns = bool_columns;
+        self
+    }
+
+    /// Set [`DumpConfig::bool_as_int`].
+    pub fn bool_as_int(mut self, bool_as_int: bool) -> Self {
+        self.config.bool_as_int = bool_as_int;
+        self
+    }
+
+    /// Set [`DumpConfig::redact_columns`].
+    pub fn redact_columns(mut self, redact_columns: Vec<RedactColumn>) -> Self {
+        self.config.redact_columns = redact_columns;
+        self
+    }
+
+    /// Set [`DumpConfig::redact_pattern`].
+    pub fn redact_pattern(mut self, redact_pattern: Option<regex::Regex>) -> Self {
+        self.config.redact_pattern = redact_pattern;
+        self
+    }
+
+    /// Set [`DumpConfig::redact_hash`].
+    pub fn redact_hash(mut self, redact_hash: bool) -> Self {
+        self.config.redact_hash = redact_hash;
+        self
+    }
+
+    /// Set [`DumpConfig::sort_columns`].
+    pub fn sort_columns(mut self, sort_columns: bool) -> Self {
+        self.config.sort_columns = sort_columns;
+        self
+    }
+
+    /// Set [`DumpConfig::include_rowid`].
+    pub fn include_rowid(mut self, include_rowid: bool) -> Self {
+        self.config.include_rowid = include_rowid;
+        self
+    }
+
+    /// Set [`DumpConfig::row_number`].
+    pub fn row_number(mut self, row_number: bool) -> Self {
+        self.config.row_number = row_number;
+        self
+    }
+
+    /// Set [`DumpConfig::since_rowid`].
+    pub fn since_rowid(mut self, since_rowid: HashMap<String, i64>) -> Self {
+        self.config.since_rowid = since_rowid;
+        self
+    }
+
+    /// Set [`DumpConfig::rowid_state_file`].
+    pub fn rowid_state_file(mut self, rowid_state_file: Option<String>) -> Self {
+        self.config.rowid_state_file = rowid_state_file;
+        self
+    }
+
+    /// Set [`DumpConfig::since_timestamp`].
+    pub fn since_timestamp(
+        mut self,
+        since_timestamp: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Self {
+        self.config.since_timestamp = since_timestamp;
+        self
+    }
+
+    /// Set [`DumpConfig::table_since_timestamp`].
+    pub fn table_since_timestamp(mut self, table_since_timestamp: HashMap<String, i64>) -> Self {
+        self.config.table_since_timestamp = table_since_timestamp;
+        self
+    }
+
+    /// Set [`DumpConfig::timestamp_state_file`].
+    pub fn timestamp_state_file(mut self, timestamp_state_file: Option<String>) -> Self {
+        self.config.timestamp_state_file = timestamp_state_file;
+        self
+    }
+
+    /// Set [`DumpConfig::where_clause`].
+    pub fn where_clause(mut self, where_clause: Option<String>) -> Self {
+        self.config.where_clause = where_clause;
+        self
+    }
+
+    /// Set [`DumpConfig::table_where`].
+    pub fn table_where(mut self, table_where: HashMap<String, String>) -> Self {
+        self.config.table_where = table_where;
+        self
+    }
+
+    /// Set [`DumpConfig::order_by`].
+    pub fn order_by(mut self, order_by: Option<String>) -> Self {
+        self.config.order_by = order_by;
+        self
+    }
+
+    /// Set [`DumpConfig::order_by_rowid`].
+    pub fn order_by_rowid(mut self, order_by_rowid: bool) -> Self {
+        self.config.order_by_rowid = order_by_rowid;
+        self
+    }
+
+    /// Set [`DumpConfig::limit`].
+    pub fn limit(mut self, limit: Option<u64>) -> Self {
+        self.config.limit = limit;
+        self
+    }
+
+    /// Set [`DumpConfig::offset`].
+    pub fn offset(mut self, offset: Option<u64>) -> Self {
+        self.config.offset = offset;
+        self
+    }
+
+    /// Set [`DumpConfig::sample`].
+    pub fn sample(mut self, sample: Option<u64>) -> Self {
+        self.config.sample = sample;
+        self
+    }
+
+    /// Set [`DumpConfig::sample_seed`].
+    pub fn sample_seed(mut self, sample_seed: Option<u32>) -> Self {
+        self.config.sample_seed = sample_seed;
+        self
+    }
+
+    /// Set [`DumpConfig::no_header`].
+    pub fn no_header(mut self, no_header: bool) -> Self {
+        self.config.no_header = no_header;
+        self
+    }
+
+    /// Set [`DumpConfig::append`].
+    pub fn append(mut self, append: bool) -> Self {
+        self.config.append = append;
+        self
+    }
+
+    /// Set [`DumpConfig::overwrite`].
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.config.overwrite = overwrite;
+        self
+    }
+
+    /// Set [`DumpConfig::progress`].
+    pub fn progress(mut self, progress: bool) -> Self {
+        self.config.progress = progress;
+        self
+    }
+
+    /// Set [`DumpConfig::cancel`].
+    pub fn cancel(mut self, cancel: tokio_util::sync::CancellationToken) -> Self {
+        self.config.cancel = cancel;
+        self
+    }
+
+    /// Set [`DumpConfig::max_errors`].
+    pub fn max_errors(mut self, max_errors: Option<usize>) -> Self {
+        self.config.max_errors = max_errors;
+        self
+    }
+
+    /// Set [`DumpConfig::keep_partial`].
+    pub fn keep_partial(mut self, keep_partial: bool) -> Self {
+        self.config.keep_partial = keep_partial;
+        self
+    }
+
+    /// Set [`DumpConfig::retry`].
+    pub fn retry(mut self, retry: u32) -> Self {
+        self.config.retry = retry;
+        self
+    }
+
+    /// Set [`DumpConfig::retry_delay`].
+    pub fn retry_delay(mut self, retry_delay: std::time::Duration) -> Self {
+        self.config.retry_delay = retry_delay;
+        self
+    }
+
+    /// Set [`DumpConfig::sequential`].
+    pub fn sequential(mut self, sequential: bool) -> Self {
+        self.config.sequential = sequential;
+        self
+    }
+
+    /// Set [`DumpConfig::parallel_tables`].
+    pub fn parallel_tables(mut self, parallel_tables: Option<usize>) -> Self {
+        self.config.parallel_tables = parallel_tables;
+        self
+    }
+
+    /// Set [`DumpConfig::null_value`].
+    pub fn null_value(mut self, null_value: impl Into<String>) -> Self {
+        self.config.null_value = null_value.into();
+        self
+    }
+
+    /// Set [`DumpConfig::max_cell_size`].
+    pub fn max_cell_size(mut self, max_cell_size: Option<usize>) -> Self {
+        self.config.max_cell_size = max_cell_size;
+        self
+    }
+
+    /// Set [`DumpConfig::truncation_marker`].
+    pub fn truncation_marker(mut self, truncation_marker: impl Into<String>) -> Self {
+        self.config.truncation_marker = truncation_marker.into();
+        self
+    }
+
+    /// Set [`DumpConfig::max_blob_size`].
+    pub fn max_blob_size(mut self, max_blob_size: Option<usize>) -> Self {
+        self.config.max_blob_size = max_blob_size;
+        self
+    }
+
+    /// Set [`DumpConfig::blob_dir`].
+    pub fn blob_dir(mut self, blob_dir: Option<String>) -> Self {
+        self.config.blob_dir = blob_dir;
+        self
+    }
+
+    /// Set [`DumpConfig::blob_inline_threshold`].
+    pub fn blob_inline_threshold(mut self, blob_inline_threshold: usize) -> Self {
+        self.config.blob_inline_threshold = blob_inline_threshold;
+        self
+    }
+
+    /// Set [`DumpConfig::blob_format`].
+    pub fn blob_format(mut self, blob_format: Option<BlobFormat>) -> Self {
+        self.config.blob_format = blob_format;
+        self
+    }
+
+    /// Set [`DumpConfig::float_precision`].
+    pub fn float_precision(mut self, float_precision: Option<usize>) -> Self {
+        self.config.float_precision = float_precision;
+        self
+    }
+
+    /// Set [`DumpConfig::float_format`].
+    pub fn float_format(mut self, float_format: FloatFormat) -> Self {
+        self.config.float_format = float_format;
+        self
+    }
+
+    /// Set [`DumpConfig::normalize_floats`].
+    pub fn normalize_floats(mut self, normalize_floats: bool) -> Self {
+        self.config.normalize_floats = normalize_floats;
+        self
+    }
+
+    /// Set [`DumpConfig::nan_value`].
+    pub fn nan_value(mut self, nan_value: impl Into<String>) -> Self {
+        self.config.nan_value = nan_value.into();
+        self
+    }
+
+    /// Set [`DumpConfig::inf_value`].
+    pub fn inf_value(mut self, inf_value: impl Into<String>) -> Self {
+        self.config.inf_value = inf_value.into();
+        self
+    }
+
+    /// Set [`DumpConfig::timestamp_columns`].
+    pub fn timestamp_columns(mut self, timestamp_columns: Vec<String>) -> Self {
+        self.config.timestamp_columns = timestamp_columns;
+        self
+    }
+
+    /// Set [`DumpConfig::epoch_unit`].
+    pub fn epoch_unit(mut self, epoch_unit: EpochUnit) -> Self {
+        self.config.epoch_unit = epoch_unit;
+        self
+    }
+
+    /// Set [`DumpConfig::timestamp_format`].
+    pub fn timestamp_format(mut self, timestamp_format: Option<String>) -> Self {
+        self.config.timestamp_format = timestamp_format;
+        self
+    }
+
+    /// Set [`DumpConfig::timestamp_tz`].
+    pub fn timestamp_tz(mut self, timestamp_tz: Option<chrono_tz::Tz>) -> Self {
+        self.config.timestamp_tz = timestamp_tz;
+        self
+    }
+
+    /// Set [`DumpConfig::flatten_json`].
+    pub fn flatten_json(mut self, flatten_json: bool) -> Self {
+        self.config.flatten_json = flatten_json;
+        self
+    }
+
+    /// Set [`DumpConfig::flatten_json_depth`].
+    pub fn flatten_json_depth(mut self, flatten_json_depth: usize) -> Self {
+        self.config.flatten_json_depth = flatten_json_depth;
+        self
+    }
+
+    /// Check the settings and build the config. Settings that cannot be used
+    /// together fail with [`DumperError::InvalidConfig`].
+    pub fn build(self) -> Result<DumpConfig, DumperError> {
+        if self.config.db_path.is_empty() {
+            return Err(DumperError::InvalidConfig(
+                "db_path is required".to_string(),
+            ));
+        }
+        if !self.config.output_pattern.contains("{table}") {
+            return Err(DumperError::InvalidConfig(
+                "output pattern must contain {table}".to_string(),
+            ));
+        }
+        if self.config.zstd_dictionary.is_some() && self.config.compression != Compression::Zstd {
+            return Err(DumperError::InvalidConfig(
+                "a zstd dictionary requires zstd compression".to_string(),
+            ));
+        }
+        if self.config.output_zip_password.is_some() && self.config.output_zip.is_none() {
+            return Err(DumperError::InvalidConfig(
+                "a ZIP password requires a ZIP archive output".to_string(),
+            ));
+        }
+        if self.config.output_zip.is_some() && self.config.compression == Compression::Lz4 {
+            return Err(DumperError::InvalidConfig(
+                "lz4 is not supported inside ZIP archives".to_string(),
+            ));
+        }
+        if let Some(path) = &self.config.output_tar {
+            crate::archive::TarCompression::from_path(path)?;
+        }
+        if self.config.append && self.config.overwrite {
+            return Err(DumperError::InvalidConfig(
+                "append and overwrite cannot be used together".to_string(),
+            ));
+        }
+        if self.config.append && self.config.output_format == OutputFormat::Json {
+            return Err(DumperError::InvalidConfig(
+                "append cannot be used with the json format, use jsonl instead".to_string(),
+            ));
+        }
+        if self.config.append
+            && matches!(
+                self.config.output_format,
+                OutputFormat::Parquet | OutputFormat::Arrow
+            )
+        {
+            return Err(DumperError::InvalidConfig(
+                "append cannot be used with the parquet and arrow formats".to_string(),
+            ));
+        }
+        if self.config.quote_char == self.config.delimiter
+            || self.config.escape_char == self.config.delimiter
+        {
+            return Err(DumperError::InvalidConfig(
+                "the CSV quote and escape characters must differ from the delimiter".to_string(),
+            ));
+        }
+        if let Some(encoding) = self.config.encoding {
+            if self.config.output_format != OutputFormat::Csv {
+                return Err(DumperError::InvalidConfig(
+                    "an output encoding can only be used with the csv format".to_string(),
+                ));
+            }
+            if encoding.output_encoding() != encoding {
+                return Err(DumperError::InvalidConfig(format!(
+                    "text cannot be written in {}",
+                    encoding.name()
+                )));
+            }
+            if self.config.csv_bom && encoding != encoding_rs::UTF_8 {
+                return Err(DumperError::InvalidConfig(format!(
+                    "the UTF-8 byte order mark cannot start a {} file",
+                    encoding.name()
+                )));
+            }
+        }
+        if self.config.append && self.config.output_format == OutputFormat::Html {
+            return Err(DumperError::InvalidConfig(
+                "append cannot be used with the html format".to_string(),
+            ));
+        }
+        if self.config.output_format == OutputFormat::Sql && self.config.single_file.is_some() {
+            return Err(DumperError::InvalidConfig(
+                "the sql format writes one table per file, not a single file".to_string(),
+            ));
+        }
+        if self.config.output_format == OutputFormat::Parquet
+            && self.config.zstd_dictionary.is_some()
+        {
+            return Err(DumperError::InvalidConfig(
+                "a zstd dictionary cannot be used with the parquet format".to_string(),
+            ));
+        }
+        if self.config.parquet_row_group_size == 0 {
+            return Err(DumperError::InvalidConfig(
+                "parquet row group size must be greater than 0".to_string(),
+            ));
+        }
+        if self.config.single_file.is_some()
+            && (self.config.output_zip.is_some()
+                || self.config.output_tar.is_some()
+                || self.config.rows_per_file.is_some())
+        {
+            return Err(DumperError::InvalidConfig(
+                "single file output cannot be used with archives or rows per file".to_string(),
+            ));
+        }
+        let hashed = self.config.output_manifest.is_some() || self.config.checksum.is_some();
+        if hashed
+            && (self.config.output_zip.is_some()
+                || self.config.output_tar.is_some()
+                || self.config.single_file.is_some())
+        {
+            return Err(DumperError::InvalidConfig(
+                "the output manifest and checksums require one file per table, not archives or a single file"
+                    .to_string(),
+            ));
+        }
+        if hashed && self.config.append {
+            return Err(DumperError::InvalidConfig(
+                "the output manifest and checksums cannot be used with append".to_string(),
+            ));
+        }
+        let metadata = self.config.include_indexes
+            || self.config.include_triggers
+            || self.config.include_foreign_keys;
+        if metadata && (self.config.output_zip.is_some() || self.config.output_tar.is_some()) {
+            return Err(DumperError::InvalidConfig(
+                "indexes, triggers and foreign keys are written to the output directory and cannot be used with archives"
+                    .to_string(),
+            ));
+        }
+        crate::named_query::validate_names(&self.config.queries)?;
+        if !self.config.queries.is_empty() && self.config.schema_only {
+            return Err(DumperError::InvalidConfig(
+                "queries cannot be dumped with schema only".to_string(),
+            ));
+        }
+        if self.config.backup.is_some() && self.config.vacuum_first {
+            return Err(DumperError::InvalidConfig(
+                "a backup cannot be used with vacuum first".to_string(),
+            ));
+        }
+        if self.config.compare_db.is_some()
+            && (!self.config.queries.is_empty() || self.config.schema_only)
+        {
+            return Err(DumperError::InvalidConfig(
+                "a compared database cannot be used with queries or schema only".to_string(),
+            ));
+        }
+        if self.config.parallel_tables == Some(0) {
+            return Err(DumperError::InvalidConfig(
+                "parallel tables must be greater than 0".to_string(),
+            ));
+        }
+        if self.config.rows_per_file == Some(0) {
+            return Err(DumperError::InvalidConfig(
+                "rows per file must be greater than 0".to_string(),
+            ));
+        }
+        if self.config.limit == Some(0) {
+            return Err(DumperError::InvalidConfig(
+                "limit must be greater than 0".to_string(),
+            ));
+        }
+        if self.config.sample.is_some()
+            && (self.config.limit.is_some()
+                || self.config.offset.is_some()
+                || self.config.order_by.is_some()
+                || self.config.order_by_rowid)
+        {
+            return Err(DumperError::InvalidConfig(
+                "sample cannot be combined with limit, offset or an order".to_string(),
+            ));
+        }
+        if self.config.sort_columns && !self.config.column_order.is_empty() {
+            return Err(DumperError::InvalidConfig(
+                "sort columns cannot be combined with a column order".to_string(),
+            ));
+        }
+        if self.config.flatten_json_depth == 0 {
+            return Err(DumperError::InvalidConfig(
+                "flatten JSON depth must be greater than 0".to_string(),
+            ));
+        }
+        if self.config.sample == Some(0) {
+            return Err(DumperError::InvalidConfig(
+                "sample must be greater than 0".to_string(),
+            ));
+        }
+        if self.config.sample_seed.is_some() && self.config.sample.is_none() {
+            return Err(DumperError::InvalidConfig(
+                "sample seed requires a sample".to_string(),
+            ));
+        }
+        if let Some(format) = &self.config.timestamp_format {
+            crate::timestamp::validate_format(format)?;
+        }
+        crate::pragma::validate(&self.config.pragmas)?;
+        if let Some(path) = &self.config.html_template {
+            crate::formats::validate_template(path)?;
+        }
+        for (i, (alias, _)) in self.config.attach.iter().enumerate() {
+            let valid = !alias.is_empty()
+                && alias.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                && !["main", "temp", crate::delta::COMPARE_ALIAS]
+                    .contains(&alias.to_lowercase().as_str());
+            if !valid {
+                return Err(DumperError::InvalidConfig(format!(
+                    "invalid database alias '{alias}', use letters, digits and underscores other than main, temp and compare_db"
+                )));
+            }
+            if self.config.attach[..i]
+                .iter()
+                .any(|(other, _)| other == alias)
+            {
+                return Err(DumperError::InvalidConfig(format!(
+                    "database alias '{alias}' is used more than once"
+                )));
+            }
+        }
+        for clause in self
+            .config
+            .where_clause
+            .iter()
+            .chain(self.config.table_where.values())
+            .chain(self.config.order_by.iter())
+        {
+            crate::query::validate_sql_fragment(clause)?;
+        }
+        Ok(self.config)
+    }
+}
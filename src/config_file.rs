@@ -0,0 +1,218 @@
+use crate::error::DumperError;
+
+/// Per table keys of a config file: the key, the id of the argument it
+/// becomes and the separator between the table name and the value.
+const TABLE_KEYS: &[(&str, &str, char)] = &[
+    ("where", "table_where", '='),
+    ("columns", "columns", ':'),
+    ("exclude_columns", "exclude_columns_table", ':'),
+    ("column_order", "column_order", ':'),
+    ("pivot", "pivot", ':'),
+    ("bool_columns", "bool_columns", ':'),
+    ("since_rowid", "since_rowid", ':'),
+];
+
+/// Arguments that cannot be set in a config file.
+const COMMAND_LINE_ONLY: &[&str] = &[
+    "config_file",
+    "profile",
+    "dump_config",
+    "sqlite_version",
+    "help",
+    "version",
+];
+
+/// A TOML config file with the same settings as the command line
+/// arguments: a `[defaults]` section keyed by argument, with underscores or
+/// dashes, and `[table.<name>]` sections with the per table `where`,
+/// `columns`, `exclude_columns`, `column_order`, `pivot`, `bool_columns` and
+/// `since_rowid`.
+/// `[profile.<name>]` sections take the same keys as `[defaults]` and
+/// override them when selected.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigFile {
+    path: String,
+    defaults: toml::Table,
+    tables: toml::Table,
+    profiles: toml::Table,
+}
+
+/// Read and parse the config file at `path`.
+pub fn read_config_file(path: &str) -> Result<ConfigFile, DumperError> {
+    let text = std::fs::read_to_string(path)?;
+    let invalid = |message: String| DumperError::InvalidConfigFile {
+        path: path.to_string(),
+        message,
+    };
+    let mut root: toml::Table = text
+        .parse()
+        .map_err(|e: toml::de::Error| invalid(e.to_string()))?;
+    let mut section = |name: &str| match root.remove(name) {
+        None => Ok(toml::Table::new()),
+        Some(toml::Value::Table(table)) => Ok(table),
+        Some(_) => Err(invalid(format!("'{name}' must be a section"))),
+    };
+    let defaults = section("defaults")?;
+    let tables = section("table")?;
+    let profiles = section("profile")?;
+    if let Some(key) = root.keys().next() {
+        return Err(invalid(format!(
+            "unknown section '{key}', expected [defaults], [profile.<name>] or [table.<name>]"
+        )));
+    }
+    Ok(ConfigFile {
+        path: path.to_string(),
+        defaults,
+        tables,
+        profiles,
+    })
+}
+
+impl ConfigFile {
+    fn invalid(&self, message: String) -> DumperError {
+        DumperError::InvalidConfigFile {
+            path: self.path.clone(),
+            message,
+        }
+    }
+
+    /// Apply the settings of profile `name` over the defaults.
+    pub fn select_profile(&mut self, name: &str) -> Result<(), DumperError> {
+        match self.profiles.get(name) {
+            Some(toml::Value::Table(profile)) => {
+                let profile = profile.clone();
+                self.defaults.extend(profile);
+                Ok(())
+            }
+            Some(_) => Err(self.invalid(format!("'profile.{name}' must be a section"))),
+            None => {
+                let names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+                let known = if names.is_empty() {
+                    "it has no profiles".to_string()
+                } else {
+                    format!("its profiles are {}", names.join(", "))
+                };
+                Err(self.invalid(format!("no profile '{name}', {known}")))
+            }
+        }
+    }
+
+    /// The settings of the file as command line arguments of `command`, to
+    /// be parsed before the real ones. Settings of arguments for which
+    /// `given` is true are left out, so the command line replaces them,
+    /// lists included.
+    pub fn args(
+        &self,
+        command: &clap::Command,
+        given: impl Fn(&str) -> bool,
+    ) -> Result<Vec<String>, DumperError> {
+        let mut args = Vec::new();
+        for (key, value) in &self.defaults {
+            let id = key.replace('-', "_");
+            let arg = command
+                .get_arguments()
+                .find(|arg| {
+                    arg.get_id() == id.as_str() && !COMMAND_LINE_ONLY.contains(&id.as_str())
+                })
+                .ok_or_else(|| self.invalid(format!("unknown setting '{key}'")))?;
+            if given(&id) {
+                continue;
+            }
+            let long = arg.get_long().expect("every setting has a long option");
+            if !arg.get_action().takes_values() {
+                match value {
+                    toml::Value::Boolean(true) => args.push(format!("--{long}")),
+                    toml::Value::Boolean(false) => {}
+                    _ => return Err(self.invalid(format!("'{key}' must be true or false"))),
+                }
+                continue;
+            }
+            for value in self.values(key, value)? {
+                args.push(format!("--{long}={value}"));
+            }
+        }
+        for (table, settings) in &self.tables {
+            let toml::Value::Table(settings) = settings else {
+                return Err(self.invalid(format!("'table.{table}' must be a section")));
+            };
+            for (key, value) in settings {
+                let &(_, id, separator) = TABLE_KEYS
+                    .iter()
+                    .find(|(name, _, _)| name == key)
+                    .ok_or_else(|| self.invalid(format!("unknown table setting '{key}'")))?;
+                if given(id) {
+                    continue;
+                }
+                let arg = command
+                    .get_arguments()
+                    .find(|arg| arg.get_id() == id)
+                    .expect("every table setting is an argument");
+                let long = arg.get_long().expect("every setting has a long option");
+                let value = self.values(key, value)?.join(",");
+                args.push(format!("--{long}={table}{separator}{value}"));
+            }
+        }
+        Ok(args)
+    }
+
+    /// Text of a value, or of each element of an array.
+    fn values(&self, key: &str, value: &toml::Value) -> Result<Vec<String>, DumperError> {
+        let text = |value: &toml::Value| match value {
+            toml::Value::String(s) => Ok(s.clone()),
+            toml::Value::Integer(i) => Ok(i.to_string()),
+            toml::Value::Float(f) => Ok(f.to_string()),
+            toml::Value::Boolean(b) => Ok(b.to_string()),
+            _ => Err(self.invalid(format!("'{key}' must be a string, number or list of those"))),
+        };
+        match value {
+            toml::Value::Array(values) => values.iter().map(text).collect(),
+            value => Ok(vec![text(value)?]),
+        }
+    }
+}
+
+/// The settings of parsed arguments as a config file. Settings left at
+/// their default are commented out.
+pub fn effective_config(command: &clap::Command, matches: &clap::ArgMatches) -> String {
+    use clap::parser::ValueSource;
+
+    let mut text = String::from("[defaults]\n");
+    for arg in command.get_arguments() {
+        let id = arg.get_id().as_str();
+        if COMMAND_LINE_ONLY.contains(&id) || arg.get_long().is_none() {
+            continue;
+        }
+        let Some(source) = matches.value_source(id) else {
+            continue;
+        };
+        let value = if arg.get_action().takes_values() {
+            let values: Vec<toml::Value> = matches
+                .get_raw(id)
+                .into_iter()
+                .flatten()
+                .map(|raw| {
+                    let raw = raw.to_string_lossy();
+                    raw.parse::<i64>()
+                        .map(toml::Value::Integer)
+                        .unwrap_or_else(|_| toml::Value::String(raw.into_owned()))
+                })
+                .collect();
+            match arg.get_action() {
+                clap::ArgAction::Append => toml::Value::Array(values),
+                _ => match values.into_iter().next() {
+                    Some(value) => value,
+                    None => continue,
+                },
+            }
+        } else {
+            toml::Value::Boolean(matches.get_flag(id))
+        };
+        let comment = if source == ValueSource::DefaultValue {
+            "# "
+        } else {
+            ""
+        };
+        text.push_str(&format!("{comment}{id} = {value}\n"));
+    }
+    text
+}
@@ -0,0 +1,67 @@
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+use crate::named_query::NamedQuery;
+use crate::query::quote_identifier;
+use crate::stats::UnmatchedTable;
+
+/// Alias the compared database is attached as.
+pub(crate) const COMPARE_ALIAS: &str = "compare_db";
+
+/// Turn `config` into a dump of the differences between its database and
+/// `compare_path`: for each table in both, the rows only in the dumped
+/// database go to `<table>_added`, the rows only in the compared one to
+/// `<table>_deleted`. A changed row shows up in both. Returns the config, its
+/// query names and the tables found in only one of the databases.
+pub(crate) fn delta_config(
+    config: &DumpConfig,
+    compare_path: &str,
+) -> Result<(DumpConfig, Vec<String>, Vec<UnmatchedTable>), DumperError> {
+    let tables = config.tables_filter.apply(crate::get_tables(config)?)?;
+    let mut compare_config = config.clone();
+    compare_config.db_path = compare_path.to_string();
+    let compare_tables = config
+        .tables_filter
+        .apply(crate::get_tables(&compare_config)?)?;
+
+    let mut unmatched = Vec::new();
+    for (names, others, database) in [
+        (&tables, &compare_tables, config.db_path.as_str()),
+        (&compare_tables, &tables, compare_path),
+    ] {
+        unmatched.extend(
+            names
+                .iter()
+                .filter(|name| !others.contains(name))
+                .map(|name| {
+                    tracing::warn!("Table {name} is only in {database}, skipping it");
+                    UnmatchedTable {
+                        table: name.clone(),
+                        database: database.to_string(),
+                    }
+                }),
+        );
+    }
+
+    let mut delta = config.clone();
+    delta
+        .attach
+        .push((COMPARE_ALIAS.to_string(), compare_path.to_string()));
+    let mut names = Vec::new();
+    for table in tables.iter().filter(|name| compare_tables.contains(name)) {
+        let main = format!("main.{}", quote_identifier(table));
+        let compare = format!(
+            "{}.{}",
+            quote_identifier(COMPARE_ALIAS),
+            quote_identifier(table)
+        );
+        for (suffix, from, except) in [("added", &main, &compare), ("deleted", &compare, &main)] {
+            let query = NamedQuery {
+                name: format!("{table}_{suffix}"),
+                sql: format!("SELECT * FROM {from} EXCEPT SELECT * FROM {except}"),
+            };
+            names.push(query.name.clone());
+            delta.queries.push(query);
+        }
+    }
+    Ok((delta, names, unmatched))
+}
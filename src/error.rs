@@ -0,0 +1,135 @@
+/// Errors returned while dumping a database.
+#[derive(Debug, thiserror::Error)]
+pub enum DumperError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+
+    #[error(transparent)]
+    FromSql(#[from] rusqlite::types::FromSqlError),
+
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Zip(#[from] zip::result::ZipError),
+
+    #[error(transparent)]
+    Parquet(#[from] parquet::errors::ParquetError),
+
+    #[error(transparent)]
+    Arrow(#[from] arrow::error::ArrowError),
+
+    #[error("Cannot watch the database. {0}")]
+    Watch(#[from] notify::Error),
+
+    #[error("Column '{column}' has a {found} value, but its {format} type is {expected}")]
+    ColumnTypeMismatch {
+        format: &'static str,
+        column: String,
+        expected: &'static str,
+        found: &'static str,
+    },
+
+    #[error("Invalid log level '{0}'")]
+    InvalidLogLevel(String),
+
+    #[error("Invalid table pattern '{pattern}'. {source}")]
+    InvalidTablePattern {
+        pattern: String,
+        source: regex::Error,
+    },
+
+    #[error("Database integrity check failed:\n{}", .0.join("\n"))]
+    IntegrityCheckFailed(Vec<String>),
+
+    #[error("Query '{0}' is not a SELECT statement")]
+    QueryNotReadOnly(String),
+
+    #[error("Verification failed for {0} tables")]
+    VerifyFailed(usize),
+
+    #[error("{0} tables failed to dump")]
+    TablesFailed(usize),
+
+    #[error("Table '{0}' does not exist in the database")]
+    TableNotFound(String),
+
+    #[error(
+        "Output file '{}' already exists. Use --overwrite to replace it or --append to add rows to it",
+        .0.display()
+    )]
+    OutputExists(std::path::PathBuf),
+
+    #[error("Column '{column}' does not exist in table '{table}'")]
+    ColumnNotFound { table: String, column: String },
+
+    #[error("View '{view}' cannot be read, it may reference a missing table or view. {source}")]
+    BrokenView {
+        view: String,
+        source: rusqlite::Error,
+    },
+
+    #[error("Query '{name}' is invalid. {source}")]
+    InvalidQuery {
+        name: String,
+        source: rusqlite::Error,
+    },
+
+    #[error("Cannot attach database '{path}'. {source}")]
+    AttachFailed {
+        path: String,
+        source: rusqlite::Error,
+    },
+
+    #[error("{pragma} failed. {source}")]
+    PragmaFailed {
+        pragma: String,
+        source: rusqlite::Error,
+    },
+
+    #[error("Unsafe SQL fragment '{0}'. Semicolons and comments are not allowed")]
+    UnsafeSqlFragment(String),
+
+    #[error("Invalid HTML template '{path}'. {source}")]
+    InvalidTemplate {
+        path: String,
+        source: minijinja::Error,
+    },
+
+    #[error("Invalid config file '{path}'. {message}")]
+    InvalidConfigFile { path: String, message: String },
+
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+
+    #[error("Invalid timestamp '{0}'")]
+    InvalidTimestamp(String),
+
+    #[error("Dump cancelled")]
+    Cancelled,
+}
+
+impl DumperError {
+    /// Whether the error is SQLite reporting a busy or locked database,
+    /// which may go away when tried again.
+    pub(crate) fn is_busy(&self) -> bool {
+        let source = match self {
+            DumperError::Sqlite(source)
+            | DumperError::BrokenView { source, .. }
+            | DumperError::InvalidQuery { source, .. }
+            | DumperError::AttachFailed { source, .. }
+            | DumperError::PragmaFailed { source, .. } => source,
+            _ => return false,
+        };
+        matches!(
+            source.sqlite_error_code(),
+            Some(rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked)
+        )
+    }
+}
@@ -0,0 +1,116 @@
+use crate::error::DumperError;
+
+/// Selects which of the discovered tables are dumped.
+///
+/// The filters are applied in order: `include`, `listed`, `pattern`, then
+/// `exclude`.
+#[derive(Debug, Clone, Default)]
+pub struct TableFilter {
+    /// Dump only these tables. Empty means all tables.
+    pub include: Vec<String>,
+    /// Like `include`, but tables that do not exist are skipped with a
+    /// warning instead of failing.
+    pub listed: Vec<String>,
+    /// Dump only tables whose name matches this pattern.
+    pub pattern: Option<regex::Regex>,
+    /// Skip tables matching any of these glob patterns.
+    pub exclude: Vec<glob::Pattern>,
+}
+
+impl TableFilter {
+    pub(crate) fn apply(&self, table_names: Vec<String>) -> Result<Vec<String>, DumperError> {
+        let table_names = select_tables(table_names, &self.include)?;
+        let table_names = select_listed_tables(table_names, &self.listed);
+        let table_names = match_tables(table_names, self.pattern.as_ref());
+        Ok(exclude_tables(table_names, &self.exclude))
+    }
+}
+
+/// Keep only the tables named in `requested`, in the requested order.
+fn select_tables(
+    table_names: Vec<String>,
+    requested: &[String],
+) -> Result<Vec<String>, DumperError> {
+    if requested.is_empty() {
+        return Ok(table_names);
+    }
+    let mut selected = Vec::new();
+    for name in requested {
+        if !table_names.contains(name) {
+            return Err(DumperError::TableNotFound(name.clone()));
+        }
+        if !selected.contains(name) {
+            selected.push(name.clone());
+        }
+    }
+    Ok(selected)
+}
+
+/// Keep only the tables named in `listed`, in the listed order, warning about
+/// the ones that do not exist.
+fn select_listed_tables(table_names: Vec<String>, listed: &[String]) -> Vec<String> {
+    if listed.is_empty() {
+        return table_names;
+    }
+    let mut selected = Vec::new();
+    for name in listed {
+        if !table_names.contains(name) {
+            tracing::warn!("Listed table {name} does not exist, skipping it");
+        } else if !selected.contains(name) {
+            selected.push(name.clone());
+        }
+    }
+    selected
+}
+
+/// Read a table list file: one table name per line, ignoring empty lines and
+/// lines starting with `#`.
+pub fn read_table_list(path: &str) -> Result<Vec<String>, DumperError> {
+    let text = std::fs::read_to_string(path).map_err(|e| {
+        DumperError::InvalidConfig(format!("cannot read table list file '{path}'. {e}"))
+    })?;
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Keep only the tables whose name matches `pattern`.
+fn match_tables(table_names: Vec<String>, pattern: Option<&regex::Regex>) -> Vec<String> {
+    let Some(re) = pattern else {
+        return table_names;
+    };
+    table_names
+        .into_iter()
+        .filter(|name| {
+            let matched = re.is_match(name);
+            if matched {
+                tracing::info!("Table {name} matched pattern '{re}'");
+            } else {
+                tracing::info!("Skipping table {name} (does not match pattern '{re}')");
+            }
+            matched
+        })
+        .collect()
+}
+
+/// Drop the tables matching any of the `excludes` glob patterns.
+fn exclude_tables(table_names: Vec<String>, excludes: &[glob::Pattern]) -> Vec<String> {
+    for pattern in excludes {
+        if !table_names.iter().any(|name| pattern.matches(name)) {
+            tracing::warn!("Exclude pattern '{pattern}' did not match any table");
+        }
+    }
+    table_names
+        .into_iter()
+        .filter(|name| match excludes.iter().find(|p| p.matches(name)) {
+            Some(pattern) => {
+                tracing::info!("Excluding table {name} (matched '{pattern}')");
+                false
+            }
+            None => true,
+        })
+        .collect()
+}
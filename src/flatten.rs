@@ -0,0 +1,126 @@
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+use crate::formats::Field;
+use crate::query::Query;
+
+/// Rows read to find the JSON columns and their keys.
+const PREVIEW_ROWS: usize = 100;
+
+/// Separator between a column and its keys in flattened column names.
+const SEPARATOR: &str = "__";
+
+/// Keys leading from a JSON column value to a flattened value.
+pub(crate) type JsonPath = Vec<String>;
+
+/// Find the keys to flatten of each column of `query`, from its first rows.
+///
+/// A column is flattened when every non-null value of the preview is JSON
+/// text and some are objects. Its keys are the ones seen in the preview, so
+/// keys that only show up in later rows are not flattened; they stay in the
+/// original column, which is written as well.
+pub(crate) fn detect(
+    conn: &rusqlite::Connection,
+    config: &DumpConfig,
+    query: &Query,
+    column_count: usize,
+) -> Result<Vec<Vec<JsonPath>>, DumperError> {
+    let mut paths = vec![Vec::new(); column_count];
+    if !config.flatten_json {
+        return Ok(paths);
+    }
+    let mut is_json = vec![true; column_count];
+    let mut stmt = conn.prepare(&format!(
+        "SELECT * FROM ({}) LIMIT {PREVIEW_ROWS}",
+        query.sql
+    ))?;
+    let mut rows = stmt.query(rusqlite::params_from_iter(&query.params))?;
+    while let Some(row) = rows.next()? {
+        for i in 0..column_count {
+            if !is_json[i] {
+                continue;
+            }
+            match row.get_ref(i)? {
+                rusqlite::types::ValueRef::Null => {}
+                rusqlite::types::ValueRef::Text(text) => {
+                    match serde_json::from_slice::<serde_json::Value>(text) {
+                        Ok(value) => collect_paths(
+                            &value,
+                            &mut Vec::new(),
+                            config.flatten_json_depth,
+                            &mut paths[i],
+                        ),
+                        Err(_) => is_json[i] = false,
+                    }
+                }
+                _ => is_json[i] = false,
+            }
+        }
+    }
+    for (i, paths) in paths.iter_mut().enumerate() {
+        if !is_json[i] {
+            paths.clear();
+        }
+    }
+    Ok(paths)
+}
+
+/// Add the paths of the values of `value` to `paths`, descending into
+/// objects at most `depth` levels.
+fn collect_paths(
+    value: &serde_json::Value,
+    prefix: &mut JsonPath,
+    depth: usize,
+    paths: &mut Vec<JsonPath>,
+) {
+    match value {
+        serde_json::Value::Object(object) if depth > 0 => {
+            for (key, value) in object {
+                prefix.push(key.clone());
+                collect_paths(value, prefix, depth - 1, paths);
+                prefix.pop();
+            }
+        }
+        _ if prefix.is_empty() => {}
+        _ => {
+            if !paths.contains(prefix) {
+                paths.push(prefix.clone());
+            }
+        }
+    }
+}
+
+/// Name of the column flattened from `column` at `path`.
+pub(crate) fn column_name(column: &str, path: &JsonPath) -> String {
+    format!("{column}{SEPARATOR}{}", path.join(SEPARATOR))
+}
+
+/// The flattened values of `field` at `paths`. Booleans are written as
+/// `true` and `false`, arrays and objects below the depth limit as JSON text,
+/// missing keys as NULL.
+pub(crate) fn flatten(field: &Field, paths: &[JsonPath]) -> Vec<Field> {
+    if paths.is_empty() {
+        return Vec::new();
+    }
+    let value = match field {
+        Field::Text(text) => serde_json::from_str::<serde_json::Value>(text).ok(),
+        _ => None,
+    };
+    paths
+        .iter()
+        .map(|path| {
+            let found = value
+                .as_ref()
+                .and_then(|value| path.iter().try_fold(value, |value, key| value.get(key)));
+            match found {
+                None | Some(serde_json::Value::Null) => Field::Null,
+                Some(serde_json::Value::Bool(b)) => Field::Text(b.to_string()),
+                Some(serde_json::Value::Number(n)) => match n.as_i64() {
+                    Some(i) => Field::Integer(i),
+                    None => n.as_f64().map_or(Field::Null, Field::Real),
+                },
+                Some(serde_json::Value::String(s)) => Field::Text(s.clone()),
+                Some(other) => Field::Text(other.to_string()),
+            }
+        })
+        .collect()
+}
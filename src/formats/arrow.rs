@@ -0,0 +1,245 @@
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, BinaryArray, Float64Array, Int64Array, StringArray, TimestampMicrosecondArray,
+    TimestampMillisecondArray, TimestampSecondArray,
+};
+use arrow::datatypes::{DataType, Schema, TimeUnit};
+use arrow::ipc::writer::StreamWriter;
+use arrow::record_batch::RecordBatch;
+
+use super::columnar::ColumnType;
+use super::{Field, TableWriter};
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+use crate::output::{OutputWriter, Sink};
+use crate::timestamp::EpochUnit;
+
+/// Rows per record batch.
+const BATCH_ROWS: usize = 65_536;
+
+/// Arrow type of a column: a columnar type, or a UTC timestamp for the
+/// parsed timestamp columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArrowType {
+    Column(ColumnType),
+    Timestamp,
+}
+
+impl ArrowType {
+    fn data_type(&self, config: &DumpConfig) -> DataType {
+        match self {
+            ArrowType::Column(ColumnType::Int64) => DataType::Int64,
+            ArrowType::Column(ColumnType::Double) => DataType::Float64,
+            ArrowType::Column(ColumnType::Utf8) => DataType::Utf8,
+            ArrowType::Column(ColumnType::Binary) => DataType::Binary,
+            ArrowType::Timestamp => DataType::Timestamp(time_unit(config), Some("UTC".into())),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            ArrowType::Column(ColumnType::Int64) => "Int64",
+            ArrowType::Column(ColumnType::Double) => "Float64",
+            ArrowType::Column(ColumnType::Utf8) => "Utf8",
+            ArrowType::Column(ColumnType::Binary) => "Binary",
+            ArrowType::Timestamp => "Timestamp",
+        }
+    }
+}
+
+/// Arrow unit of the timestamps, the unit of the integer epoch timestamps.
+fn time_unit(config: &DumpConfig) -> TimeUnit {
+    match config.epoch_unit {
+        EpochUnit::Seconds => TimeUnit::Second,
+        EpochUnit::Millis => TimeUnit::Millisecond,
+        EpochUnit::Micros => TimeUnit::Microsecond,
+    }
+}
+
+/// Read a parsed timestamp column value back as an epoch value. Empty text,
+/// written for values that are not timestamps, is NULL.
+fn parsed_epoch(field: &Field, config: &DumpConfig) -> Result<Option<i64>, ()> {
+    let text = match field {
+        Field::Null => return Ok(None),
+        Field::Text(text) if text.is_empty() => return Ok(None),
+        Field::Text(text) => text,
+        _ => return Err(()),
+    };
+    let datetime = match &config.timestamp_format {
+        Some(format) => chrono::DateTime::parse_from_str(text, format),
+        None => chrono::DateTime::parse_from_rfc3339(text),
+    };
+    datetime
+        .map(|datetime| Some(config.epoch_unit.epoch(datetime.to_utc())))
+        .map_err(|_| ())
+}
+
+/// Writes an Arrow IPC stream, in record batches of at most 65,536 rows.
+/// The schema is inferred from the first batch; values of later rows that
+/// do not fit it fail the table.
+///
+/// Parsed timestamp columns become UTC timestamps when all their values of
+/// the first batch can be read back, which needs a `timestamp_format` with
+/// a UTC offset if one is set.
+pub(crate) struct ArrowTableWriter<'a> {
+    config: &'a DumpConfig,
+    /// The output until the schema is known.
+    output: Option<OutputWriter>,
+    writer: Option<StreamWriter<OutputWriter>>,
+    schema: Option<Arc<Schema>>,
+    columns: Vec<String>,
+    types: Vec<ArrowType>,
+    rows: Vec<Vec<Field>>,
+}
+
+impl<'a> ArrowTableWriter<'a> {
+    pub(crate) fn new(output: OutputWriter, config: &'a DumpConfig) -> Self {
+        ArrowTableWriter {
+            config,
+            output: Some(output),
+            writer: None,
+            schema: None,
+            columns: Vec::new(),
+            types: Vec::new(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Create the stream writer with the schema inferred from the buffered
+    /// rows, unless it exists.
+    fn start(&mut self) -> Result<(), DumperError> {
+        let Some(output) = self.output.take() else {
+            return Ok(());
+        };
+        self.types = (0..self.columns.len())
+            .map(|i| {
+                let values = || self.rows.iter().map(|row| &row[i]);
+                match ColumnType::infer(values()) {
+                    ColumnType::Utf8
+                        if self.columns[i].ends_with("_parsed")
+                            && values().all(|field| parsed_epoch(field, self.config).is_ok()) =>
+                    {
+                        ArrowType::Timestamp
+                    }
+                    ty => ArrowType::Column(ty),
+                }
+            })
+            .collect();
+        let fields: Vec<arrow::datatypes::Field> = self
+            .columns
+            .iter()
+            .zip(&self.types)
+            .map(|(name, ty)| arrow::datatypes::Field::new(name, ty.data_type(self.config), true))
+            .collect();
+        let schema = Arc::new(Schema::new(fields));
+        self.writer = Some(StreamWriter::try_new(output, &schema)?);
+        self.schema = Some(schema);
+        Ok(())
+    }
+
+    /// Write the buffered rows as one record batch.
+    fn flush_batch(&mut self) -> Result<(), DumperError> {
+        self.start()?;
+        let config = self.config;
+        let mut arrays: Vec<ArrayRef> = Vec::with_capacity(self.columns.len());
+        for (i, ty) in self.types.iter().enumerate() {
+            let cells = self.rows.iter().map(|row| &row[i]);
+            let mismatch = |field: &Field| DumperError::ColumnTypeMismatch {
+                format: "Arrow",
+                column: self.columns[i].clone(),
+                expected: ty.name(),
+                found: field.storage_class(),
+            };
+            let array: ArrayRef = match ty {
+                ArrowType::Column(ColumnType::Int64) => Arc::new(
+                    cells
+                        .map(|field| match field {
+                            Field::Null => Ok(None),
+                            Field::Integer(v) => Ok(Some(*v)),
+                            other => Err(mismatch(other)),
+                        })
+                        .collect::<Result<Int64Array, _>>()?,
+                ),
+                ArrowType::Column(ColumnType::Double) => Arc::new(
+                    cells
+                        .map(|field| match field {
+                            Field::Null => Ok(None),
+                            Field::Integer(v) => Ok(Some(*v as f64)),
+                            Field::Real(v) => Ok(Some(*v)),
+                            other => Err(mismatch(other)),
+                        })
+                        .collect::<Result<Float64Array, _>>()?,
+                ),
+                ArrowType::Column(ColumnType::Utf8) => Arc::new(
+                    cells
+                        .map(|field| match field {
+                            Field::Null => None,
+                            other => Some(other.to_text(config)),
+                        })
+                        .collect::<StringArray>(),
+                ),
+                ArrowType::Column(ColumnType::Binary) => Arc::new(
+                    cells
+                        .map(|field| match field {
+                            Field::Null => Ok(None),
+                            Field::Blob(b) => Ok(Some(b.as_slice())),
+                            other => Err(mismatch(other)),
+                        })
+                        .collect::<Result<BinaryArray, _>>()?,
+                ),
+                ArrowType::Timestamp => {
+                    let values = cells
+                        .map(|field| parsed_epoch(field, config).map_err(|()| mismatch(field)))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    match time_unit(config) {
+                        TimeUnit::Second => {
+                            Arc::new(TimestampSecondArray::from(values).with_timezone("UTC"))
+                        }
+                        TimeUnit::Millisecond => {
+                            Arc::new(TimestampMillisecondArray::from(values).with_timezone("UTC"))
+                        }
+                        _ => Arc::new(TimestampMicrosecondArray::from(values).with_timezone("UTC")),
+                    }
+                }
+            };
+            arrays.push(array);
+        }
+        let schema = self.schema.clone().expect("the schema was just created");
+        let batch = RecordBatch::try_new(schema, arrays)?;
+        self.writer
+            .as_mut()
+            .expect("the stream writer was just created")
+            .write(&batch)?;
+        self.rows.clear();
+        Ok(())
+    }
+}
+
+impl TableWriter for ArrowTableWriter<'_> {
+    fn write_header(&mut self, columns: &[String]) -> Result<(), DumperError> {
+        self.columns = columns.to_vec();
+        Ok(())
+    }
+
+    fn write_row(&mut self, fields: &[Field]) -> Result<(), DumperError> {
+        self.rows.push(fields.to_vec());
+        if self.rows.len() >= BATCH_ROWS {
+            self.flush_batch()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<Sink, DumperError> {
+        if !self.rows.is_empty() {
+            self.flush_batch()?;
+        }
+        self.start()?;
+        let mut writer = self
+            .writer
+            .take()
+            .expect("the stream writer was just created");
+        writer.finish()?;
+        Ok(writer.into_inner()?.finish()?)
+    }
+}
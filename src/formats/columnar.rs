@@ -0,0 +1,38 @@
+use super::Field;
+
+/// Type of a column in the columnar formats, inferred from the values of the
+/// first rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ColumnType {
+    Int64,
+    Double,
+    Utf8,
+    Binary,
+}
+
+impl ColumnType {
+    /// The narrowest type holding all non-null `values`. Columns without
+    /// values, or with TEXT or mixed values, are UTF8.
+    pub(crate) fn infer<'f>(values: impl Iterator<Item = &'f Field>) -> ColumnType {
+        let mut inferred = None;
+        for value in values {
+            let ty = match value {
+                Field::Null => continue,
+                Field::Integer(_) => ColumnType::Int64,
+                Field::Real(_) => ColumnType::Double,
+                Field::Text(_) => return ColumnType::Utf8,
+                Field::Blob(_) => ColumnType::Binary,
+            };
+            inferred = Some(match (inferred, ty) {
+                (None, ty) => ty,
+                (Some(a), b) if a == b => a,
+                (
+                    Some(ColumnType::Int64 | ColumnType::Double),
+                    ColumnType::Int64 | ColumnType::Double,
+                ) => ColumnType::Double,
+                _ => return ColumnType::Utf8,
+            });
+        }
+        inferred.unwrap_or(ColumnType::Utf8)
+    }
+}
@@ -0,0 +1,123 @@
+use std::io::Write;
+
+use super::{Field, TableWriter};
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+use crate::output::{OutputWriter, Sink};
+
+/// UTF-8 byte order mark written with `csv_bom`.
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+pub(crate) struct CsvTableWriter<'a> {
+    writer: csv::Writer<OutputWriter>,
+    config: &'a DumpConfig,
+    table_name: String,
+    header: bool,
+    /// Characters replaced with `?` because `encoding` cannot represent
+    /// them.
+    unmappable: u64,
+}
+
+impl<'a> CsvTableWriter<'a> {
+    /// With `csv_bom`, the byte order mark is written right away, except to
+    /// files rows are appended to.
+    pub(crate) fn new(
+        mut writer: OutputWriter,
+        config: &'a DumpConfig,
+        table_name: &str,
+        header: bool,
+    ) -> std::io::Result<Self> {
+        if config.csv_bom && (header || !config.append) {
+            writer.write_all(BOM)?;
+        }
+        Ok(CsvTableWriter {
+            writer: csv::WriterBuilder::new()
+                .delimiter(config.delimiter)
+                .terminator(config.line_ending.terminator())
+                .quote(config.quote_char)
+                .quote_style(config.quoting.quote_style())
+                // An escape character other than the quote replaces doubling
+                // the quote.
+                .double_quote(config.escape_char == config.quote_char)
+                .escape(config.escape_char)
+                .from_writer(writer),
+            config,
+            table_name: table_name.to_string(),
+            header,
+            unmappable: 0,
+        })
+    }
+}
+
+impl TableWriter for CsvTableWriter<'_> {
+    fn write_header(&mut self, columns: &[String]) -> Result<(), DumperError> {
+        if self.header {
+            let columns: Vec<_> = columns
+                .iter()
+                .map(|column| encode(self.config.encoding, column, &mut self.unmappable))
+                .collect();
+            self.writer.write_record(&columns)?;
+        }
+        Ok(())
+    }
+
+    fn write_row(&mut self, fields: &[Field]) -> Result<(), DumperError> {
+        for field in fields {
+            let text = field.to_text(self.config);
+            let text = encode(self.config.encoding, &text, &mut self.unmappable);
+            self.writer.write_field(text)?;
+        }
+        self.writer.write_record(None::<&[u8]>)?; // 改行
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<Sink, DumperError> {
+        if self.unmappable > 0 {
+            tracing::warn!(
+                table = self.table_name,
+                unmappable = self.unmappable,
+                "Table {}: {} characters cannot be written in {} and were replaced with '?'",
+                self.table_name,
+                self.unmappable,
+                self.config
+                    .encoding
+                    .map_or("UTF-8", encoding_rs::Encoding::name)
+            );
+        }
+        let writer = self.writer.into_inner().map_err(|e| e.into_error())?;
+        Ok(writer.finish()?)
+    }
+}
+
+/// `text` in `encoding`, UTF-8 when `None`, with `?` for the characters it
+/// cannot represent. They are added to `unmappable`.
+fn encode<'t>(
+    encoding: Option<&'static encoding_rs::Encoding>,
+    text: &'t str,
+    unmappable: &mut u64,
+) -> std::borrow::Cow<'t, [u8]> {
+    let Some(encoding) = encoding else {
+        return text.as_bytes().into();
+    };
+    let mut encoder = encoding.new_encoder();
+    let mut encoded = Vec::with_capacity(
+        encoder
+            .max_buffer_length_from_utf8_without_replacement(text.len())
+            .unwrap_or(text.len()),
+    );
+    let mut rest = text;
+    loop {
+        let (result, read) =
+            encoder.encode_from_utf8_to_vec_without_replacement(rest, &mut encoded, true);
+        rest = &rest[read..];
+        match result {
+            encoding_rs::EncoderResult::InputEmpty => break,
+            encoding_rs::EncoderResult::OutputFull => encoded.reserve(rest.len().max(16)),
+            encoding_rs::EncoderResult::Unmappable(_) => {
+                encoded.push(b'?');
+                *unmappable += 1;
+            }
+        }
+    }
+    encoded.into()
+}
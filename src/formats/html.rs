@@ -0,0 +1,129 @@
+use std::io::Write;
+
+use super::{Field, TableWriter};
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+use crate::output::{OutputWriter, Sink};
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Environment that HTML escapes the values of every template.
+fn template_environment() -> minijinja::Environment<'static> {
+    let mut env = minijinja::Environment::new();
+    env.set_auto_escape_callback(|_| minijinja::AutoEscape::Html);
+    env
+}
+
+/// Check that the template at `path` can be read and compiled.
+pub(crate) fn validate_template(path: &str) -> Result<(), DumperError> {
+    let source = std::fs::read_to_string(path)?;
+    template_environment()
+        .template_from_str(&source)
+        .map(|_| ())
+        .map_err(|source| DumperError::InvalidTemplate {
+            path: path.to_string(),
+            source,
+        })
+}
+
+/// Writes an HTML document with one `<table>`. With an `html_template`, the
+/// rows are kept until the end and rendered through the template instead,
+/// with `table`, `columns`, `rows` and `class` in its context.
+pub(crate) struct HtmlTableWriter<'a> {
+    writer: std::io::BufWriter<OutputWriter>,
+    config: &'a DumpConfig,
+    table_name: String,
+    columns: Vec<String>,
+    /// Cell texts of the rows, kept for the template.
+    rows: Vec<Vec<String>>,
+}
+
+impl<'a> HtmlTableWriter<'a> {
+    pub(crate) fn new(writer: OutputWriter, config: &'a DumpConfig, table_name: &str) -> Self {
+        HtmlTableWriter {
+            writer: std::io::BufWriter::new(writer),
+            config,
+            table_name: table_name.to_string(),
+            columns: Vec::new(),
+            rows: Vec::new(),
+        }
+    }
+
+    fn render_template(&mut self, path: &str) -> Result<(), DumperError> {
+        let source = std::fs::read_to_string(path)?;
+        let invalid = |source| DumperError::InvalidTemplate {
+            path: path.to_string(),
+            source,
+        };
+        let env = template_environment();
+        let template = env.template_from_str(&source).map_err(invalid)?;
+        let html = template
+            .render(minijinja::context! {
+                table => &self.table_name,
+                columns => &self.columns,
+                rows => std::mem::take(&mut self.rows),
+                class => &self.config.html_class,
+            })
+            .map_err(invalid)?;
+        self.writer.write_all(html.as_bytes())?;
+        Ok(())
+    }
+}
+
+impl TableWriter for HtmlTableWriter<'_> {
+    fn write_header(&mut self, columns: &[String]) -> Result<(), DumperError> {
+        self.columns = columns.to_vec();
+        if self.config.html_template.is_some() {
+            return Ok(());
+        }
+        let class = match &self.config.html_class {
+            Some(class) => format!(" class=\"{}\"", escape(class)),
+            None => String::new(),
+        };
+        write!(
+            self.writer,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n<table{class}>\n<thead>\n<tr>",
+            escape(&self.table_name)
+        )?;
+        for column in columns {
+            write!(self.writer, "<th>{}</th>", escape(column))?;
+        }
+        self.writer.write_all(b"</tr>\n</thead>\n<tbody>\n")?;
+        Ok(())
+    }
+
+    fn write_row(&mut self, fields: &[Field]) -> Result<(), DumperError> {
+        if self.config.html_template.is_some() {
+            let cells = fields.iter().map(|field| field.to_text(self.config));
+            self.rows.push(cells.collect());
+            return Ok(());
+        }
+        self.writer.write_all(b"<tr>")?;
+        for field in fields {
+            write!(
+                self.writer,
+                "<td>{}</td>",
+                escape(&field.to_text(self.config))
+            )?;
+        }
+        self.writer.write_all(b"</tr>\n")?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<Sink, DumperError> {
+        match self.config.html_template.clone() {
+            Some(path) => self.render_template(&path)?,
+            None => self
+                .writer
+                .write_all(b"</tbody>\n</table>\n</body>\n</html>\n")?,
+        }
+        let writer = self.writer.into_inner().map_err(|e| e.into_error())?;
+        Ok(writer.finish()?)
+    }
+}
@@ -0,0 +1,66 @@
+use std::io::Write;
+
+use super::{Field, TableWriter};
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+use crate::output::{OutputWriter, Sink};
+
+/// Writes either a JSON array of objects or JSON Lines.
+pub(crate) struct JsonTableWriter<'a> {
+    writer: std::io::BufWriter<OutputWriter>,
+    config: &'a DumpConfig,
+    columns: Vec<String>,
+    lines: bool,
+    rows_written: u64,
+}
+
+impl<'a> JsonTableWriter<'a> {
+    pub(crate) fn new(writer: OutputWriter, config: &'a DumpConfig, lines: bool) -> Self {
+        JsonTableWriter {
+            writer: std::io::BufWriter::new(writer),
+            config,
+            columns: Vec::new(),
+            lines,
+            rows_written: 0,
+        }
+    }
+}
+
+impl TableWriter for JsonTableWriter<'_> {
+    fn write_header(&mut self, columns: &[String]) -> Result<(), DumperError> {
+        self.columns = columns.to_vec();
+        Ok(())
+    }
+
+    fn write_row(&mut self, fields: &[Field]) -> Result<(), DumperError> {
+        let object: serde_json::Map<String, serde_json::Value> = self
+            .columns
+            .iter()
+            .cloned()
+            .zip(fields.iter().map(|field| field.to_json(self.config)))
+            .collect();
+        if !self.lines {
+            let sep = if self.rows_written == 0 { "[\n" } else { ",\n" };
+            self.writer.write_all(sep.as_bytes())?;
+        }
+        serde_json::to_writer(&mut self.writer, &object)?;
+        if self.lines {
+            self.writer.write_all(b"\n")?;
+        }
+        self.rows_written += 1;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<Sink, DumperError> {
+        if !self.lines {
+            let tail = if self.rows_written == 0 {
+                "[]\n"
+            } else {
+                "\n]\n"
+            };
+            self.writer.write_all(tail.as_bytes())?;
+        }
+        let writer = self.writer.into_inner().map_err(|e| e.into_error())?;
+        Ok(writer.finish()?)
+    }
+}
@@ -0,0 +1,77 @@
+use std::io::Write;
+
+use super::{Field, MarkdownAlign, TableWriter};
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+use crate::output::{OutputWriter, Sink};
+
+/// Escape a value for a table cell. Pipes would end the cell and line
+/// breaks the row.
+fn escape(s: &str) -> String {
+    s.replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace(['\r', '\n'], "<br>")
+}
+
+/// Writes a GitHub Flavored Markdown table, one row per line.
+pub(crate) struct MarkdownTableWriter<'a> {
+    writer: std::io::BufWriter<OutputWriter>,
+    config: &'a DumpConfig,
+    header: bool,
+}
+
+impl<'a> MarkdownTableWriter<'a> {
+    pub(crate) fn new(writer: OutputWriter, config: &'a DumpConfig, header: bool) -> Self {
+        MarkdownTableWriter {
+            writer: std::io::BufWriter::new(writer),
+            config,
+            header,
+        }
+    }
+
+    fn write_line<I: IntoIterator<Item = String>>(&mut self, cells: I) -> Result<(), DumperError> {
+        let mut line = String::from("|");
+        for cell in cells {
+            line.push(' ');
+            line.push_str(&cell);
+            line.push_str(" |");
+        }
+        line.push('\n');
+        self.writer.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+impl TableWriter for MarkdownTableWriter<'_> {
+    fn write_header(&mut self, columns: &[String]) -> Result<(), DumperError> {
+        if !self.header {
+            return Ok(());
+        }
+        self.write_line(columns.iter().map(|column| escape(column)))?;
+        let mut separator = String::from("|");
+        for column in columns {
+            separator.push_str(match self.config.markdown_align.get(column) {
+                None => "---|",
+                Some(MarkdownAlign::Left) => ":---|",
+                Some(MarkdownAlign::Right) => "---:|",
+                Some(MarkdownAlign::Center) => ":---:|",
+            });
+        }
+        separator.push('\n');
+        self.writer.write_all(separator.as_bytes())?;
+        Ok(())
+    }
+
+    fn write_row(&mut self, fields: &[Field]) -> Result<(), DumperError> {
+        let cells: Vec<String> = fields
+            .iter()
+            .map(|field| escape(&field.to_text(self.config)))
+            .collect();
+        self.write_line(cells)
+    }
+
+    fn finish(self: Box<Self>) -> Result<Sink, DumperError> {
+        let writer = self.writer.into_inner().map_err(|e| e.into_error())?;
+        Ok(writer.finish()?)
+    }
+}
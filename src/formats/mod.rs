@@ -0,0 +1,302 @@
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+use crate::output::{OutputWriter, Sink};
+
+mod arrow;
+mod columnar;
+mod csv;
+mod html;
+mod json;
+mod markdown;
+mod parquet;
+mod parts;
+mod single;
+mod sql;
+
+pub(crate) use html::validate_template;
+pub(crate) use parts::{NextPart, PartedWriter};
+pub(crate) use single::SingleFileWriter;
+
+/// Output file format of a table dump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    Csv,
+    /// One JSON array of objects per table.
+    Json,
+    /// One JSON object per line.
+    Jsonl,
+    /// Apache Parquet, with column types inferred from the first row group.
+    Parquet,
+    /// Apache Arrow IPC stream, with column types inferred from the first
+    /// record batch.
+    Arrow,
+    /// One `INSERT` statement per row, in a transaction.
+    Sql,
+    /// GitHub Flavored Markdown table.
+    Markdown,
+    /// HTML document with one table.
+    Html,
+}
+
+impl OutputFormat {
+    /// File extension used for this format, without the leading dot.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+            OutputFormat::Jsonl => "jsonl",
+            OutputFormat::Parquet => "parquet",
+            OutputFormat::Arrow => "arrow",
+            OutputFormat::Sql => "sql",
+            OutputFormat::Markdown => "md",
+            OutputFormat::Html => "html",
+        }
+    }
+}
+
+/// SQL dialect of the `INSERT` statements of the sql format.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SqlDialect {
+    /// Double quoted identifiers, `X'..'` blobs.
+    #[default]
+    Sqlite,
+    /// Backquoted identifiers, backslashes escaped in strings, `X'..'` blobs.
+    Mysql,
+    /// Double quoted identifiers, `'\x..'::bytea` blobs.
+    Postgresql,
+}
+
+/// Alignment of a column of the markdown format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum MarkdownAlign {
+    Left,
+    Right,
+    Center,
+}
+
+/// Line terminator of CSV records.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+    /// CRLF on Windows, LF elsewhere.
+    Platform,
+}
+
+impl LineEnding {
+    pub(crate) fn terminator(self) -> ::csv::Terminator {
+        match self {
+            LineEnding::Lf => ::csv::Terminator::Any(b'\n'),
+            LineEnding::Crlf => ::csv::Terminator::CRLF,
+            LineEnding::Platform if cfg!(target_os = "windows") => ::csv::Terminator::CRLF,
+            LineEnding::Platform => ::csv::Terminator::Any(b'\n'),
+        }
+    }
+}
+
+/// When CSV fields are quoted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Quoting {
+    /// Every field.
+    Always,
+    /// Fields containing the delimiter, the quote character or a line break.
+    #[default]
+    Necessary,
+    /// No field, even if the output cannot be read back.
+    Never,
+}
+
+impl Quoting {
+    pub(crate) fn quote_style(self) -> ::csv::QuoteStyle {
+        match self {
+            Quoting::Always => ::csv::QuoteStyle::Always,
+            Quoting::Necessary => ::csv::QuoteStyle::Necessary,
+            Quoting::Never => ::csv::QuoteStyle::Never,
+        }
+    }
+}
+
+/// How BLOB values are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum BlobFormat {
+    /// Lowercase hexadecimal.
+    #[default]
+    Hex,
+    /// Standard base64 with padding.
+    Base64,
+    /// URL-safe base64 without padding.
+    Base64url,
+    /// Rust debug syntax of the byte list, e.g. `[72, 105]`.
+    Debug,
+    /// Write an empty string.
+    Skip,
+}
+
+impl BlobFormat {
+    pub(crate) fn encode(&self, blob: &[u8]) -> String {
+        use base64::Engine;
+        match self {
+            BlobFormat::Hex => hex::encode(blob),
+            BlobFormat::Base64 => base64::engine::general_purpose::STANDARD.encode(blob),
+            BlobFormat::Base64url => base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(blob),
+            BlobFormat::Debug => format!("{blob:?}"),
+            BlobFormat::Skip => String::new(),
+        }
+    }
+}
+
+/// How REAL values are written in text based formats.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum FloatFormat {
+    /// Positional notation, e.g. `1234.5`.
+    Fixed,
+    /// Scientific notation, e.g. `1.2345e3`.
+    Scientific,
+    /// Rust's default formatting, or with a precision, fixed notation for
+    /// moderate magnitudes and scientific notation for very large or small ones.
+    #[default]
+    Auto,
+}
+
+impl FloatFormat {
+    pub(crate) fn format(&self, f: f64, precision: Option<usize>) -> String {
+        let scientific = match self {
+            FloatFormat::Fixed => false,
+            FloatFormat::Scientific => true,
+            FloatFormat::Auto => match precision {
+                None => false,
+                Some(_) => f != 0.0 && f.is_finite() && !(1e-4..1e15).contains(&f.abs()),
+            },
+        };
+        match (scientific, precision) {
+            (false, None) => f.to_string(),
+            (false, Some(prec)) => format!("{f:.prec$}"),
+            (true, None) => format!("{f:e}"),
+            (true, Some(prec)) => format!("{f:.prec$e}"),
+        }
+    }
+}
+
+/// A single output cell, keeping the SQLite storage class.
+#[derive(Debug, Clone)]
+pub(crate) enum Field {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl Field {
+    pub(crate) fn from_value_ref(val: rusqlite::types::ValueRef<'_>) -> Result<Field, DumperError> {
+        use rusqlite::types::ValueRef;
+        let field = match val {
+            ValueRef::Null => Field::Null,
+            ValueRef::Integer(i) => Field::Integer(i),
+            ValueRef::Real(f) => Field::Real(f),
+            ValueRef::Text(_) => Field::Text(val.as_str()?.to_string()),
+            ValueRef::Blob(b) => Field::Blob(b.to_vec()),
+        };
+        Ok(field)
+    }
+
+    /// Cut TEXT values longer than `max_cell_size` bytes at a character
+    /// boundary and append the truncation marker.
+    pub(crate) fn truncated(self, config: &DumpConfig) -> Field {
+        match (self, config.max_cell_size) {
+            (Field::Text(mut s), Some(max)) if s.len() > max => {
+                let end = s
+                    .char_indices()
+                    .map(|(i, c)| i + c.len_utf8())
+                    .take_while(|&end| end <= max)
+                    .last()
+                    .unwrap_or(0);
+                s.truncate(end);
+                s.push_str(&config.truncation_marker);
+                Field::Text(s)
+            }
+            (field, _) => field,
+        }
+    }
+
+    /// A value of a boolean column: 0 and 1 as `false` and `true`, or with
+    /// `as_int` the text `false` and `true` as 0 and 1. Other values are kept.
+    pub(crate) fn boolean(self, as_int: bool) -> Field {
+        match (self, as_int) {
+            (Field::Integer(0), false) => Field::Text("false".to_string()),
+            (Field::Integer(1), false) => Field::Text("true".to_string()),
+            (Field::Text(s), true) if s.eq_ignore_ascii_case("false") => Field::Integer(0),
+            (Field::Text(s), true) if s.eq_ignore_ascii_case("true") => Field::Integer(1),
+            (field, _) => field,
+        }
+    }
+
+    /// SQLite storage class of the value.
+    pub(crate) fn storage_class(&self) -> &'static str {
+        match self {
+            Field::Null => "NULL",
+            Field::Integer(_) => "INTEGER",
+            Field::Real(_) => "REAL",
+            Field::Text(_) => "TEXT",
+            Field::Blob(_) => "BLOB",
+        }
+    }
+
+    pub(crate) fn to_text(&self, config: &DumpConfig) -> String {
+        match self {
+            Field::Null => config.null_value.clone(),
+            Field::Integer(i) => i.to_string(),
+            Field::Real(f) if config.normalize_floats && f.is_nan() => config.nan_value.clone(),
+            Field::Real(f) if config.normalize_floats && f.is_infinite() => match *f < 0.0 {
+                true => format!("-{}", config.inf_value),
+                false => config.inf_value.clone(),
+            },
+            Field::Real(f) => config.float_format.format(*f, config.float_precision),
+            Field::Text(s) => s.clone(),
+            Field::Blob(b) => config.blob_format.unwrap_or(BlobFormat::Hex).encode(b),
+        }
+    }
+
+    pub(crate) fn to_json(&self, config: &DumpConfig) -> serde_json::Value {
+        match self {
+            Field::Null => serde_json::Value::Null,
+            Field::Integer(i) => serde_json::Value::from(*i),
+            Field::Real(f) => serde_json::Number::from_f64(*f)
+                .map(serde_json::Value::Number)
+                .unwrap_or(serde_json::Value::Null),
+            Field::Text(s) => serde_json::Value::String(s.clone()),
+            Field::Blob(b) => serde_json::Value::String(
+                config.blob_format.unwrap_or(BlobFormat::Base64).encode(b),
+            ),
+        }
+    }
+}
+
+pub(crate) trait TableWriter {
+    fn write_header(&mut self, columns: &[String]) -> Result<(), DumperError>;
+    fn write_row(&mut self, fields: &[Field]) -> Result<(), DumperError>;
+    /// Flush everything and hand the underlying sink back.
+    fn finish(self: Box<Self>) -> Result<Sink, DumperError>;
+}
+
+pub(crate) fn new_table_writer<'a>(
+    config: &'a DumpConfig,
+    table_name: &str,
+    file: OutputWriter,
+    header: bool,
+) -> Result<Box<dyn TableWriter + Send + 'a>, DumperError> {
+    let writer: Box<dyn TableWriter + Send + 'a> = match config.output_format {
+        OutputFormat::Csv => Box::new(csv::CsvTableWriter::new(file, config, table_name, header)?),
+        OutputFormat::Json => Box::new(json::JsonTableWriter::new(file, config, false)),
+        OutputFormat::Jsonl => Box::new(json::JsonTableWriter::new(file, config, true)),
+        OutputFormat::Parquet => Box::new(parquet::ParquetTableWriter::new(file, config)),
+        OutputFormat::Arrow => Box::new(arrow::ArrowTableWriter::new(file, config)),
+        OutputFormat::Sql => Box::new(sql::SqlTableWriter::new(file, config, table_name)),
+        OutputFormat::Markdown => {
+            Box::new(markdown::MarkdownTableWriter::new(file, config, header))
+        }
+        OutputFormat::Html => Box::new(html::HtmlTableWriter::new(file, config, table_name)),
+    };
+    Ok(writer)
+}
@@ -0,0 +1,213 @@
+use std::sync::Arc;
+
+use parquet::basic::{LogicalType, Repetition, Type as PhysicalType};
+use parquet::data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::types::Type;
+
+use super::columnar::ColumnType;
+use super::{Field, TableWriter};
+use crate::compress::Compression;
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+use crate::output::{OutputWriter, Sink};
+
+/// Parquet name of `ty`.
+fn type_name(ty: ColumnType) -> &'static str {
+    match ty {
+        ColumnType::Int64 => "INT64",
+        ColumnType::Double => "DOUBLE",
+        ColumnType::Utf8 => "UTF8",
+        ColumnType::Binary => "BYTE_ARRAY",
+    }
+}
+
+/// Optional Parquet column `name` of type `ty`.
+fn column_schema(name: &str, ty: ColumnType) -> Result<Type, DumperError> {
+    let (physical, logical) = match ty {
+        ColumnType::Int64 => (PhysicalType::INT64, None),
+        ColumnType::Double => (PhysicalType::DOUBLE, None),
+        ColumnType::Utf8 => (PhysicalType::BYTE_ARRAY, Some(LogicalType::String)),
+        ColumnType::Binary => (PhysicalType::BYTE_ARRAY, None),
+    };
+    Ok(Type::primitive_type_builder(name, physical)
+        .with_repetition(Repetition::OPTIONAL)
+        .with_logical_type(logical)
+        .build()?)
+}
+
+/// Parquet codec for the `compression` of the config, Snappy without one.
+fn codec(config: &DumpConfig) -> Result<parquet::basic::Compression, DumperError> {
+    use parquet::basic::{Compression as Codec, GzipLevel, ZstdLevel};
+    let level = config.compress_level;
+    Ok(match config.compression {
+        Compression::None => Codec::SNAPPY,
+        Compression::Gzip => {
+            Codec::GZIP(GzipLevel::try_new(level.unwrap_or(6).clamp(1, 9) as u32)?)
+        }
+        Compression::Zstd => Codec::ZSTD(ZstdLevel::try_new(level.unwrap_or(3).clamp(1, 22))?),
+        Compression::Lz4 => Codec::LZ4_RAW,
+    })
+}
+
+/// Writes a Parquet file, buffering `parquet_row_group_size` rows per row
+/// group. The schema is inferred from the first row group; values of later
+/// rows that do not fit it fail the table.
+pub(crate) struct ParquetTableWriter<'a> {
+    config: &'a DumpConfig,
+    /// The output until the schema is known.
+    output: Option<OutputWriter>,
+    writer: Option<SerializedFileWriter<OutputWriter>>,
+    columns: Vec<String>,
+    types: Vec<ColumnType>,
+    rows: Vec<Vec<Field>>,
+}
+
+impl<'a> ParquetTableWriter<'a> {
+    pub(crate) fn new(output: OutputWriter, config: &'a DumpConfig) -> Self {
+        ParquetTableWriter {
+            config,
+            output: Some(output),
+            writer: None,
+            columns: Vec::new(),
+            types: Vec::new(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Create the file writer with the schema inferred from the buffered
+    /// rows, unless it exists.
+    fn start(&mut self) -> Result<(), DumperError> {
+        if let Some(output) = self.output.take() {
+            self.types = (0..self.columns.len())
+                .map(|i| ColumnType::infer(self.rows.iter().map(|row| &row[i])))
+                .collect();
+            let fields = self
+                .columns
+                .iter()
+                .zip(&self.types)
+                .map(|(name, ty)| column_schema(name, *ty).map(Arc::new))
+                .collect::<Result<Vec<_>, _>>()?;
+            let schema = Type::group_type_builder("schema")
+                .with_fields(fields)
+                .build()?;
+            let properties = WriterProperties::builder()
+                .set_compression(codec(self.config)?)
+                .build();
+            self.writer = Some(SerializedFileWriter::new(
+                output,
+                Arc::new(schema),
+                Arc::new(properties),
+            )?);
+        }
+        Ok(())
+    }
+
+    /// Write the buffered rows as one row group.
+    fn flush_row_group(&mut self) -> Result<(), DumperError> {
+        self.start()?;
+        let config = self.config;
+        let writer = self
+            .writer
+            .as_mut()
+            .expect("the file writer was just created");
+        let mut row_group = writer.next_row_group()?;
+        for (i, ty) in self.types.iter().enumerate() {
+            let mut column = row_group
+                .next_column()?
+                .expect("the schema has a column per header name");
+            let cells = self.rows.iter().map(|row| &row[i]);
+            let def_levels: Vec<i16> = cells
+                .clone()
+                .map(|field| i16::from(!matches!(field, Field::Null)))
+                .collect();
+            let mismatch = |field: &Field| DumperError::ColumnTypeMismatch {
+                format: "Parquet",
+                column: self.columns[i].clone(),
+                expected: type_name(*ty),
+                found: field.storage_class(),
+            };
+            let values = cells.filter(|field| !matches!(field, Field::Null));
+            match ty {
+                ColumnType::Int64 => {
+                    let values = values
+                        .map(|field| match field {
+                            Field::Integer(v) => Ok(*v),
+                            other => Err(mismatch(other)),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    column
+                        .typed::<Int64Type>()
+                        .write_batch(&values, Some(&def_levels), None)?;
+                }
+                ColumnType::Double => {
+                    let values = values
+                        .map(|field| match field {
+                            Field::Integer(v) => Ok(*v as f64),
+                            Field::Real(v) => Ok(*v),
+                            other => Err(mismatch(other)),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    column
+                        .typed::<DoubleType>()
+                        .write_batch(&values, Some(&def_levels), None)?;
+                }
+                ColumnType::Utf8 => {
+                    let values: Vec<ByteArray> = values
+                        .map(|field| ByteArray::from(field.to_text(config).into_bytes()))
+                        .collect();
+                    column.typed::<ByteArrayType>().write_batch(
+                        &values,
+                        Some(&def_levels),
+                        None,
+                    )?;
+                }
+                ColumnType::Binary => {
+                    let values = values
+                        .map(|field| match field {
+                            Field::Blob(b) => Ok(ByteArray::from(b.clone())),
+                            other => Err(mismatch(other)),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    column.typed::<ByteArrayType>().write_batch(
+                        &values,
+                        Some(&def_levels),
+                        None,
+                    )?;
+                }
+            }
+            column.close()?;
+        }
+        row_group.close()?;
+        self.rows.clear();
+        Ok(())
+    }
+}
+
+impl TableWriter for ParquetTableWriter<'_> {
+    fn write_header(&mut self, columns: &[String]) -> Result<(), DumperError> {
+        self.columns = columns.to_vec();
+        Ok(())
+    }
+
+    fn write_row(&mut self, fields: &[Field]) -> Result<(), DumperError> {
+        self.rows.push(fields.to_vec());
+        if self.rows.len() >= self.config.parquet_row_group_size {
+            self.flush_row_group()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<Sink, DumperError> {
+        if !self.rows.is_empty() {
+            self.flush_row_group()?;
+        }
+        self.start()?;
+        let writer = self
+            .writer
+            .take()
+            .expect("the file writer was just created");
+        Ok(writer.into_inner()?.finish()?)
+    }
+}
@@ -0,0 +1,102 @@
+use super::{Field, TableWriter};
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+use crate::output::{OutputWriter, Sink};
+
+/// Called with the finished sink, the number of rows in it and the index of
+/// the next part when a table is split by `rows_per_file`. Returns the sink of
+/// the next part and whether it needs a header row.
+pub(crate) type NextPart<'a> =
+    &'a mut dyn FnMut(Sink, u64, usize) -> Result<(Sink, bool), DumperError>;
+
+/// Splits a table over several sinks of at most `rows_per_file` rows each,
+/// repeating the header in every part.
+pub(crate) struct PartedWriter<'a> {
+    writer: Option<Box<dyn TableWriter + 'a>>,
+    config: &'a DumpConfig,
+    table_name: &'a str,
+    rows_per_file: u64,
+    next_part: NextPart<'a>,
+    columns: Vec<String>,
+    part: usize,
+    rows_in_part: u64,
+    rows: u64,
+}
+
+impl<'a> PartedWriter<'a> {
+    pub(crate) fn new(
+        writer: Box<dyn TableWriter + 'a>,
+        config: &'a DumpConfig,
+        table_name: &'a str,
+        rows_per_file: u64,
+        next_part: NextPart<'a>,
+    ) -> Self {
+        PartedWriter {
+            writer: Some(writer),
+            config,
+            table_name,
+            rows_per_file,
+            next_part,
+            columns: Vec::new(),
+            part: 0,
+            rows_in_part: 0,
+            rows: 0,
+        }
+    }
+
+    fn writer(&mut self) -> &mut (dyn TableWriter + 'a) {
+        self.writer
+            .as_deref_mut()
+            .expect("writer is only missing while switching parts")
+    }
+
+    fn start_next_part(&mut self) -> Result<(), DumperError> {
+        let writer = self
+            .writer
+            .take()
+            .expect("writer is only missing while switching parts");
+        self.part += 1;
+        let (sink, header) = (self.next_part)(writer.finish()?, self.rows_in_part, self.part)?;
+        let output = OutputWriter::new(sink, self.config)?;
+        let mut writer = super::new_table_writer(self.config, self.table_name, output, header)?;
+        writer.write_header(&self.columns)?;
+        self.writer = Some(writer);
+        self.rows_in_part = 0;
+        Ok(())
+    }
+}
+
+impl TableWriter for PartedWriter<'_> {
+    fn write_header(&mut self, columns: &[String]) -> Result<(), DumperError> {
+        self.columns = columns.to_vec();
+        self.writer().write_header(columns)
+    }
+
+    fn write_row(&mut self, fields: &[Field]) -> Result<(), DumperError> {
+        if self.rows_in_part == self.rows_per_file {
+            self.start_next_part()?;
+        }
+        self.writer().write_row(fields)?;
+        self.rows_in_part += 1;
+        self.rows += 1;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<Sink, DumperError> {
+        tracing::info!(
+            table = self.table_name,
+            rows = self.rows,
+            files = self.part + 1,
+            "Table {}: {} rows in {} files of up to {} rows",
+            self.table_name,
+            self.rows,
+            self.part + 1,
+            self.rows_per_file
+        );
+        let writer = self
+            .writer
+            .take()
+            .expect("writer is only missing while switching parts");
+        writer.finish()
+    }
+}
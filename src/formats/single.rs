@@ -0,0 +1,68 @@
+use super::{Field, TableWriter};
+use crate::error::DumperError;
+use crate::output::Sink;
+
+/// Writes the rows of several tables into one output under a shared header:
+/// `_table` followed by the union of the columns of all tables. Columns a
+/// table does not have are NULL.
+pub(crate) struct SingleFileWriter<'a> {
+    writer: Box<dyn TableWriter + 'a>,
+    columns: Vec<String>,
+    table_name: String,
+    /// Position in `columns` of each column of the current table.
+    positions: Vec<usize>,
+    row: Vec<Field>,
+}
+
+impl<'a> SingleFileWriter<'a> {
+    pub(crate) fn new(
+        mut writer: Box<dyn TableWriter + 'a>,
+        columns: Vec<String>,
+    ) -> Result<Self, DumperError> {
+        let header: Vec<String> = std::iter::once("_table".to_string())
+            .chain(columns.iter().cloned())
+            .collect();
+        writer.write_header(&header)?;
+        Ok(SingleFileWriter {
+            writer,
+            columns,
+            table_name: String::new(),
+            positions: Vec::new(),
+            row: Vec::new(),
+        })
+    }
+
+    /// Rows written from now on belong to `table_name`.
+    pub(crate) fn start_table(&mut self, table_name: &str) {
+        self.table_name = table_name.to_string();
+    }
+}
+
+impl TableWriter for SingleFileWriter<'_> {
+    fn write_header(&mut self, columns: &[String]) -> Result<(), DumperError> {
+        self.positions = columns
+            .iter()
+            .map(|column| {
+                self.columns
+                    .iter()
+                    .position(|c| c == column)
+                    .expect("the shared header contains the columns of every table")
+            })
+            .collect();
+        Ok(())
+    }
+
+    fn write_row(&mut self, fields: &[Field]) -> Result<(), DumperError> {
+        self.row.clear();
+        self.row.push(Field::Text(self.table_name.clone()));
+        self.row.resize_with(self.columns.len() + 1, || Field::Null);
+        for (field, &pos) in fields.iter().zip(&self.positions) {
+            self.row[pos + 1] = field.clone();
+        }
+        self.writer.write_row(&self.row)
+    }
+
+    fn finish(self: Box<Self>) -> Result<Sink, DumperError> {
+        self.writer.finish()
+    }
+}
@@ -0,0 +1,113 @@
+use std::io::Write;
+
+use super::{Field, SqlDialect, TableWriter};
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+use crate::output::{OutputWriter, Sink};
+
+impl SqlDialect {
+    fn quote_identifier(&self, name: &str) -> String {
+        match self {
+            SqlDialect::Mysql => format!("`{}`", name.replace('`', "``")),
+            SqlDialect::Sqlite | SqlDialect::Postgresql => {
+                format!("\"{}\"", name.replace('"', "\"\""))
+            }
+        }
+    }
+
+    fn quote_string(&self, s: &str) -> String {
+        match self {
+            // MySQL reads backslash escapes in strings by default.
+            SqlDialect::Mysql => format!("'{}'", s.replace('\\', "\\\\").replace('\'', "''")),
+            SqlDialect::Sqlite | SqlDialect::Postgresql => format!("'{}'", s.replace('\'', "''")),
+        }
+    }
+
+    fn blob(&self, b: &[u8]) -> String {
+        match self {
+            SqlDialect::Sqlite | SqlDialect::Mysql => format!("X'{}'", hex::encode(b)),
+            SqlDialect::Postgresql => format!("'\\x{}'::bytea", hex::encode(b)),
+        }
+    }
+
+    /// Infinite REAL values, which have no literal in SQL.
+    fn infinity(&self, negative: bool) -> &'static str {
+        match (self, negative) {
+            (SqlDialect::Sqlite, false) => "9e999",
+            (SqlDialect::Sqlite, true) => "-9e999",
+            (SqlDialect::Postgresql, false) => "'Infinity'::float8",
+            (SqlDialect::Postgresql, true) => "'-Infinity'::float8",
+            // MySQL has no infinity.
+            (SqlDialect::Mysql, _) => "NULL",
+        }
+    }
+
+    fn begin(&self) -> &'static str {
+        match self {
+            SqlDialect::Mysql => "START TRANSACTION;",
+            SqlDialect::Sqlite | SqlDialect::Postgresql => "BEGIN TRANSACTION;",
+        }
+    }
+}
+
+/// Writes one `INSERT` statement per row inside a transaction.
+pub(crate) struct SqlTableWriter<'a> {
+    writer: std::io::BufWriter<OutputWriter>,
+    config: &'a DumpConfig,
+    /// `INSERT INTO table (columns) VALUES `, up to the values.
+    insert: String,
+    table_name: String,
+}
+
+impl<'a> SqlTableWriter<'a> {
+    pub(crate) fn new(writer: OutputWriter, config: &'a DumpConfig, table_name: &str) -> Self {
+        SqlTableWriter {
+            writer: std::io::BufWriter::new(writer),
+            config,
+            insert: String::new(),
+            table_name: table_name.to_string(),
+        }
+    }
+
+    fn value(&self, field: &Field) -> String {
+        let dialect = self.config.sql_dialect;
+        match field {
+            Field::Null => "NULL".to_string(),
+            Field::Integer(i) => i.to_string(),
+            Field::Real(f) if f.is_nan() => "NULL".to_string(),
+            Field::Real(f) if f.is_infinite() => dialect.infinity(*f < 0.0).to_string(),
+            Field::Real(_) => field.to_text(self.config),
+            Field::Text(s) => dialect.quote_string(s),
+            Field::Blob(b) => dialect.blob(b),
+        }
+    }
+}
+
+impl TableWriter for SqlTableWriter<'_> {
+    fn write_header(&mut self, columns: &[String]) -> Result<(), DumperError> {
+        let dialect = self.config.sql_dialect;
+        let columns: Vec<String> = columns
+            .iter()
+            .map(|column| dialect.quote_identifier(column))
+            .collect();
+        self.insert = format!(
+            "INSERT INTO {} ({}) VALUES ",
+            dialect.quote_identifier(&self.table_name),
+            columns.join(", ")
+        );
+        writeln!(self.writer, "{}", dialect.begin())?;
+        Ok(())
+    }
+
+    fn write_row(&mut self, fields: &[Field]) -> Result<(), DumperError> {
+        let values: Vec<String> = fields.iter().map(|field| self.value(field)).collect();
+        writeln!(self.writer, "{}({});", self.insert, values.join(", "))?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<Sink, DumperError> {
+        writeln!(self.writer, "COMMIT;")?;
+        let writer = self.writer.into_inner().map_err(|e| e.into_error())?;
+        Ok(writer.finish()?)
+    }
+}
@@ -0,0 +1,1159 @@
+//! Dump the tables of a SQLite database to CSV or JSON files.
+
+mod archive;
+mod blob;
+mod checksum;
+mod compress;
+mod config;
+mod config_file;
+mod delta;
+mod error;
+mod filter;
+mod flatten;
+mod formats;
+mod manifest;
+mod metadata;
+mod named_query;
+mod output;
+mod pivot;
+mod plan;
+mod pragma;
+mod progress;
+mod query;
+mod redact;
+mod schema;
+mod single_file;
+mod state;
+mod stats;
+mod timestamp;
+mod verify;
+mod watch;
+mod zstd_dict;
+
+pub use archive::ZipPassword;
+pub use checksum::ChecksumAlgorithm;
+pub use compress::Compression;
+pub use config::{DumpConfig, DumpConfigBuilder};
+pub use config_file::{ConfigFile, effective_config, read_config_file};
+pub use error::DumperError;
+pub use filter::{TableFilter, read_table_list};
+pub use formats::{
+    BlobFormat, FloatFormat, LineEnding, MarkdownAlign, OutputFormat, Quoting, SqlDialect,
+};
+pub use manifest::ManifestEntry;
+pub use named_query::{NamedQuery, parse_named_query, read_query_file};
+pub use pivot::Pivot;
+pub use plan::{TablePlan, plan_dump};
+pub use pragma::READ_ONLY_PRAGMAS;
+pub use redact::RedactColumn;
+pub use schema::{ColumnInfo, TableInfo, table_info};
+pub use stats::{DumpStats, TableMetrics, TableStats, UnmatchedTable};
+pub use timestamp::{EpochUnit, parse_since_timestamp};
+pub use verify::{TableVerification, verify_manifest};
+pub use watch::watch;
+pub use zstd_dict::train_zstd_dict;
+
+use formats::Field;
+
+/// Dump a single table into `<output_dir>/<table_name>.<ext>`.
+///
+/// An existing output file fails the dump with [`DumperError::OutputExists`],
+/// unless `overwrite` truncates and rewrites it or `append` adds the rows to
+/// it, with a header only if it is empty.
+pub async fn dump_table(config: &DumpConfig, table_name: &str) -> Result<(), DumperError> {
+    dump_table_with_progress(config, table_name, None, &mut Vec::new()).await?;
+    Ok(())
+}
+
+/// Dump a single table like [`dump_table`]. Returns the rows written and the
+/// total size of the output files. The records of the output files are
+/// added to `files`, on failure those of the partial files that are kept.
+///
+/// While the database is busy the table is dumped again, up to `retry`
+/// times.
+async fn dump_table_with_progress(
+    config: &DumpConfig,
+    table_name: &str,
+    progress: Option<&progress::DumpProgress>,
+    files: &mut Vec<output::FileRecord>,
+) -> Result<(RowsWritten, u64), DumperError> {
+    let mut attempt = 0;
+    loop {
+        let mut attempt_files = Vec::new();
+        match dump_table_once(config, table_name, progress, &mut attempt_files) {
+            Err(e) if e.is_busy() && attempt < config.retry && !config.append => {
+                attempt += 1;
+                for record in attempt_files {
+                    if let Err(e) = std::fs::remove_file(&record.path) {
+                        tracing::warn!(
+                            table = table_name,
+                            "Could not remove partial output {}. {e}",
+                            record.path.display()
+                        );
+                    }
+                }
+                tracing::warn!(
+                    table = table_name,
+                    attempt,
+                    error = %e,
+                    "Table {table_name} is busy, retry {attempt} of {} in {} ms. {e}",
+                    config.retry,
+                    config.retry_delay.as_millis()
+                );
+                tokio::select! {
+                    _ = tokio::time::sleep(config.retry_delay) => {}
+                    _ = config.cancel.cancelled() => return Err(DumperError::Cancelled),
+                }
+            }
+            result => {
+                files.extend(attempt_files);
+                return result;
+            }
+        }
+    }
+}
+
+/// One attempt of [`dump_table_with_progress`].
+fn dump_table_once(
+    config: &DumpConfig,
+    table_name: &str,
+    progress: Option<&progress::DumpProgress>,
+    files: &mut Vec<output::FileRecord>,
+) -> Result<(RowsWritten, u64), DumperError> {
+    if config.cancel.is_cancelled() {
+        return Err(DumperError::Cancelled);
+    }
+    tracing::info!(table = table_name, "Dumping table {table_name}");
+    let part = config.rows_per_file.map(|_| 0);
+    let (sink, header) = open_table_file(config, table_name, part)?;
+    let mut paths: Vec<std::path::PathBuf> = sink.path().into_iter().map(Into::into).collect();
+    let mut bytes = 0;
+    let mut done = Vec::new();
+    let mut part_rows = 0;
+    let mut next_part = |sink: output::Sink, rows: u64, part: usize| {
+        bytes += sink.len()?;
+        part_rows += rows;
+        done.extend(sink.into_record(table_name, rows));
+        let (sink, header) = open_table_file(config, table_name, Some(part))?;
+        paths.extend(sink.path().map(Into::into));
+        Ok((sink, header))
+    };
+    let result = write_table(
+        config,
+        table_name,
+        sink,
+        header,
+        Some(&mut next_part),
+        progress,
+    );
+    let (sink, written) = match result {
+        Ok(written) => written,
+        Err(e) => {
+            files.extend(partial_files(config, table_name, &paths, &e));
+            return Err(e);
+        }
+    };
+    bytes += sink.len()?;
+    done.extend(sink.into_record(table_name, written.rows - part_rows));
+    files.extend(done);
+    Ok((written, bytes))
+}
+
+/// Handle the output files of a failed table: delete them, or with
+/// `keep_partial` rename them to `<name>.partial` and return their records.
+/// Files rows were appended to are left alone.
+fn partial_files(
+    config: &DumpConfig,
+    table_name: &str,
+    paths: &[std::path::PathBuf],
+    error: &DumperError,
+) -> Vec<output::FileRecord> {
+    if config.append {
+        return Vec::new();
+    }
+    let mut records = Vec::new();
+    for path in paths {
+        if !config.keep_partial {
+            match std::fs::remove_file(path) {
+                Ok(()) => tracing::info!(
+                    table = table_name,
+                    "Removed partial output {}",
+                    path.display()
+                ),
+                Err(e) => tracing::warn!(
+                    table = table_name,
+                    "Could not remove partial output {}. {e}",
+                    path.display()
+                ),
+            }
+            continue;
+        }
+        let mut partial = path.clone().into_os_string();
+        partial.push(".partial");
+        let partial = std::path::PathBuf::from(partial);
+        let record = std::fs::rename(path, &partial)
+            .and_then(|()| output::FileRecord::partial(config, table_name, &partial, error));
+        match record {
+            Ok(record) => {
+                tracing::info!(
+                    table = table_name,
+                    "Kept partial output {}",
+                    partial.display()
+                );
+                records.push(record);
+            }
+            Err(e) => tracing::warn!(
+                table = table_name,
+                "Could not keep partial output {}. {e}",
+                path.display()
+            ),
+        }
+    }
+    records
+}
+
+/// Open the output file of `table_name`, or of one of its parts. Returns the
+/// file and whether it needs a header row.
+fn open_table_file(
+    config: &DumpConfig,
+    table_name: &str,
+    part: Option<usize>,
+) -> Result<(output::Sink, bool), DumperError> {
+    let dir = std::path::Path::new(&config.output_dir);
+    let path = dir.join(format!(
+        "{}{}",
+        output::file_name(config, table_name, part),
+        config.file_compression().extension()
+    ));
+    output::open_file(config, &path)
+}
+
+/// Write the rows of `table_name` to `sink`, preceded by the header row when
+/// `header` is set. With `rows_per_file` and `next_part`, the rows are split
+/// over several sinks. Returns the last sink and the rows written.
+fn write_table(
+    config: &DumpConfig,
+    table_name: &str,
+    sink: output::Sink,
+    header: bool,
+    next_part: Option<formats::NextPart<'_>>,
+    progress: Option<&progress::DumpProgress>,
+) -> Result<(output::Sink, RowsWritten), DumperError> {
+    let file = output::OutputWriter::new(sink, config)?;
+    let mut writer: Box<dyn formats::TableWriter> =
+        formats::new_table_writer(config, table_name, file, header)?;
+    if let (Some(rows_per_file), Some(next_part)) = (config.rows_per_file, next_part) {
+        writer = Box::new(formats::PartedWriter::new(
+            writer,
+            config,
+            table_name,
+            rows_per_file,
+            next_part,
+        ));
+    }
+    let written = write_rows(config, table_name, writer.as_mut(), progress)?;
+    let sink = writer.finish()?;
+    Ok((sink, written))
+}
+
+/// What [`write_rows`] wrote of a table.
+struct RowsWritten {
+    rows: u64,
+    /// Largest rowid among the rows, when the query reads the rowid.
+    max_rowid: Option<i64>,
+    /// Largest integer value of the timestamp column, when the rows are
+    /// selected by timestamp.
+    max_timestamp: Option<i64>,
+}
+
+/// Header name of the column added with `row_number`.
+const ROW_NUMBER_COLUMN: &str = "_row_number";
+
+/// Columns of a table query that are written, and their header names.
+struct ColumnLayout {
+    source: Vec<String>,
+    /// Indices of the source columns that are not excluded.
+    kept: Vec<usize>,
+    /// Whether each source column gets a parsed timestamp sibling.
+    is_timestamp: Vec<bool>,
+    /// Whether each source column is one of the `bool_columns`.
+    is_bool: Vec<bool>,
+    /// Keys of each source column flattened with `flatten_json`.
+    json_paths: Vec<Vec<flatten::JsonPath>>,
+    names: Vec<String>,
+    /// With `sort_columns`, the position among the unsorted fields of each
+    /// written field.
+    order: Option<Vec<usize>>,
+}
+
+impl ColumnLayout {
+    /// With `rowid`, the first source column is the rowid, which is only
+    /// written with `include_rowid`.
+    fn new(
+        config: &DumpConfig,
+        table_name: &str,
+        source: Vec<String>,
+        rowid: bool,
+        json_paths: Vec<Vec<flatten::JsonPath>>,
+    ) -> ColumnLayout {
+        let is_timestamp = timestamp::detect_columns(&source, config);
+        let bool_columns = config.bool_columns.get(table_name);
+        let is_bool = source
+            .iter()
+            .map(|column| bool_columns.is_some_and(|columns| columns.contains(column)))
+            .collect();
+        let hidden_rowid = rowid && !config.include_rowid;
+        let mut kept: Vec<usize> = (0..source.len())
+            .filter(|&i| !(hidden_rowid && i == 0))
+            .filter(|&i| !query::is_excluded(config, table_name, &source[i]))
+            .collect();
+        if let Some(order) = config.column_order.get(table_name) {
+            let (mut listed, rest): (Vec<usize>, Vec<usize>) =
+                kept.iter().partition(|&&i| order.contains(&source[i]));
+            listed.sort_by_key(|&i| order.iter().position(|name| *name == source[i]));
+            listed.extend(rest);
+            kept = listed;
+        }
+        let mut names = Vec::with_capacity(source.len() + 1);
+        if config.row_number {
+            names.push(ROW_NUMBER_COLUMN.to_string());
+        }
+        for &i in &kept {
+            names.push(source[i].clone());
+            if is_timestamp[i] {
+                names.push(timestamp::parsed_column_name(&source[i], &source));
+            }
+            for path in &json_paths[i] {
+                names.push(flatten::column_name(&source[i], path));
+            }
+        }
+        let order = config.sort_columns.then(|| {
+            // The row number stays in front.
+            let first = usize::from(config.row_number);
+            let mut order: Vec<usize> = (0..names.len()).collect();
+            order[first..].sort_by_key(|&i| names[i].to_lowercase());
+            names = order.iter().map(|&i| names[i].clone()).collect();
+            order
+        });
+        ColumnLayout {
+            source,
+            kept,
+            is_timestamp,
+            is_bool,
+            json_paths,
+            names,
+            order,
+        }
+    }
+}
+
+/// A prepared dump query of a table.
+struct PreparedTable<'c> {
+    stmt: rusqlite::Statement<'c>,
+    query: query::Query,
+    /// Whether the rowid is the first column.
+    rowid: bool,
+    /// Timestamp column the rows are selected by.
+    timestamp_column: Option<String>,
+}
+
+/// Prepare the dump query of `table_name`, or the named query called
+/// `table_name`.
+fn prepare_table<'c>(
+    conn: &'c rusqlite::Connection,
+    config: &DumpConfig,
+    table_name: &str,
+) -> Result<PreparedTable<'c>, DumperError> {
+    if let Some(named) = config.queries.iter().find(|q| q.name == table_name) {
+        tracing::debug!("Query for {table_name}: {}", named.sql);
+        let query = query::Query {
+            sql: named.sql.clone(),
+            params: Vec::new(),
+        };
+        return Ok(PreparedTable {
+            stmt: named_query::prepare(conn, named)?,
+            query,
+            rowid: false,
+            timestamp_column: None,
+        });
+    }
+    query::validate_columns(conn, config, table_name)?;
+    if let Some(pivot) = config.pivot.get(table_name) {
+        let Some(column) = timestamp::table_column(conn, config, table_name)? else {
+            return Err(DumperError::InvalidConfig(format!(
+                "table '{table_name}' has no timestamp column to pivot by"
+            )));
+        };
+        let since: Vec<(&str, i64)> = timestamp::since(config, table_name)
+            .map(|value| (column.as_str(), value))
+            .into_iter()
+            .collect();
+        let query = pivot::build_query(conn, config, table_name, pivot, &column, &since)?;
+        return Ok(PreparedTable {
+            stmt: conn.prepare(&query.sql)?,
+            query,
+            rowid: false,
+            timestamp_column: Some(column),
+        });
+    }
+    let since_rowid = config.since_rowid.get(table_name).copied();
+    let wants_rowid = config.include_rowid
+        || since_rowid.is_some()
+        || config.rowid_state_file.is_some()
+        || config.sample_seed.is_some();
+    let rowid = wants_rowid && query::has_rowid(conn, table_name)?;
+    if config.include_rowid && !rowid {
+        tracing::info!("Table {table_name} has no separate rowid, dumping it without one");
+    }
+    if since_rowid.is_some() && !rowid {
+        tracing::warn!("Table {table_name} has no rowid, dumping all of its rows");
+    }
+    if config.sample_seed.is_some() && !rowid {
+        tracing::warn!("Table {table_name} has no rowid, its sample is not repeatable");
+    }
+    let since_timestamp = timestamp::since(config, table_name);
+    let timestamp_column = match since_timestamp.is_some() || config.timestamp_state_file.is_some()
+    {
+        true => timestamp::table_column(conn, config, table_name)?,
+        false => None,
+    };
+    if since_timestamp.is_some() && timestamp_column.is_none() {
+        tracing::warn!("Table {table_name} has no timestamp column, dumping all of its rows");
+    }
+    let mut since = Vec::new();
+    if let (Some(rowid_value), true) = (since_rowid, rowid) {
+        since.push(("rowid", rowid_value));
+    }
+    if let (Some(value), Some(column)) = (since_timestamp, &timestamp_column) {
+        since.push((column.as_str(), value));
+    }
+    let query = query::build_query(config, table_name, rowid, &since);
+    match conn.prepare(&query.sql) {
+        Ok(stmt) => Ok(PreparedTable {
+            stmt,
+            query,
+            rowid,
+            timestamp_column,
+        }),
+        Err(source) if is_view(conn, table_name) => Err(DumperError::BrokenView {
+            view: table_name.to_string(),
+            source,
+        }),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Header names `table_name` is dumped with.
+fn table_columns(config: &DumpConfig, table_name: &str) -> Result<Vec<String>, DumperError> {
+    let conn = create_db_connection_ro(config)?;
+    let prepared = prepare_table(&conn, config, table_name)?;
+    let source: Vec<String> = prepared
+        .stmt
+        .column_names()
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let json_paths = flatten::detect(&conn, config, &prepared.query, source.len())?;
+    Ok(ColumnLayout::new(config, table_name, source, prepared.rowid, json_paths).names)
+}
+
+/// Write the header and the rows of `table_name` to `writer`.
+fn write_rows(
+    config: &DumpConfig,
+    table_name: &str,
+    writer: &mut dyn formats::TableWriter,
+    progress: Option<&progress::DumpProgress>,
+) -> Result<RowsWritten, DumperError> {
+    let conn = create_db_connection_ro(config)?;
+    let PreparedTable {
+        mut stmt,
+        query,
+        rowid,
+        timestamp_column,
+    } = prepare_table(&conn, config, table_name)?;
+    let source: Vec<String> = stmt.column_names().iter().map(|s| s.to_string()).collect();
+    let json_paths = flatten::detect(&conn, config, &query, source.len())?;
+    let columns = ColumnLayout::new(config, table_name, source, rowid, json_paths);
+    let timestamp_index =
+        timestamp_column.and_then(|name| columns.source.iter().position(|column| *column == name));
+    let redact_rules = redact::column_rules(config, table_name, &columns.source);
+    let redacting = config.redact_pattern.is_some() || !config.redact_columns.is_empty();
+
+    // Write header;
+    writer.write_header(&columns.names)?;
+
+    tracing::info!("Column name: {:?}", columns.names);
+    let bar = match progress {
+        Some(progress) => {
+            let row_count: u64 = conn.query_row(
+                &format!("SELECT COUNT(*) FROM ({})", query.sql),
+                rusqlite::params_from_iter(&query.params),
+                |row| row.get(0),
+            )?;
+            Some(progress.table_bar(table_name, row_count))
+        }
+        None => None,
+    };
+    let mut rows = stmt.query(rusqlite::params_from_iter(&query.params))?;
+    let mut fields = Vec::with_capacity(columns.names.len());
+    let mut sorted = Vec::with_capacity(columns.names.len());
+    let mut row_num: u64 = 0;
+    let mut max_rowid = None;
+    let mut max_timestamp = None;
+    let mut warned_special_floats = false;
+
+    while let Some(row) = rows.next()? {
+        if config.cancel.is_cancelled() {
+            return Err(DumperError::Cancelled);
+        }
+        fields.clear();
+        // The rowid when it is dumped, otherwise the 1-based row number.
+        let row_id = match rowid {
+            true => row.get::<_, i64>(0)?,
+            false => row_num as i64 + 1,
+        };
+        if rowid {
+            max_rowid = max_rowid.max(Some(row_id));
+        }
+        if let Some(i) = timestamp_index
+            && let rusqlite::types::ValueRef::Integer(ts) = row.get_ref(i)?
+        {
+            max_timestamp = max_timestamp.max(Some(ts));
+        }
+        if config.row_number {
+            fields.push(Field::Integer(row_num as i64 + 1));
+        }
+        for &i in &columns.kept {
+            let mut field = Field::from_value_ref(row.get_ref(i)?)?;
+            if columns.is_bool[i] {
+                field = field.boolean(config.bool_as_int);
+            }
+            if redacting {
+                // Before the parsed and flattened columns, which would show
+                // the value.
+                field = redact::redact(field, &redact_rules[i], config);
+            }
+            if !config.normalize_floats
+                && !warned_special_floats
+                && matches!(field, Field::Real(f) if !f.is_finite())
+            {
+                tracing::warn!(
+                    table = table_name,
+                    "Table {table_name} has NaN or infinite REAL values, they are written as NaN, inf and -inf"
+                );
+                warned_special_floats = true;
+            }
+            let field = blob::limit_blob(field, config, table_name, &columns.source[i], row_id)?;
+            let parsed = match columns.is_timestamp[i] {
+                true => Some(timestamp::parse_timestamp(&field, config)),
+                false => None,
+            };
+            let flattened = flatten::flatten(&field, &columns.json_paths[i]);
+            fields.push(field.truncated(config));
+            if let Some(a) = parsed {
+                fields.push(Field::Text(a));
+            }
+            fields.extend(flattened.into_iter().map(|field| field.truncated(config)));
+        }
+        match &columns.order {
+            Some(order) => {
+                sorted.clear();
+                sorted.extend(
+                    order
+                        .iter()
+                        .map(|&i| std::mem::replace(&mut fields[i], Field::Null)),
+                );
+                writer.write_row(&sorted)?;
+            }
+            None => writer.write_row(&fields)?,
+        }
+        row_num += 1;
+        if let Some(bar) = &bar
+            && row_num.is_multiple_of(progress::UPDATE_INTERVAL)
+        {
+            bar.set_position(row_num);
+        }
+    }
+    if let Some(progress) = progress {
+        progress.table_done(bar);
+    }
+
+    if config.sample.is_some() {
+        tracing::info!(
+            table = table_name,
+            rows = row_num,
+            "Table {table_name}: sampled {row_num} rows"
+        );
+    }
+
+    drop(rows);
+    drop(stmt);
+    close_db_connection(conn, config);
+    Ok(RowsWritten {
+        rows: row_num,
+        max_rowid,
+        max_timestamp,
+    })
+}
+
+/// Open the database of `config` read-only and run its pragmas.
+fn create_db_connection_ro(config: &DumpConfig) -> Result<rusqlite::Connection, DumperError> {
+    let conn = rusqlite::Connection::open_with_flags(
+        &config.db_path,
+        rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+    )?;
+    if config.mmap_size > 0 {
+        conn.pragma_update(None, "mmap_size", config.mmap_size as i64)?;
+    }
+    if let Some(cache_size) = config.cache_size {
+        conn.pragma_update(None, "cache_size", cache_size)?;
+    }
+    pragma::apply(&conn, &config.pragmas)?;
+    for (alias, path) in &config.attach {
+        // Attached databases are opened with the flags of the main one, so
+        // they are read-only too.
+        conn.execute(
+            &format!("ATTACH DATABASE ?1 AS {}", query::quote_identifier(alias)),
+            [path],
+        )
+        .map_err(|source| DumperError::AttachFailed {
+            path: path.clone(),
+            source,
+        })?;
+    }
+    Ok(conn)
+}
+
+/// Detach the databases of `attach` and close `conn`, logging errors.
+fn close_db_connection(conn: rusqlite::Connection, config: &DumpConfig) {
+    for (alias, _) in &config.attach {
+        let detach = format!("DETACH DATABASE {}", query::quote_identifier(alias));
+        if let Err(err) = conn.execute(&detach, []) {
+            tracing::error!("Error while detaching database {alias}. {err}");
+        }
+    }
+    match conn.close() {
+        Ok(()) => {}
+        Err((_, err)) => {
+            tracing::error!("Error while closing db connection. {err}");
+        }
+    }
+}
+
+/// Run `PRAGMA wal_checkpoint(PASSIVE)` on a short-lived read-write
+/// connection so that the dump sees transactions still in the WAL file. Does
+/// nothing unless the database is in WAL mode.
+pub fn checkpoint_wal(config: &DumpConfig) -> Result<(), DumperError> {
+    let conn = rusqlite::Connection::open_with_flags(
+        &config.db_path,
+        rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE,
+    )?;
+    let journal_mode: String = conn.query_row("PRAGMA journal_mode", [], |row| row.get(0))?;
+    if journal_mode.eq_ignore_ascii_case("wal") {
+        let (busy, log_frames, checkpointed): (i64, i64, i64) =
+            conn.query_row("PRAGMA wal_checkpoint(PASSIVE)", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?;
+        tracing::info!("WAL checkpoint: {checkpointed} of {log_frames} frames checkpointed");
+        if busy != 0 {
+            tracing::warn!("WAL checkpoint could not complete, the database is busy");
+        }
+    } else {
+        tracing::info!("Database is in {journal_mode} mode, skipping WAL checkpoint");
+    }
+    match conn.close() {
+        Ok(()) => {}
+        Err((_, err)) => {
+            tracing::error!("Error while closing db connection. {err}");
+        }
+    }
+    Ok(())
+}
+
+/// Run `PRAGMA integrity_check`, or the faster `PRAGMA quick_check` with
+/// `quick`, and fail with every reported problem unless the result is `ok`.
+pub fn check_integrity(config: &DumpConfig, quick: bool) -> Result<(), DumperError> {
+    let conn = create_db_connection_ro(config)?;
+    let pragma = if quick {
+        "quick_check"
+    } else {
+        "integrity_check"
+    };
+    let mut stmt = conn.prepare(&format!("PRAGMA {pragma}"))?;
+    let messages = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    drop(stmt);
+    close_db_connection(conn, config);
+    if messages != ["ok"] {
+        return Err(DumperError::IntegrityCheckFailed(messages));
+    }
+    tracing::info!("PRAGMA {pragma}: ok");
+    Ok(())
+}
+
+/// File name of the compacted copy made with `vacuum_first`.
+const VACUUMED_FILENAME: &str = "vacuumed.sqlite";
+
+/// Copy the database of `config` with `VACUUM INTO` to a new temporary
+/// directory, removed with the returned value. Needs a read-write
+/// connection, the database itself is not changed.
+fn vacuum_into_temp_dir(config: &DumpConfig) -> Result<tempfile::TempDir, DumperError> {
+    let dir = tempfile::Builder::new().prefix("sqlitedumper-").tempdir()?;
+    let path = dir.path().join(VACUUMED_FILENAME);
+    let conn = rusqlite::Connection::open_with_flags(
+        &config.db_path,
+        rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE,
+    )?;
+    let start_time = std::time::Instant::now();
+    conn.execute("VACUUM INTO ?1", [path.display().to_string()])?;
+    drop(conn);
+    let original = std::fs::metadata(&config.db_path)?.len();
+    let vacuumed = std::fs::metadata(&path)?.len();
+    let elapsed_ms = start_time.elapsed().as_millis() as u64;
+    tracing::info!(
+        database = config.db_path,
+        original,
+        vacuumed,
+        elapsed_ms,
+        "Vacuumed {} into {}: {original} bytes before, {vacuumed} bytes after. Elapsed {elapsed_ms} ms",
+        config.db_path,
+        path.display()
+    );
+    Ok(dir)
+}
+
+/// Pages copied by each step of [`backup_database`].
+const BACKUP_PAGES_PER_STEP: std::ffi::c_int = 1024;
+
+/// Copy the database of `config` to `path` with the online backup API. The
+/// returned path deletes the copy when dropped.
+fn backup_database(config: &DumpConfig, path: &str) -> Result<tempfile::TempPath, DumperError> {
+    if !config.overwrite && std::path::Path::new(path).exists() {
+        return Err(DumperError::OutputExists(path.into()));
+    }
+    let start_time = std::time::Instant::now();
+    let src = rusqlite::Connection::open_with_flags(
+        &config.db_path,
+        rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+    )?;
+    let mut dst = rusqlite::Connection::open(path)?;
+    let backup_path = tempfile::TempPath::try_from_path(path)?;
+    {
+        let backup = rusqlite::backup::Backup::new(&src, &mut dst)?;
+        loop {
+            match backup.step(BACKUP_PAGES_PER_STEP)? {
+                rusqlite::backup::StepResult::Done => break,
+                rusqlite::backup::StepResult::More => {
+                    let progress = backup.progress();
+                    tracing::debug!(
+                        database = config.db_path,
+                        remaining = progress.remaining,
+                        pages = progress.pagecount,
+                        "Backup of {}: {} of {} pages copied",
+                        config.db_path,
+                        progress.pagecount - progress.remaining,
+                        progress.pagecount
+                    );
+                }
+                _ => std::thread::sleep(std::time::Duration::from_millis(100)),
+            }
+        }
+    }
+    drop(dst);
+    let elapsed_ms = start_time.elapsed().as_millis() as u64;
+    tracing::info!(
+        database = config.db_path,
+        elapsed_ms,
+        "Backed up {} to {path}. Elapsed {elapsed_ms} ms",
+        config.db_path
+    );
+    Ok(backup_path)
+}
+
+/// Compile options of the linked SQLite library, from `PRAGMA
+/// compile_options` on an in-memory database.
+pub fn sqlite_compile_options() -> Result<Vec<String>, DumperError> {
+    let conn = rusqlite::Connection::open_in_memory()?;
+    let mut stmt = conn.prepare("PRAGMA compile_options")?;
+    let options = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    Ok(options)
+}
+
+fn is_view(conn: &rusqlite::Connection, name: &str) -> bool {
+    conn.query_row(
+        "SELECT 1 FROM sqlite_master WHERE type='view' AND name = ?1",
+        [name],
+        |_| Ok(()),
+    )
+    .is_ok()
+}
+
+/// List the user tables of the database, skipping `sqlite_*` internal tables
+/// unless `include_system_tables` is set.
+///
+/// Views are included with `include_views`, or returned alone with `views_only`.
+pub fn get_tables(config: &DumpConfig) -> Result<Vec<String>, DumperError> {
+    let conn = create_db_connection_ro(config)?;
+    let types = if config.views_only {
+        "'view'"
+    } else if config.include_views {
+        "'table', 'view'"
+    } else {
+        "'table'"
+    };
+    let system_tables = match config.include_system_tables {
+        true => "",
+        false => r" AND name NOT LIKE 'sqlite\_%' ESCAPE '\'",
+    };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT name FROM sqlite_master WHERE type IN ({types}){system_tables};"
+    ))?;
+    let table_names_row = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    let mut table_names = Vec::new();
+    for table_name_res in table_names_row {
+        match table_name_res {
+            Ok(table_name) => {
+                if table_name.starts_with("sqlite_") {
+                    tracing::warn!(
+                        "⚠️ Including system table {table_name}, it holds internal SQLite metadata"
+                    );
+                }
+                tracing::info!("Found table name {table_name}");
+                table_names.push(table_name);
+            }
+            Err(e) => tracing::error!("⚠️ Error while getting table name {e}"),
+        }
+    }
+
+    drop(stmt);
+    close_db_connection(conn, config);
+    Ok(table_names)
+}
+
+/// Count the rows of every table selected by `config` over one connection.
+pub fn count_tables(config: &DumpConfig) -> Result<Vec<(String, u64)>, DumperError> {
+    let table_names = get_tables(config)?;
+    let table_names = config.tables_filter.apply(table_names)?;
+    let conn = create_db_connection_ro(config)?;
+    let mut counts = Vec::with_capacity(table_names.len());
+    for table_name in table_names {
+        let sql = format!(
+            "SELECT COUNT(*) FROM {}",
+            query::quote_identifier(&table_name)
+        );
+        let count: u64 = conn.query_row(&sql, [], |row| row.get(0))?;
+        counts.push((table_name, count));
+    }
+    close_db_connection(conn, config);
+    Ok(counts)
+}
+
+/// Dump every table selected by `config`, one task per table.
+///
+/// Errors of individual tables are logged and recorded in the returned stats.
+/// They do not stop the other tables unless `max_errors` tables failed.
+pub async fn dump_database(config: &DumpConfig) -> Result<DumpStats, DumperError> {
+    let start_time = std::time::Instant::now();
+    let mut vacuum_config;
+    let (config, _vacuumed) = if config.vacuum_first {
+        let dir = vacuum_into_temp_dir(config)?;
+        vacuum_config = config.clone();
+        vacuum_config.db_path = dir.path().join(VACUUMED_FILENAME).display().to_string();
+        (&vacuum_config, Some(dir))
+    } else {
+        (config, None)
+    };
+    let mut backup_config;
+    let (config, _backup) = match &config.backup {
+        Some(path) => {
+            let backup = backup_database(config, path)?;
+            let backup = match config.keep_backup {
+                true => {
+                    backup.keep().map_err(|e| e.error)?;
+                    None
+                }
+                false => Some(backup),
+            };
+            backup_config = config.clone();
+            backup_config.db_path = path.clone();
+            (&backup_config, backup)
+        }
+        None => (config, None),
+    };
+    let mut state_config;
+    let (config, rowid_state, timestamp_state) =
+        match config.rowid_state_file.is_some() || config.timestamp_state_file.is_some() {
+            true => {
+                state_config = config.clone();
+                let (rowid_state, timestamp_state) = state::apply(&mut state_config)?;
+                (&state_config, rowid_state, timestamp_state)
+            }
+            false => (config, None, None),
+        };
+    let delta_config;
+    let (config, table_names, unmatched_tables) = match &config.compare_db {
+        Some(compare_path) => {
+            let (delta, names, unmatched) = delta::delta_config(config, compare_path)?;
+            delta_config = delta;
+            (&delta_config, Some(names), unmatched)
+        }
+        None => (config, None, Vec::new()),
+    };
+    let table_names = if let Some(names) = table_names {
+        names
+    } else if config.queries.is_empty() {
+        let table_names = get_tables(config)?;
+        config.tables_filter.apply(table_names)?
+    } else {
+        named_query::check_queries(config)?;
+        config.queries.iter().map(|q| q.name.clone()).collect()
+    };
+    query::check_column_order(config, &table_names)?;
+
+    let tables = if let Some(archive_path) = &config.output_zip {
+        archive::dump_zip(config, &table_names, archive_path)?
+    } else if let Some(archive_path) = &config.output_tar {
+        archive::dump_tar(config, &table_names, archive_path).await?
+    } else if let Some(path) = &config.single_file {
+        single_file::dump_single_file(config, &table_names, path)?
+    } else {
+        // ダンプ先ディレクトリ作成
+        let dump_path = std::path::Path::new(&config.output_dir);
+        tokio::fs::create_dir_all(dump_path).await?;
+
+        if config.schema_only {
+            schema::dump_schema(config, &table_names)?;
+            table_names
+                .iter()
+                .map(|table_name| TableStats::new(table_name, start_time))
+                .collect()
+        } else {
+            dump_tables(config, &table_names).await
+        }
+    };
+    if config.include_indexes || config.include_triggers || config.include_foreign_keys {
+        let meta_tables = match config.queries.is_empty() {
+            true => table_names.clone(),
+            false => config.tables_filter.apply(get_tables(config)?)?,
+        };
+        tokio::fs::create_dir_all(&config.output_dir).await?;
+        if config.include_indexes {
+            metadata::write_indexes(config, &meta_tables)?;
+        }
+        if config.include_triggers {
+            metadata::write_triggers(config, &meta_tables)?;
+        }
+        if config.include_foreign_keys {
+            metadata::write_foreign_keys(config, &meta_tables)?;
+        }
+    }
+    let files: Vec<&output::FileRecord> = tables.iter().flat_map(|stats| &stats.files).collect();
+    if let Some(path) = &config.output_manifest {
+        manifest::write(config, path, &files)?;
+    }
+    if let Some(algorithm) = config.checksum {
+        checksum::write(config, algorithm, &files)?;
+    }
+    if let (Some(path), Some(rowid_state)) = (&config.rowid_state_file, rowid_state) {
+        let max_rowids = tables
+            .iter()
+            .filter_map(|stats| Some((stats.table.clone(), stats.max_rowid?)));
+        state::update(path, rowid_state, max_rowids)?;
+    }
+    if let (Some(path), Some(timestamp_state)) = (&config.timestamp_state_file, timestamp_state) {
+        let max_timestamps = tables
+            .iter()
+            .filter_map(|stats| Some((stats.table.clone(), stats.max_timestamp?)));
+        state::update(path, timestamp_state, max_timestamps)?;
+    }
+    Ok(DumpStats {
+        elapsed_ms: start_time.elapsed().as_millis() as u64,
+        tables,
+        unmatched_tables,
+    })
+}
+
+/// Dump one table of [`dump_tables`], logging and recording its error.
+async fn dump_table_stats(
+    config: &DumpConfig,
+    table_name: &str,
+    progress: Option<&progress::DumpProgress>,
+    failures: &std::sync::atomic::AtomicUsize,
+) -> TableStats {
+    let start_time = std::time::Instant::now();
+    let mut files = Vec::new();
+    let result = dump_table_with_progress(config, table_name, progress, &mut files).await;
+    let mut stats = TableStats::new(table_name, start_time);
+    stats.files = files;
+    match result {
+        Ok((written, bytes)) => {
+            stats.rows = Some(written.rows);
+            stats.max_rowid = written.max_rowid;
+            stats.max_timestamp = written.max_timestamp;
+            stats.bytes = Some(bytes);
+        }
+        Err(e) => {
+            tracing::error!(table = table_name, error = %e, "Error while handling table {table_name}. {e}");
+            if !matches!(e, DumperError::Cancelled) {
+                let failed = failures.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                if config.max_errors.is_some_and(|max| failed >= max) {
+                    tracing::error!("{failed} tables failed, cancelling the dump");
+                    config.cancel.cancel();
+                }
+            }
+            stats.error = Some(e.to_string());
+        }
+    }
+    let elapsed_ms = start_time.elapsed().as_millis() as u64;
+    tracing::debug!(
+        table = table_name,
+        rows = stats.rows,
+        elapsed_ms,
+        "Table {table_name} dump completed. Elapsed {elapsed_ms} ms"
+    );
+    stats
+}
+
+/// Dump several databases at the same time, each like [`dump_database`] into
+/// a subdirectory of `output_dir` named after the file stem. The tables in the
+/// returned stats are named `<stem>/<table>`.
+pub async fn dump_databases(
+    config: &DumpConfig,
+    db_paths: &[String],
+) -> Result<DumpStats, DumperError> {
+    let start_time = std::time::Instant::now();
+    if config.output_zip.is_some()
+        || config.output_tar.is_some()
+        || config.single_file.is_some()
+        || config.output_manifest.is_some()
+        || config.rowid_state_file.is_some()
+        || config.timestamp_state_file.is_some()
+        || config.compare_db.is_some()
+        || config.backup.is_some()
+    {
+        return Err(DumperError::InvalidConfig(
+            "archives, a single file, the output manifest, state files, a compared database and a backup cannot be used with several databases"
+                .to_string(),
+        ));
+    }
+    let mut stems: Vec<String> = Vec::with_capacity(db_paths.len());
+    for db_path in db_paths {
+        let stem = std::path::Path::new(db_path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| db_path.clone());
+        if stems.contains(&stem) {
+            return Err(DumperError::InvalidConfig(format!(
+                "several databases are named '{stem}', their dumps would share a directory"
+            )));
+        }
+        stems.push(stem);
+    }
+
+    let mut joinhandles = Vec::with_capacity(db_paths.len());
+    for (db_path, stem) in db_paths.iter().zip(&stems) {
+        let mut config = config.clone();
+        config.db_path = db_path.clone();
+        config.output_dir = std::path::Path::new(&config.output_dir)
+            .join(stem)
+            .display()
+            .to_string();
+        joinhandles.push(tokio::spawn(async move { dump_database(&config).await }));
+    }
+
+    let mut tables = Vec::new();
+    let mut first_error = None;
+    for ((db_path, stem), handle) in db_paths.iter().zip(&stems).zip(joinhandles) {
+        match handle.await.map_err(std::io::Error::other) {
+            Ok(Ok(stats)) => tables.extend(stats.tables.into_iter().map(|mut table| {
+                table.table = format!("{stem}/{}", table.table);
+                table
+            })),
+            Ok(Err(e)) => {
+                tracing::error!(database = %db_path, error = %e, "Error while dumping {db_path}. {e}");
+                first_error.get_or_insert(e);
+            }
+            Err(e) => {
+                tracing::error!(database = %db_path, error = %e, "Error while dumping {db_path}. {e}");
+                first_error.get_or_insert(e.into());
+            }
+        }
+    }
+    if let Some(e) = first_error {
+        return Err(e);
+    }
+    Ok(DumpStats {
+        elapsed_ms: start_time.elapsed().as_millis() as u64,
+        tables,
+        ..DumpStats::default()
+    })
+}
+
+/// Dump `table_names` into `output_dir` in parallel, one task per table, or
+/// one after another with `sequential`.
+async fn dump_tables(config: &DumpConfig, table_names: &[String]) -> Vec<TableStats> {
+    let start_time = std::time::Instant::now();
+    let mut config = config.clone();
+    if config.max_errors.is_some() {
+        // Failing tables only cancel the tables of this dump.
+        config.cancel = config.cancel.child_token();
+    }
+    let config = std::sync::Arc::new(config);
+    let failures = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let progress = config
+        .progress
+        .then(|| std::sync::Arc::new(progress::DumpProgress::new(table_names.len())));
+
+    if config.sequential {
+        let mut tables = Vec::with_capacity(table_names.len());
+        for table_name in table_names {
+            tables
+                .push(dump_table_stats(&config, table_name, progress.as_deref(), &failures).await);
+        }
+        if let Some(progress) = progress {
+            progress.finish();
+        }
+        return tables;
+    }
+
+    let permits = config.parallel_tables.unwrap_or(table_names.len()).max(1);
+    let semaphore = std::sync::Arc::new(tokio::sync::Semaphore::new(permits));
+    let mut joinhandles = Vec::new();
+    for tbl_name in table_names.iter() {
+        let table_name = tbl_name.to_string();
+        let config = config.clone();
+        let progress = progress.clone();
+        let semaphore = semaphore.clone();
+        let failures = failures.clone();
+        let jh = tokio::spawn(async move {
+            let _permit = semaphore
+                .acquire_owned()
+                .await
+                .expect("the semaphore is never closed");
+            tracing::debug!("Start thread for table {table_name}");
+            dump_table_stats(&config, &table_name, progress.as_deref(), &failures).await
+        });
+        tracing::debug!("Thread {} created.", tbl_name);
+        joinhandles.push(jh);
+    }
+
+    let mut tables = Vec::with_capacity(table_names.len());
+    for (table_name, handle) in table_names.iter().zip(joinhandles) {
+        match handle.await {
+            Ok(stats) => tables.push(stats),
+            Err(e) => {
+                tracing::error!(table = table_name.as_str(), error = %e, "Task of table {table_name} failed. {e}");
+                let mut stats = TableStats::new(table_name, start_time);
+                stats.error = Some(e.to_string());
+                tables.push(stats);
+            }
+        }
+    }
+    if let Some(progress) = progress {
+        progress.finish();
+    }
+    tables
+}
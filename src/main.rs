@@ -1,206 +1,1519 @@
-use clap::Parser;
+use std::io::IsTerminal;
+
+use clap::{CommandFactory, FromArgMatches, Parser};
+use sqlitedumper::{
+    BlobFormat, ChecksumAlgorithm, Compression, DumpConfig, DumperError, EpochUnit, FloatFormat,
+    LineEnding, MarkdownAlign, OutputFormat, Pivot, Quoting, SqlDialect, TableFilter, ZipPassword,
+};
 
 // Constants and command line options.
 const DB_DEFAULT_FILENAME: &str = "appliance_stats.sqlite";
 const DUMP_OUTPUT_DIR_DEFAULT: &str = "sqlite_dump";
+const NULL_VALUE_DEFAULT: &str = "null";
+
+/// Format of the log lines written to stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum LogFormat {
+    Text,
+    Json,
+}
 
 #[derive(Debug, Parser)]
 struct CommandArguments {
-    /// SQLite database file.
+    /// SQLite database file, or a glob pattern like '*.sqlite'. Can be repeated; several
+    /// databases are dumped at the same time, each into a subdirectory of --dir named
+    /// after the file.
     #[clap(short, long, default_value = DB_DEFAULT_FILENAME)]
-    file: String,
+    file: Vec<String>,
+
+    /// TOML config file with the same settings as the arguments, in a [defaults] section,
+    /// and per table where, columns, exclude_columns, column_order, pivot, bool_columns and
+    /// since_rowid in [table.<name>] sections. Arguments given on the command line replace
+    /// its settings.
+    #[clap(long = "config")]
+    config_file: Option<String>,
+
+    /// Apply the settings of the [profile.<name>] section of the --config file over its
+    /// [defaults].
+    #[clap(long, requires = "config_file")]
+    profile: Option<String>,
+
+    /// Print the effective configuration, merged from --config and the arguments, as TOML
+    /// and exit.
+    #[clap(long = "dump-config")]
+    dump_config: bool,
+
+    /// Print the version, features and compile options of the SQLite library and exit.
+    #[clap(long = "sqlite-version")]
+    sqlite_version: bool,
 
     /// Log level. One of trace, debug, info, wanr, error.
     #[clap(short, long, default_value = "NONE")]
     log: String,
 
+    /// Log line format. json writes one object per event with timestamp, level, target,
+    /// message and the fields of the event.
+    #[clap(long = "log-format", value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+
+    /// Also append the log to this file, at the trace level and with full timestamps
+    /// regardless of --log.
+    #[clap(long = "log-file")]
+    log_file: Option<String>,
+
     /// Output directory.
     #[clap(short, long, default_value = DUMP_OUTPUT_DIR_DEFAULT)]
     dir: String,
 
-    /// Same as --log debug.
-    #[arg(short, long, action = clap::ArgAction::SetTrue)]
+    /// Same as --log trace.
+    #[arg(short, long, action = clap::ArgAction::SetTrue, conflicts_with = "quiet")]
     verbose: bool,
+
+    /// Only log errors, regardless of --log, and hide progress bars.
+    #[arg(short, long, action = clap::ArgAction::SetTrue)]
+    quiet: bool,
+
+    /// Output file name template relative to --dir. {table}, {date} (YYYYMMDD), {datetime}
+    /// (UTC, YYYYMMDDTHHMMSSZ) and {ext} are substituted, e.g. "{date}/{table}_{datetime}.{ext}".
+    #[clap(long = "output-pattern", default_value = "{table}.{ext}")]
+    output_pattern: String,
+
+    /// Split each table into files of at most this many rows, named <table>_partNNN.<ext>.
+    /// Every part gets its own header and compression.
+    #[clap(long = "rows-per-file")]
+    rows_per_file: Option<u64>,
+
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Csv)]
+    format: OutputFormat,
+
+    /// SQL dialect of the INSERT statements written with --format sql.
+    #[clap(long = "sql-dialect", value_enum, default_value_t = SqlDialect::Sqlite)]
+    sql_dialect: SqlDialect,
+
+    /// Alignment of a column written with --format markdown, as column:right. One of left,
+    /// right or center. Can be repeated.
+    #[clap(long = "markdown-align", value_parser = parse_markdown_align)]
+    markdown_align: Vec<(String, MarkdownAlign)>,
+
+    /// CSS class of the <table> element written with --format html.
+    #[clap(long = "html-class")]
+    html_class: Option<String>,
+
+    /// minijinja template file rendering each table written with --format html. It gets
+    /// table, columns, rows (lists of cell texts) and class, all HTML escaped.
+    #[clap(long = "html-template")]
+    html_template: Option<String>,
+
+    /// CSV field delimiter. A single ASCII character; use \t for tab.
+    #[clap(long, default_value = ",", value_parser = parse_delimiter)]
+    delimiter: u8,
+
+    /// Start CSV files with a UTF-8 byte order mark, so that Excel on Windows reads them as
+    /// UTF-8. Files rows are appended to get none.
+    #[clap(long = "csv-bom")]
+    csv_bom: bool,
+
+    /// Line ending of CSV records. platform is crlf on Windows and lf elsewhere. Compressed
+    /// files keep it.
+    #[clap(long = "line-ending", value_enum, default_value_t = LineEnding::Lf)]
+    line_ending: LineEnding,
+
+    /// Character CSV fields are quoted with.
+    #[clap(long = "quote-char", default_value = "\"", value_parser = parse_csv_char)]
+    quote_char: u8,
+
+    /// Character escaping quotes inside quoted CSV fields, e.g. \\ for PostgreSQL COPY. The
+    /// default, the quote character, doubles quotes as RFC 4180 does.
+    #[clap(long = "escape-char", default_value = "\"", value_parser = parse_csv_char)]
+    escape_char: u8,
+
+    /// When CSV fields are quoted.
+    #[clap(long, value_enum, default_value_t = Quoting::Necessary)]
+    quoting: Quoting,
+
+    /// Write CSV text in this encoding, like windows-1252 or iso-8859-1, instead of UTF-8.
+    /// Characters it cannot represent are written as ? and counted in a warning per table.
+    #[clap(long, value_parser = parse_encoding)]
+    encoding: Option<&'static encoding_rs::Encoding>,
+
+    /// Dump only the given table. Can be repeated.
+    #[clap(short, long = "tables")]
+    tables: Vec<String>,
+
+    /// Dump only the tables named in this file, one per line. Empty lines and lines
+    /// starting with # are ignored, tables that do not exist are skipped with a warning.
+    #[clap(long = "table-list-file")]
+    table_list_file: Option<String>,
+
+    /// Skip tables matching the given name or glob pattern (e.g. 'audit_*'). Can be repeated.
+    #[clap(short = 'x', long = "exclude-tables", value_parser = glob::Pattern::new)]
+    exclude_tables: Vec<glob::Pattern>,
+
+    /// Dump only tables whose name matches this regular expression.
+    #[clap(long = "table-pattern")]
+    table_pattern: Option<String>,
+
+    #[clap(skip)]
+    table_regex: Option<regex::Regex>,
+
+    /// Dump the results of the `;` separated SELECT statements in this SQL file instead of
+    /// the tables. A `-- name: foo` comment before a query names its output file, otherwise
+    /// it is named query_N.
+    #[clap(long = "query-file")]
+    query_file: Option<String>,
+
+    /// Dump the result of a query to <name>.<ext>, e.g.
+    /// "revenue=SELECT product, SUM(amount) FROM orders GROUP BY product".
+    /// Can be repeated, the queries run in order after those of --query-file.
+    #[clap(long = "query", value_parser = parse_query)]
+    query: Vec<sqlitedumper::NamedQuery>,
+
+    /// Run "PRAGMA key = value" on every database connection, e.g. cache_size=-64000.
+    /// Can be repeated. The connection is read-only, so only pragmas that affect
+    /// reading work: cache_size, mmap_size, temp_store, busy_timeout, page_size and
+    /// the like. Others are run with a warning.
+    #[clap(long = "pragma", value_parser = parse_pragma)]
+    pragma: Vec<(String, String)>,
+
+    /// Let SQLite memory-map up to this many bytes of the database, e.g. 512M or 2G.
+    /// Pages are then read through the OS page cache instead of being copied into
+    /// SQLite's cache. Dumping a 140 MB table to CSV got about 1-2% faster, as formatting
+    /// dominates; the gain is larger for scans that write little. 0 disables it.
+    #[clap(long = "mmap-size", value_parser = parse_size, default_value = "0")]
+    mmap_size: u64,
+
+    /// SQLite page cache of each connection. A plain number is a count of pages, a size
+    /// like 64M is converted to KiB. Unlike --mmap-size this memory belongs to SQLite.
+    /// Defaults to SQLite's default of 2000 KiB.
+    #[clap(long = "cache-size", value_parser = parse_cache_size)]
+    cache_size: Option<i64>,
+
+    /// Attach another database read-only to every connection as "alias=path.sqlite", so
+    /// that --query can join across files, e.g. FROM main.events JOIN metrics.metrics.
+    /// Can be repeated.
+    #[clap(long = "attach", value_parser = parse_attach)]
+    attach: Vec<(String, String)>,
+
+    /// Compare with an older snapshot of the database and dump only the differences: rows
+    /// only in --file go to <table>_added, rows only in this database to <table>_deleted.
+    /// A changed row appears in both. Tables found in only one database are listed in
+    /// the stats file.
+    #[clap(long = "compare-db")]
+    compare_db: Option<String>,
+
+    /// Dump views in addition to tables.
+    #[clap(long = "include-views")]
+    include_views: bool,
+
+    /// Dump only views.
+    #[clap(long = "views-only")]
+    views_only: bool,
+
+    /// Also dump the sqlite_* internal tables, such as sqlite_sequence and sqlite_stat1.
+    #[clap(long = "include-system-tables")]
+    include_system_tables: bool,
+
+    /// Write the CREATE statements of each table to <table>.sql instead of its rows.
+    #[clap(long = "schema-only")]
+    schema_only: bool,
+
+    /// With --schema-only, write all DDL into a single schema.sql.
+    #[clap(long = "schema-file")]
+    schema_file: bool,
+
+    /// Write the indexes of the dumped tables to _indexes.csv (name, table, SQL, unique) and
+    /// their columns to _index_columns.csv. With --schema-only, also write the CREATE INDEX
+    /// statements of each table.
+    #[clap(long = "include-indexes")]
+    include_indexes: bool,
+
+    /// Write the triggers of the dumped tables to _triggers.csv (name, table, timing, event,
+    /// SQL). With --schema-only, also write the CREATE TRIGGER statements of each table.
+    #[clap(long = "include-triggers")]
+    include_triggers: bool,
+
+    /// Write the foreign keys of the dumped tables to a single _foreign_keys.csv.
+    #[clap(long = "include-foreign-keys")]
+    include_foreign_keys: bool,
+
+    /// Dump only these columns of a table, as table:col1,col2. Can be repeated.
+    #[clap(long = "columns", value_parser = parse_table_columns)]
+    columns: Vec<(String, Vec<String>)>,
+
+    /// Leave these columns out of every table, e.g. password_hash,ssn. Case-insensitive.
+    #[clap(long = "exclude-columns", value_delimiter = ',')]
+    exclude_columns: Vec<String>,
+
+    /// Leave these columns out of a table, as table:col1,col2. Can be repeated.
+    #[clap(long = "exclude-columns-table", value_parser = parse_table_columns)]
+    exclude_columns_table: Vec<(String, Vec<String>)>,
+
+    /// Write these columns of a table first, in this order, as table:col3,col1. The other
+    /// columns follow in schema order. Can be repeated.
+    #[clap(long = "column-order", value_parser = parse_table_columns)]
+    column_order: Vec<(String, Vec<String>)>,
+
+    /// Dump a key-value table in wide format, as table:key_column:value_column: one row
+    /// per value of its timestamp column and one column per distinct key, holding the
+    /// value. The values of rows with a NULL key are dropped with a warning. Keys of
+    /// different types with the same text, like 1 and '1', get the columns 1 and 1_2. Can
+    /// be repeated.
+    #[clap(long, value_parser = parse_pivot)]
+    pivot: Vec<(String, Pivot)>,
+
+    /// Write these integer columns of a table as false for 0 and true for 1, as
+    /// table:col1,col2. NULL stays --null-value. Can be repeated.
+    #[clap(long = "bool-columns", value_parser = parse_table_columns)]
+    bool_columns: Vec<(String, Vec<String>)>,
+
+    /// Write the --bool-columns stored as the text false and true as 0 and 1 instead.
+    #[clap(long = "bool-as-int", requires = "bool_columns")]
+    bool_as_int: bool,
+
+    /// Write the values of a column as [REDACTED], as table:column, or only the values
+    /// matching a glob pattern, as table:column:pattern, e.g. users:email:*@*. Can be
+    /// repeated.
+    #[clap(long = "redact-columns", value_parser = parse_redact_column)]
+    redact_columns: Vec<sqlitedumper::RedactColumn>,
+
+    /// Replace the parts of text values matching this regular expression with [REDACTED],
+    /// in every column of every table.
+    #[clap(long = "redact-pattern", value_parser = regex::Regex::new)]
+    redact_pattern: Option<regex::Regex>,
+
+    /// Write the SHA-256 hex digest of redacted values instead of [REDACTED], so that equal
+    /// values can still be matched.
+    #[clap(long = "redact-hash")]
+    redact_hash: bool,
+
+    /// Write the columns of every table in alphabetical order, ignoring case. Parsed
+    /// timestamp columns sort by their own name; a --row-number column stays first.
+    #[clap(long = "sort-columns", conflicts_with = "column_order")]
+    sort_columns: bool,
+
+    /// Add the rowid as the first column. Skipped for WITHOUT ROWID tables and views.
+    #[clap(long = "include-rowid")]
+    include_rowid: bool,
+
+    /// Add a _row_number column before all others, counting the rows of each table from 1.
+    #[clap(long = "row-number")]
+    row_number: bool,
+
+    /// Dump only the rows of a table with a rowid above a value, as "table:12345".
+    /// Can be repeated. Tables without a rowid are dumped fully.
+    #[clap(long = "since-rowid", value_parser = parse_since_rowid)]
+    since_rowid: Vec<(String, i64)>,
+
+    /// Keep the largest rowid dumped of each table in this JSON file and continue from it on
+    /// the next run. --since-rowid takes precedence for its tables.
+    #[clap(long = "rowid-state-file")]
+    rowid_state_file: Option<String>,
+
+    /// Dump only the rows whose timestamp column (sm_timestamp, timestamp or a
+    /// --timestamp-column) is after this time, e.g. "2024-01-15T00:00:00Z". The column is
+    /// compared as an integer epoch in the --epoch-ms/--epoch-us unit. Tables without a
+    /// timestamp column are dumped fully.
+    #[clap(long = "since-timestamp", value_parser = parse_since_timestamp)]
+    since_timestamp: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Keep the largest timestamp dumped of each table in this JSON file and continue from
+    /// it on the next run. The later of it and --since-timestamp is used.
+    #[clap(long = "timestamp-state-file")]
+    timestamp_state_file: Option<String>,
+
+    /// Dump only rows matching this SQL predicate, e.g. "status = 'active'".
+    #[clap(long = "where")]
+    where_clause: Option<String>,
+
+    /// Per-table predicate as table=predicate, e.g. "events=status='active'". Can be repeated.
+    #[clap(long = "table-where", value_parser = parse_table_where)]
+    table_where: Vec<(String, String)>,
+
+    /// Dump at most this many rows per table.
+    #[clap(long)]
+    limit: Option<u64>,
+
+    /// Skip this many rows of each table.
+    #[clap(long)]
+    offset: Option<u64>,
+
+    /// Dump a random sample of this many rows of each table instead of all rows.
+    #[clap(long, conflicts_with_all = ["limit", "offset", "order_by", "order_by_rowid"])]
+    sample: Option<u64>,
+
+    /// Pick the same --sample on every run, by shuffling the rowids with this seed. Tables
+    /// without a rowid still get a random sample.
+    #[clap(long = "sample-seed", requires = "sample")]
+    sample_seed: Option<u32>,
+
+    /// Order rows by this SQL expression, e.g. "created_at DESC, id ASC".
+    #[clap(long = "order-by")]
+    order_by: Option<String>,
+
+    /// Order rows by rowid. Comes before --order-by when both are given.
+    #[clap(long = "order-by-rowid")]
+    order_by_rowid: bool,
+
+    /// Compress each output file. With --format parquet, the codec of the Parquet pages
+    /// instead, Snappy by default.
+    #[clap(long, value_enum, default_value_t = Compression::None)]
+    compress: Compression,
+
+    /// Maximum number of rows per row group with --format parquet. The column types are
+    /// inferred from the first row group.
+    #[clap(long = "parquet-row-group-size", default_value_t = 100_000)]
+    parquet_row_group_size: usize,
+
+    /// Compression level 1-22, clamped to the range of the algorithm (gzip 1-9, zstd 1-22).
+    /// Ignored for lz4.
+    #[clap(long = "compress-level", value_parser = clap::value_parser!(i32).range(1..=22))]
+    compress_level: Option<i32>,
+
+    /// Pre-trained zstd dictionary used with --compress zstd.
+    #[clap(long = "zstd-dict")]
+    zstd_dict: Option<String>,
+
+    /// Train a zstd dictionary from rows of the selected tables, write it to this path and exit.
+    #[clap(long = "train-zstd-dict")]
+    train_zstd_dict: Option<String>,
+
+    /// Write all tables into this ZIP archive instead of the output directory.
+    /// --compress then selects the compression of the archive entries.
+    #[clap(long = "output-zip")]
+    output_zip: Option<String>,
+
+    /// Encrypt the --output-zip entries with AES-256 and this password, or with the password
+    /// of the SQLITEDUMPER_ZIP_PASSWORD environment variable for -, which keeps it out of
+    /// the shell history. This is not authentication and weak passwords can be brute-forced.
+    #[clap(long = "output-zip-password", value_name = "PASSWORD", requires = "output_zip", value_parser = parse_zip_password)]
+    output_zip_password: Option<ZipPassword>,
+
+    /// Write all tables into this tar archive instead of the output directory.
+    /// The compression follows the extension: .tar, .tar.gz, .tar.zst or .tar.bz2. The
+    /// files inside a compressed archive are not compressed again, --compress is ignored.
+    /// The archive is only written when every table was dumped.
+    #[clap(long = "output-tar", conflicts_with = "output_zip")]
+    output_tar: Option<String>,
+
+    /// Write the rows of all tables, one table after another, into this one file with a
+    /// leading _table column. The header is the union of all columns.
+    #[clap(
+        long = "single-file",
+        conflicts_with_all = ["dir", "output_zip", "output_tar", "rows_per_file"]
+    )]
+    single_file: Option<String>,
+
+    /// Text written for SQL NULL, e.g. "" or "\N" or "NA". Defaults to "null".
+    #[clap(long = "null-value")]
+    null_value: Option<String>,
+
+    /// Truncate TEXT values longer than this many bytes, at a character boundary.
+    #[clap(long = "max-cell-size")]
+    max_cell_size: Option<usize>,
+
+    /// Text appended to truncated values.
+    #[clap(long = "truncation-marker", default_value = "…")]
+    truncation_marker: String,
+
+    /// BLOBs larger than this many bytes are written to --blob-dir, or truncated without it.
+    #[clap(long = "max-blob-size")]
+    max_blob_size: Option<usize>,
+
+    /// Write BLOBs to files under this directory as <table>/<column>/<rowid>.bin and put the
+    /// path relative to the output directory in the cell. Only BLOBs over --max-blob-size
+    /// when it is given. Rows are numbered from 1 without --include-rowid.
+    #[clap(long = "blob-dir")]
+    blob_dir: Option<String>,
+
+    /// With --blob-dir, keep BLOBs smaller than this many bytes in the cell.
+    #[clap(long = "blob-inline-threshold", default_value_t = 0)]
+    blob_inline_threshold: usize,
+
+    /// Encoding of BLOB values. Defaults to hex, and to base64 in JSON output.
+    #[clap(long = "blob-format", value_enum)]
+    blob_format: Option<BlobFormat>,
+
+    /// Number of decimal places of REAL values.
+    #[clap(long = "float-precision")]
+    float_precision: Option<usize>,
+
+    /// Notation of REAL values.
+    #[clap(long = "float-format", value_enum, default_value_t = FloatFormat::Auto)]
+    float_format: FloatFormat,
+
+    /// Write NaN as --nan-value and infinite REAL values as --inf-value or -<inf-value>,
+    /// instead of NaN, inf and -inf that many CSV parsers reject.
+    #[clap(long = "normalize-floats")]
+    normalize_floats: bool,
+
+    /// Text written for NaN with --normalize-floats.
+    #[clap(long = "nan-value", default_value = "", requires = "normalize_floats")]
+    nan_value: String,
+
+    /// Text written for +Infinity with --normalize-floats, -Infinity gets a leading minus.
+    #[clap(
+        long = "inf-value",
+        default_value = "Inf",
+        requires = "normalize_floats"
+    )]
+    inf_value: String,
+
+    /// Also parse this column as a timestamp, in addition to sm_timestamp and timestamp.
+    /// Every matching column gets a <column>_parsed sibling. Can be repeated.
+    #[clap(long = "timestamp-column")]
+    timestamp_columns: Vec<String>,
+
+    /// Integer timestamps are milliseconds since the epoch.
+    #[clap(long = "epoch-ms", conflicts_with = "epoch_us")]
+    epoch_ms: bool,
+
+    /// Integer timestamps are microseconds since the epoch.
+    #[clap(long = "epoch-us")]
+    epoch_us: bool,
+
+    /// chrono format string of the parsed timestamp column, e.g. "%Y-%m-%d %H:%M:%S".
+    /// Defaults to RFC 3339.
+    #[clap(long = "timestamp-format")]
+    timestamp_format: Option<String>,
+
+    /// Time zone the parsed timestamps are converted to, e.g. "America/New_York".
+    /// Defaults to UTC.
+    #[clap(long = "timestamp-tz", value_parser = parse_timezone)]
+    timestamp_tz: Option<chrono_tz::Tz>,
+
+    /// Add a <column>__<key> column for each key of TEXT columns holding JSON objects. The
+    /// columns and keys are found by reading the first 100 rows of each table once more
+    /// before dumping it, so keys that only appear later stay in the original column only.
+    /// Values are written as they are in the JSON, booleans as true and false.
+    #[clap(long = "flatten-json")]
+    flatten_json: bool,
+
+    /// Levels of nested JSON objects flattened into <column>__<key>__<key> columns. Deeper
+    /// objects and arrays are written as JSON text.
+    #[clap(
+        long = "flatten-json-depth",
+        default_value_t = 1,
+        requires = "flatten_json"
+    )]
+    flatten_json_depth: usize,
+
+    /// Do not write the CSV header row.
+    #[clap(long = "no-header")]
+    no_header: bool,
+
+    /// Dump the other tables when a table fails. The failures are recorded in --stats-file
+    /// and the exit code is 2 if any table failed. Archives are only written when every
+    /// table succeeded, so this cannot be used with --output-zip or --output-tar.
+    #[clap(long = "continue-on-error", conflicts_with_all = ["output_zip", "output_tar"])]
+    continue_on_error: bool,
+
+    /// Stop the dump when a table fails, cancelling the tables being written. This is the
+    /// default without --continue-on-error.
+    #[clap(long = "fail-fast", conflicts_with_all = ["continue_on_error", "max_errors"])]
+    fail_fast: bool,
+
+    /// Stop the dump once N tables failed, cancelling the tables being written. 0 dumps
+    /// every table with --continue-on-error and stops at the first failure without it.
+    /// Cannot be used with --output-zip or --output-tar.
+    #[clap(
+        long = "max-errors",
+        value_name = "N",
+        default_value_t = 0,
+        conflicts_with_all = ["output_zip", "output_tar"]
+    )]
+    max_errors: usize,
+
+    /// Keep the output files of tables that fail or are interrupted by Ctrl-C or SIGTERM,
+    /// renamed to <name>.partial, instead of deleting them. The manifest lists them as
+    /// incomplete.
+    #[clap(long = "keep-partial")]
+    keep_partial: bool,
+
+    /// Dump a table again up to N times when the database is busy or locked by another
+    /// connection. Tables dumped with --append are not retried.
+    #[clap(long = "retry", value_name = "N", default_value_t = 0)]
+    retry: u32,
+
+    /// Milliseconds to wait before dumping a busy table again.
+    #[clap(long = "retry-delay-ms", value_name = "M", default_value_t = 1000)]
+    retry_delay_ms: u64,
+
+    /// Append rows to existing output files. The header is only written to new files.
+    /// Without --append or --overwrite, existing output files are an error.
+    #[clap(long = "append", conflicts_with = "overwrite")]
+    append: bool,
+
+    /// Truncate and rewrite existing output files.
+    #[clap(long = "overwrite")]
+    overwrite: bool,
+
+    /// Number of worker threads. Defaults to the number of logical CPUs.
+    #[clap(long = "threads")]
+    threads: Option<std::num::NonZeroUsize>,
+
+    /// Dump the tables one after another on a single thread.
+    #[clap(long = "no-parallel", conflicts_with = "threads")]
+    no_parallel: bool,
+
+    /// Dump at most this many tables at the same time. Defaults to all tables at once.
+    #[clap(long = "parallel-tables", conflicts_with = "no_parallel")]
+    parallel_tables: Option<usize>,
+
+    /// Write timing, row counts, output sizes and errors of the dump to this JSON file.
+    #[clap(long = "stats-file")]
+    stats_file: Option<String>,
+
+    /// Write the rows, bytes, duration and throughput of each dumped table to this JSON file.
+    #[clap(long = "metrics-file")]
+    metrics_file: Option<String>,
+
+    /// Write a JSON manifest of the output files with their row counts, sizes and SHA-256 hashes to this file.
+    /// The file paths are relative to the directory of the manifest.
+    #[clap(long = "output-manifest")]
+    output_manifest: Option<String>,
+
+    /// Write a checksum file into the output directory listing every output file,
+    /// in the format of `sha256sum -c`.
+    #[clap(long = "checksum")]
+    checksum: bool,
+
+    /// Hash algorithm of --checksum. The file is named checksums.<algorithm>.
+    #[clap(long = "checksum-algorithm", value_enum, default_value_t = ChecksumAlgorithm::Sha256, requires = "checksum")]
+    checksum_algorithm: ChecksumAlgorithm,
+
+    /// Print the row count of each table instead of dumping it.
+    /// Prints a JSON object with --format json.
+    #[clap(long = "count-only")]
+    count_only: bool,
+
+    /// Checkpoint the WAL file of a database in WAL mode before dumping, so that the dump
+    /// includes all committed transactions. Opens the database read-write for this.
+    #[clap(long = "wal-checkpoint")]
+    wal_checkpoint: bool,
+
+    /// After the dump, watch the database and its WAL file and dump again, overwriting their
+    /// output, the tables whose row count or schema changed. Runs until Ctrl-C or SIGTERM.
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "query_file", "query", "compare_db", "output_zip", "output_tar", "single_file",
+        ]
+    )]
+    watch: bool,
+
+    /// Wait for this many milliseconds without changes before dumping again with --watch.
+    #[clap(
+        long = "watch-debounce-ms",
+        value_name = "MS",
+        default_value_t = 500,
+        requires = "watch"
+    )]
+    watch_debounce_ms: u64,
+
+    /// Dump from a compacted copy of the database made with VACUUM INTO in a temporary
+    /// directory, deleted after the dump. Opens the database read-write for this.
+    #[clap(long = "vacuum-first")]
+    vacuum_first: bool,
+
+    /// Copy the database with SQLite's online backup API to this file and dump from the
+    /// copy, a consistent snapshot even while the database is written. The copy is deleted
+    /// after the dump unless --keep-backup is given.
+    #[clap(long, value_name = "DESTINATION", conflicts_with = "vacuum_first")]
+    backup: Option<String>,
+
+    /// Keep the --backup copy after the dump.
+    #[clap(long = "keep-backup", requires = "backup")]
+    keep_backup: bool,
+
+    /// Run PRAGMA integrity_check before dumping and stop if the database is corrupt.
+    #[clap(long = "integrity-check")]
+    integrity_check: bool,
+
+    /// Like --integrity-check but runs the faster PRAGMA quick_check.
+    #[clap(long = "quick-check")]
+    quick_check: bool,
+
+    /// Check the files and row counts listed in this manifest from --output-manifest
+    /// against the database instead of dumping. Prints PASS or FAIL per table and
+    /// exits non-zero if any table fails. Prints JSON with --format json. The rows
+    /// of tables dumped with --where, --limit, --offset, --sample or a --since-*
+    /// bound, and of --query, --query-file and --compare-db outputs, are not counted.
+    #[clap(long = "verify", value_name = "MANIFEST")]
+    verify: Option<String>,
+
+    /// Print the columns of each table instead of dumping it.
+    /// Prints JSON with --format json.
+    #[clap(long = "table-info")]
+    table_info: bool,
+
+    /// Print the tables that would be dumped with their row counts and estimated sizes,
+    /// without writing anything. Prints JSON with --format json.
+    #[clap(long = "dry-run")]
+    dry_run: bool,
 }
 
-fn set_loglevel(loglevel: &str) {
-    unsafe {
-        std::env::set_var("RUST_LOG", loglevel);
+/// Expand the glob patterns among the --file arguments, in sorted order.
+fn expand_db_paths(patterns: &[String]) -> Result<Vec<String>, DumperError> {
+    let mut paths = Vec::new();
+    for pattern in patterns {
+        if !pattern.contains(['*', '?', '[']) {
+            paths.push(pattern.clone());
+            continue;
+        }
+        let invalid = |e: &dyn std::fmt::Display| {
+            DumperError::InvalidConfig(format!("invalid file pattern '{pattern}'. {e}"))
+        };
+        let mut matched = glob::glob(pattern)
+            .map_err(|e| invalid(&e))?
+            .map(|path| Ok(path.map_err(|e| invalid(&e))?.display().to_string()))
+            .collect::<Result<Vec<_>, DumperError>>()?;
+        if matched.is_empty() {
+            return Err(DumperError::InvalidConfig(format!(
+                "no database file matches '{pattern}'"
+            )));
+        }
+        matched.sort();
+        paths.extend(matched);
+    }
+    paths.dedup();
+    Ok(paths)
+}
+
+fn parse_delimiter(s: &str) -> Result<u8, String> {
+    match s {
+        "\\t" | "\t" => Ok(b'\t'),
+        _ => match s.as_bytes() {
+            [b] if b.is_ascii() => Ok(*b),
+            _ => Err(format!(
+                "delimiter must be a single ASCII character, got '{s}'"
+            )),
+        },
     }
 }
 
-fn handle_cmd_args() -> Result<CommandArguments, Box<dyn std::error::Error>> {
-    let cli_commands = CommandArguments::parse();
-    set_loglevel("INFO");
+fn parse_csv_char(s: &str) -> Result<u8, String> {
+    match s.as_bytes() {
+        [b] if b.is_ascii() => Ok(*b),
+        _ => Err(format!("expected a single ASCII character, got '{s}'")),
+    }
+}
 
-    match cli_commands.log.to_lowercase().as_str() {
-        "error" | "warn" | "info" | "debug" | "trace" | "none" => {
-            set_loglevel(&cli_commands.log.to_uppercase())
+/// Parse a byte count with an optional binary K, M, G or T suffix.
+fn parse_size(s: &str) -> Result<u64, String> {
+    let upper = s.trim().to_ascii_uppercase();
+    let digits = upper.trim_end_matches(['B', 'I']);
+    let (number, shift) = match digits.char_indices().last() {
+        Some((i, 'K')) => (&digits[..i], 10),
+        Some((i, 'M')) => (&digits[..i], 20),
+        Some((i, 'G')) => (&digits[..i], 30),
+        Some((i, 'T')) => (&digits[..i], 40),
+        _ => (digits, 0),
+    };
+    number
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(1 << shift))
+        .ok_or_else(|| format!("expected a size like 4096, 512M or 2G, got '{s}'"))
+}
+
+/// Parse a `PRAGMA cache_size` value: pages for a plain number, the negative
+/// KiB form for a size with a suffix.
+fn parse_cache_size(s: &str) -> Result<i64, String> {
+    if let Ok(pages) = s.trim().parse::<i64>() {
+        return Ok(pages);
+    }
+    let kib = parse_size(s)? / 1024;
+    i64::try_from(kib)
+        .map(|kib| -kib)
+        .map_err(|_| format!("cache size '{s}' is too large"))
+}
+
+fn parse_table_columns(s: &str) -> Result<(String, Vec<String>), String> {
+    match s.split_once(':') {
+        Some((table, columns)) if !table.is_empty() && !columns.is_empty() => Ok((
+            table.to_string(),
+            columns.split(',').map(|c| c.trim().to_string()).collect(),
+        )),
+        _ => Err(format!("expected table:col1,col2, got '{s}'")),
+    }
+}
+
+/// Environment variable read for `--output-zip-password -`.
+const ZIP_PASSWORD_ENV: &str = "SQLITEDUMPER_ZIP_PASSWORD";
+
+fn parse_zip_password(s: &str) -> Result<ZipPassword, String> {
+    let password = match s {
+        "-" => std::env::var(ZIP_PASSWORD_ENV)
+            .map_err(|_| format!("{ZIP_PASSWORD_ENV} is not set or not valid UTF-8"))?,
+        _ => s.to_string(),
+    };
+    if password.is_empty() {
+        return Err("the ZIP password is empty".to_string());
+    }
+    Ok(ZipPassword::new(password))
+}
+
+fn parse_encoding(s: &str) -> Result<&'static encoding_rs::Encoding, String> {
+    encoding_rs::Encoding::for_label(s.trim().as_bytes())
+        .ok_or_else(|| format!("'{s}' is not a known encoding"))
+}
+
+fn parse_markdown_align(s: &str) -> Result<(String, MarkdownAlign), String> {
+    use clap::ValueEnum;
+    match s.rsplit_once(':') {
+        Some((column, align)) if !column.is_empty() => MarkdownAlign::from_str(align, true)
+            .map(|align| (column.to_string(), align))
+            .map_err(|_| format!("'{align}' is not one of left, right or center")),
+        _ => Err(format!("expected column:alignment, got '{s}'")),
+    }
+}
+
+fn parse_since_timestamp(s: &str) -> Result<chrono::DateTime<chrono::Utc>, String> {
+    sqlitedumper::parse_since_timestamp(s)
+        .map_err(|_| format!("'{s}' is not an ISO 8601 timestamp"))
+}
+
+fn parse_pivot(s: &str) -> Result<(String, Pivot), String> {
+    match s.split(':').collect::<Vec<_>>().as_slice() {
+        [table, key_column, value_column]
+            if [table, key_column, value_column]
+                .iter()
+                .all(|s| !s.is_empty()) =>
+        {
+            Ok((
+                table.to_string(),
+                Pivot {
+                    key_column: key_column.to_string(),
+                    value_column: value_column.to_string(),
+                },
+            ))
+        }
+        _ => Err(format!("expected table:key_column:value_column, got '{s}'")),
+    }
+}
+
+fn parse_redact_column(s: &str) -> Result<sqlitedumper::RedactColumn, String> {
+    let mut parts = s.splitn(3, ':');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(table), Some(column), pattern) if !table.is_empty() && !column.is_empty() => {
+            let pattern = pattern
+                .map(glob::Pattern::new)
+                .transpose()
+                .map_err(|e| format!("invalid pattern in '{s}'. {e}"))?;
+            Ok(sqlitedumper::RedactColumn {
+                table: table.to_string(),
+                column: column.to_string(),
+                pattern,
+            })
+        }
+        _ => Err(format!(
+            "expected table:column or table:column:pattern, got '{s}'"
+        )),
+    }
+}
+
+fn parse_since_rowid(s: &str) -> Result<(String, i64), String> {
+    match s.rsplit_once(':') {
+        Some((table, rowid)) if !table.is_empty() => match rowid.trim().parse() {
+            Ok(rowid) => Ok((table.to_string(), rowid)),
+            Err(_) => Err(format!("'{rowid}' is not a rowid")),
+        },
+        _ => Err(format!("expected table:rowid, got '{s}'")),
+    }
+}
+
+fn group_table_columns(
+    entries: &[(String, Vec<String>)],
+) -> std::collections::HashMap<String, Vec<String>> {
+    let mut grouped = std::collections::HashMap::<String, Vec<String>>::new();
+    for (table, columns) in entries {
+        grouped
+            .entry(table.clone())
+            .or_default()
+            .extend(columns.iter().cloned());
+    }
+    grouped
+}
+
+fn parse_table_where(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((table, clause)) if !table.is_empty() && !clause.is_empty() => {
+            Ok((table.to_string(), clause.to_string()))
+        }
+        _ => Err(format!("expected table=predicate, got '{s}'")),
+    }
+}
+
+fn parse_query(s: &str) -> Result<sqlitedumper::NamedQuery, String> {
+    sqlitedumper::parse_named_query(s).map_err(|e| e.to_string())
+}
+
+fn parse_pragma(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() && !value.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.trim().to_string()))
         }
-        other => return Err(format!("Invalid log level '{other}'").into()),
+        _ => Err(format!("expected key=value, got '{s}'")),
     }
+}
+
+fn parse_attach(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((alias, path)) if !alias.trim().is_empty() && !path.is_empty() => {
+            Ok((alias.trim().to_string(), path.to_string()))
+        }
+        _ => Err(format!("expected alias=path, got '{s}'")),
+    }
+}
 
-    if cli_commands.verbose {
-        set_loglevel("DEBUG");
+fn parse_timezone(s: &str) -> Result<chrono_tz::Tz, String> {
+    s.parse::<chrono_tz::Tz>()
+        .map_err(|_| format!("unknown time zone '{s}'"))
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
     }
+}
 
-    env_logger::init();
-    log::debug!("{cli_commands:?}");
-    Ok(cli_commands)
+/// Cancel `cancel` on the first Ctrl-C or SIGTERM and exit on the second
+/// one. The signals are awaited on a thread of their own, as the dump keeps
+/// the workers of the runtime busy.
+fn cancel_on_signal(cancel: tokio_util::sync::CancellationToken) {
+    std::thread::spawn(move || {
+        let runtime = match tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                tracing::warn!("Cannot listen for Ctrl-C. {e}");
+                return;
+            }
+        };
+        if let Err(e) = runtime.block_on(wait_for_signal()) {
+            tracing::warn!("Cannot listen for Ctrl-C. {e}");
+            return;
+        }
+        eprintln!("Interrupted, stopping the dump. Press Ctrl-C again to exit right away.");
+        cancel.cancel();
+        if runtime.block_on(wait_for_signal()).is_ok() {
+            std::process::exit(130);
+        }
+    });
 }
 
-async fn dump_table(
-    db: &str,
-    table_name: &str,
-    dump_dir: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    log::info!("Dumping table {table_name}");
-    let file = {
-        let dir = std::path::Path::new(dump_dir);
-        let path = dir.join(format!("{table_name}.csv"));
-        tokio::fs::OpenOptions::new()
-            .write(true)
-            .create_new(true)
-            .open(path)
-            .await?
-            .into_std()
-            .await
+async fn wait_for_signal() -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        let mut terminate =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result,
+            _ = terminate.recv() => Ok(()),
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await
+}
+
+/// Print which tables were dumped before the dump was interrupted.
+fn print_interrupted(stats: &sqlitedumper::DumpStats) {
+    let (completed, interrupted): (Vec<_>, Vec<_>) =
+        stats.tables.iter().partition(|table| table.error.is_none());
+    let names = |tables: &[&sqlitedumper::TableStats]| match tables {
+        [] => "none".to_string(),
+        tables => tables
+            .iter()
+            .map(|table| table.table.as_str())
+            .collect::<Vec<_>>()
+            .join(", "),
     };
-    let conn = create_db_connection_ro(db)?;
-    let query = format!("SELECT * FROM '{}'", table_name);
-    let mut stmt = conn.prepare(&query)?;
-    let column_count = stmt.column_count();
-    let mut column_name: Vec<String> = stmt.column_names().iter().map(|s| s.to_string()).collect();
-    let mut csv_writer = csv::Writer::from_writer(file);
-    let timestamp_position = column_name
-        .iter()
-        .position(|name| name == "sm_timestamp" || name == "timestamp");
+    eprintln!("Completed tables: {}", names(&completed));
+    eprintln!("Not completed tables: {}", names(&interrupted));
+}
 
-    if let Some(pos) = timestamp_position {
-        column_name.insert(pos + 1, "timestamp_parsed".to_string());
+fn print_dry_run(config: &DumpConfig) -> Result<(), DumperError> {
+    let plans = sqlitedumper::plan_dump(config)?;
+    if config.output_format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&plans)?);
+        return Ok(());
     }
+    let width = plans
+        .iter()
+        .map(|plan| plan.table.len())
+        .chain(["table".len()])
+        .max()
+        .unwrap_or_default();
+    println!("{:<width$}  {:>12}  {:>12}", "table", "rows", "est. size");
+    for plan in &plans {
+        println!(
+            "{:<width$}  {:>12}  {:>12}",
+            plan.table,
+            plan.row_count,
+            format_bytes(plan.estimated_bytes)
+        );
+    }
+    Ok(())
+}
 
-    // Write header;
-    csv_writer.write_record(&column_name)?;
+/// Features reported by --sqlite-version and the compile option enabling
+/// them.
+const SQLITE_FEATURES: &[(&str, &str)] = &[
+    ("FTS3", "ENABLE_FTS3"),
+    ("FTS4", "ENABLE_FTS4"),
+    ("FTS5", "ENABLE_FTS5"),
+    ("RTREE", "ENABLE_RTREE"),
+    ("GEOPOLY", "ENABLE_GEOPOLY"),
+    ("MATH_FUNCTIONS", "ENABLE_MATH_FUNCTIONS"),
+];
 
-    log::info!("Column name: {column_name:?}");
-    let mut rows = stmt.query([])?;
+fn print_sqlite_version() -> Result<(), DumperError> {
+    let options = sqlitedumper::sqlite_compile_options()?;
+    println!(
+        "SQLite {} ({})",
+        rusqlite::version(),
+        rusqlite::version_number()
+    );
+    // JSON is built in since 3.38.0 unless omitted, before it needs JSON1.
+    let json = if rusqlite::version_number() >= 3_038_000 {
+        !options.iter().any(|option| option == "OMIT_JSON")
+    } else {
+        options.iter().any(|option| option == "ENABLE_JSON1")
+    };
+    let features = SQLITE_FEATURES
+        .iter()
+        .map(|&(feature, option)| (feature, options.iter().any(|o| o == option)))
+        .chain([("JSON", json)]);
+    println!("Features:");
+    for (feature, enabled) in features {
+        println!("  {feature:<16}{}", if enabled { "yes" } else { "no" });
+    }
+    println!("Compile options:");
+    for option in &options {
+        println!("  {option}");
+    }
+    Ok(())
+}
 
-    while let Some(row) = rows.next()? {
-        for i in 0..column_count {
-            use rusqlite::types::Type;
-            let val = row.get_ref(i)?;
-            let txt = match val.data_type() {
-                Type::Text => val.as_str()?.to_string(),
-                Type::Integer => val.as_i64()?.to_string(),
-                Type::Real => val.as_f64()?.to_string(),
-                Type::Blob => format!("{:?}", val.as_blob()?),
-                Type::Null => "null".to_string(),
-            };
-            csv_writer.write_field(&txt)?;
-            if let Some(pos) = timestamp_position {
-                if pos == i {
-                    use chrono::prelude::*;
-                    let ts = txt.parse::<i64>()?;
-                    let datetime = Utc.timestamp_opt(ts, 0).unwrap();
-                    let a = datetime.to_rfc3339_opts(SecondsFormat::Secs, true);
-                    csv_writer.write_field(&a)?;
-                }
+fn print_counts(config: &DumpConfig) -> Result<(), DumperError> {
+    let counts = sqlitedumper::count_tables(config)?;
+    if config.output_format == OutputFormat::Json {
+        let object: serde_json::Map<String, serde_json::Value> = counts
+            .into_iter()
+            .map(|(table, count)| (table, count.into()))
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&object)?);
+        return Ok(());
+    }
+    let width = counts
+        .iter()
+        .map(|(table, _)| table.len())
+        .max()
+        .unwrap_or_default();
+    for (table, count) in &counts {
+        println!("{table:<width$}  {count:>12}");
+    }
+    Ok(())
+}
+
+fn print_verify(config: &DumpConfig, manifest_path: &str) -> Result<(), DumperError> {
+    let tables = sqlitedumper::verify_manifest(config, manifest_path)?;
+    if config.output_format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&tables)?);
+    } else {
+        for table in &tables {
+            if table.passed() && table.filtered {
+                println!(
+                    "PASS {}: files only, rows were filtered and not counted",
+                    table.table
+                );
+            } else if table.passed() {
+                println!("PASS {}", table.table);
+            } else {
+                println!("FAIL {}: {}", table.table, table.failures.join("; "));
             }
         }
-        csv_writer.write_record(None::<&[u8]>)?; // 改行
     }
+    let failed = tables.iter().filter(|table| !table.passed()).count();
+    if failed > 0 {
+        return Err(DumperError::VerifyFailed(failed));
+    }
+    Ok(())
+}
 
-    drop(rows);
-    drop(stmt);
-    match conn.close() {
-        Ok(()) => {}
-        Err((_, err)) => {
-            log::error!("Error while closing db connection. {err}");
+fn print_table_info(config: &DumpConfig) -> Result<(), DumperError> {
+    let tables = sqlitedumper::table_info(config)?;
+    if config.output_format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&tables)?);
+        return Ok(());
+    }
+    for (i, table) in tables.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("{}", table.table);
+        let width = table
+            .columns
+            .iter()
+            .map(|column| column.name.len())
+            .chain(["name".len()])
+            .max()
+            .unwrap_or_default();
+        let type_width = table
+            .columns
+            .iter()
+            .map(|column| column.decl_type.len())
+            .chain(["type".len()])
+            .max()
+            .unwrap_or_default();
+        println!(
+            "{:>4}  {:<width$}  {:<type_width$}  {:<8}  {:>2}  default",
+            "cid", "name", "type", "not null", "pk"
+        );
+        for column in &table.columns {
+            let note = if column.timestamp {
+                "  (timestamp)"
+            } else {
+                ""
+            };
+            let line = format!(
+                "{:>4}  {:<width$}  {:<type_width$}  {:<8}  {:>2}  {}{note}",
+                column.cid,
+                column.name,
+                column.decl_type,
+                if column.not_null { "yes" } else { "no" },
+                column.primary_key,
+                column.default_value.as_deref().unwrap_or("")
+            );
+            println!("{}", line.trim_end());
         }
     }
     Ok(())
 }
 
-fn create_db_connection_ro(db: &str) -> Result<rusqlite::Connection, Box<dyn std::error::Error>> {
-    let conn =
-        rusqlite::Connection::open_with_flags(db, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
-    Ok(conn)
-}
-
-fn get_tables(db: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let conn = create_db_connection_ro(db)?;
-    let mut stmt = conn.prepare(
-        "SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%';",
-    )?;
-    let table_names_row = stmt.query_map([], |row| row.get::<_, String>(0))?;
-    let mut table_names = Vec::new();
-    for table_name_res in table_names_row {
-        match table_name_res {
-            Ok(table_name) => {
-                log::info!("Found table name {table_name}");
-                table_names.push(table_name);
-            }
-            Err(e) => log::error!("⚠️ Error while getting table name {e}"),
+/// Text log lines as env_logger writes them, `[time LEVEL target] message`.
+/// The fields of the events repeat values of their message and are only
+/// written by `--log-format json`.
+struct TextFormat;
+
+impl<S, N> tracing_subscriber::fmt::FormatEvent<S, N> for TextFormat
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    N: for<'w> tracing_subscriber::fmt::FormatFields<'w> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &tracing_subscriber::fmt::FmtContext<'_, S, N>,
+        mut writer: tracing_subscriber::fmt::format::Writer<'_>,
+        event: &tracing::Event<'_>,
+    ) -> std::fmt::Result {
+        use tracing_subscriber::fmt::FormatFields;
+
+        let metadata = event.metadata();
+        write!(
+            writer,
+            "[{} {:<5} {}] ",
+            chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            metadata.level(),
+            metadata.target()
+        )?;
+        ctx.format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
+
+/// Field formatter of the text logs: the message only.
+fn message_only() -> impl for<'w> tracing_subscriber::fmt::FormatFields<'w> + Send + Sync + 'static
+{
+    tracing_subscriber::fmt::format::debug_fn(|writer, field, value| match field.name() {
+        "message" => write!(writer, "{value:?}"),
+        _ => Ok(()),
+    })
+}
+
+/// Log to stderr at the `RUST_LOG` level in `format`, and to `log_file`,
+/// if given, at the trace level with timestamps.
+fn init_tracing(format: LogFormat, log_file: Option<&str>) -> Result<(), DumperError> {
+    use tracing_subscriber::prelude::*;
+
+    let stderr = match format {
+        LogFormat::Text => tracing_subscriber::fmt::layer()
+            .event_format(TextFormat)
+            .fmt_fields(message_only())
+            .with_writer(std::io::stderr)
+            .boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .flatten_event(true)
+            .with_writer(std::io::stderr)
+            .boxed(),
+    };
+    let file = match log_file {
+        Some(path) => {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?;
+            let targets = tracing_subscriber::filter::Targets::new()
+                .with_target("sqlitedumper", tracing::Level::TRACE);
+            let layer = tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .fmt_fields(message_only())
+                .with_writer(std::sync::Mutex::new(file))
+                .with_filter(targets);
+            Some(layer)
         }
+        None => None,
+    };
+    tracing_subscriber::registry()
+        .with(stderr.with_filter(tracing_subscriber::EnvFilter::from_default_env()))
+        .with(file)
+        .init();
+    Ok(())
+}
+
+fn set_loglevel(loglevel: &str) {
+    unsafe {
+        std::env::set_var("RUST_LOG", loglevel);
     }
+}
 
-    drop(stmt);
-    match conn.close() {
-        Ok(()) => {}
-        Err((_, err)) => {
-            log::error!("Error while closing db connection. {err}");
+/// Parse the command line, with the settings of the --config file before
+/// the arguments.
+fn parse_cmd_args() -> Result<(CommandArguments, clap::ArgMatches), DumperError> {
+    let args: Vec<std::ffi::OsString> = std::env::args_os().collect();
+    let mut matches = CommandArguments::command().get_matches_from(&args);
+    if let Some(path) = matches.get_one::<String>("config_file") {
+        let mut config_file = sqlitedumper::read_config_file(path)?;
+        if let Some(profile) = matches.get_one::<String>("profile") {
+            config_file.select_profile(profile)?;
         }
+        let file_args = config_file.args(&CommandArguments::command(), |id| {
+            matches.value_source(id) == Some(clap::parser::ValueSource::CommandLine)
+        })?;
+        let merged = args[..1]
+            .iter()
+            .cloned()
+            .chain(file_args.into_iter().map(Into::into))
+            .chain(args[1..].iter().cloned());
+        matches = CommandArguments::command().get_matches_from(merged);
     }
-    Ok(table_names)
+    let cli_commands = CommandArguments::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    Ok((cli_commands, matches))
 }
 
-#[tokio::main(flavor = "multi_thread", worker_threads = 8)]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let start_time = std::time::Instant::now();
-    let cli_commands = handle_cmd_args()?;
-    let table_names = get_tables(&cli_commands.file)?;
-
-    // ダンプ先ディレクトリ作成
-    let dump_path = std::path::Path::new(&cli_commands.dir);
-    tokio::fs::create_dir_all(dump_path).await?;
-
-    let mut joinhandles = Vec::new();
-
-    for tbl_name in table_names.iter() {
-        let table_name = tbl_name.to_string();
-        let dump_dir = cli_commands.dir.clone();
-        let db = cli_commands.file.clone();
-        let jh = tokio::spawn(async move {
-            log::debug!("Start thread for table {table_name}");
-            let thread_start_time = std::time::Instant::now();
-            let result = dump_table(&db, &table_name, &dump_dir).await;
-            match result {
-                Ok(()) => {}
-                Err(e) => log::error!("Error while handling table {table_name}. {e}"),
+/// Parse the command line and set up logging. `None` when nothing is left
+/// to do.
+fn handle_cmd_args() -> Result<Option<CommandArguments>, DumperError> {
+    let (mut cli_commands, matches) = parse_cmd_args()?;
+    if cli_commands.dump_config {
+        print!(
+            "{}",
+            sqlitedumper::effective_config(&CommandArguments::command(), &matches)
+        );
+        return Ok(None);
+    }
+    if cli_commands.sqlite_version {
+        print_sqlite_version()?;
+        return Ok(None);
+    }
+
+    let loglevel = match cli_commands.log.to_lowercase().as_str() {
+        // tracing does not know "none", "off" disables logging.
+        "none" => "off".to_string(),
+        "error" | "warn" | "info" | "debug" | "trace" => cli_commands.log.to_uppercase(),
+        other => return Err(DumperError::InvalidLogLevel(other.to_string())),
+    };
+    let loglevel = if cli_commands.quiet {
+        "ERROR".to_string()
+    } else if cli_commands.verbose {
+        "TRACE".to_string()
+    } else {
+        loglevel
+    };
+    set_loglevel(&loglevel);
+
+    if let Some(pattern) = &cli_commands.table_pattern {
+        match regex::Regex::new(pattern) {
+            Ok(re) => cli_commands.table_regex = Some(re),
+            Err(source) => {
+                return Err(DumperError::InvalidTablePattern {
+                    pattern: pattern.clone(),
+                    source,
+                });
             }
-            log::debug!(
-                "Table {table_name} dump completed. Elapsed {} ms",
-                thread_start_time.elapsed().as_millis()
-            );
-        });
-        log::debug!("Thread {} created.", tbl_name);
-        joinhandles.push(jh);
+        }
     }
 
-    for handle in joinhandles {
-        match handle.await {
-            Ok(_) => {}
-            Err(e) => {
-                eprintln!("Error {e:?}");
+    init_tracing(cli_commands.log_format, cli_commands.log_file.as_deref())?;
+    tracing::debug!("{cli_commands:?}");
+    Ok(Some(cli_commands))
+}
+
+fn main() -> std::process::ExitCode {
+    let result = handle_cmd_args().and_then(|cli_commands| match cli_commands {
+        Some(cli_commands) => start_runtime(cli_commands),
+        None => Ok(()),
+    });
+    match result {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            match e {
+                DumperError::TablesFailed(_) => std::process::ExitCode::from(2),
+                _ => std::process::ExitCode::FAILURE,
             }
         }
     }
+}
+
+fn start_runtime(cli_commands: CommandArguments) -> Result<(), DumperError> {
+    let threads = cli_commands
+        .threads
+        .map_or_else(num_cpus::get, std::num::NonZeroUsize::get);
+    let mut builder = if cli_commands.no_parallel {
+        tracing::debug!("Starting single threaded runtime");
+        tokio::runtime::Builder::new_current_thread()
+    } else {
+        tracing::debug!("Starting runtime with {threads} worker threads");
+        let mut builder = tokio::runtime::Builder::new_multi_thread();
+        builder.worker_threads(threads);
+        builder
+    };
+    builder.enable_all().build()?.block_on(run(cli_commands))
+}
+
+async fn run(cli_commands: CommandArguments) -> Result<(), DumperError> {
+    let start_time = std::time::Instant::now();
+    let zstd_dictionary = match &cli_commands.zstd_dict {
+        Some(path) => Some(std::fs::read(path)?),
+        None => None,
+    };
+    let mut queries = match &cli_commands.query_file {
+        Some(path) => sqlitedumper::read_query_file(path)?,
+        None => Vec::new(),
+    };
+    queries.extend(cli_commands.query.iter().cloned());
+    let db_paths = expand_db_paths(&cli_commands.file)?;
+    let listed_tables = match &cli_commands.table_list_file {
+        Some(path) => sqlitedumper::read_table_list(path)?,
+        None => Vec::new(),
+    };
+    let cancel = tokio_util::sync::CancellationToken::new();
+    let config = DumpConfig::builder()
+        .db_path(&db_paths[0])
+        .pragmas(cli_commands.pragma.clone())
+        .mmap_size(cli_commands.mmap_size)
+        .cache_size(cli_commands.cache_size)
+        .attach(cli_commands.attach.clone())
+        .compare_db(cli_commands.compare_db.clone())
+        .output_dir(&cli_commands.dir)
+        .output_pattern(cli_commands.output_pattern.as_str())
+        .rows_per_file(cli_commands.rows_per_file)
+        .sequential(cli_commands.no_parallel)
+        .parallel_tables(cli_commands.parallel_tables)
+        .output_manifest(cli_commands.output_manifest.clone())
+        .checksum(
+            cli_commands
+                .checksum
+                .then_some(cli_commands.checksum_algorithm),
+        )
+        .output_format(cli_commands.format)
+        .parquet_row_group_size(cli_commands.parquet_row_group_size)
+        .sql_dialect(cli_commands.sql_dialect)
+        .markdown_align(cli_commands.markdown_align.iter().cloned().collect())
+        .html_class(cli_commands.html_class.clone())
+        .html_template(cli_commands.html_template.clone())
+        .delimiter(cli_commands.delimiter)
+        .csv_bom(cli_commands.csv_bom)
+        .line_ending(cli_commands.line_ending)
+        .quote_char(cli_commands.quote_char)
+        .escape_char(cli_commands.escape_char)
+        .quoting(cli_commands.quoting)
+        .encoding(cli_commands.encoding)
+        .output_zip(cli_commands.output_zip.clone())
+        .output_zip_password(cli_commands.output_zip_password.clone())
+        .output_tar(cli_commands.output_tar.clone())
+        .single_file(cli_commands.single_file.clone())
+        .compression(cli_commands.compress)
+        .compress_level(cli_commands.compress_level)
+        .zstd_dictionary(zstd_dictionary)
+        .tables_filter(TableFilter {
+            include: cli_commands.tables.clone(),
+            listed: listed_tables,
+            pattern: cli_commands.table_regex.clone(),
+            exclude: cli_commands.exclude_tables.clone(),
+        })
+        .queries(queries)
+        .include_views(cli_commands.include_views)
+        .include_system_tables(cli_commands.include_system_tables)
+        .views_only(cli_commands.views_only)
+        .schema_only(cli_commands.schema_only)
+        .schema_file(cli_commands.schema_file)
+        .include_indexes(cli_commands.include_indexes)
+        .include_triggers(cli_commands.include_triggers)
+        .include_foreign_keys(cli_commands.include_foreign_keys)
+        .columns(group_table_columns(&cli_commands.columns))
+        .exclude_columns(cli_commands.exclude_columns.clone())
+        .table_exclude_columns(group_table_columns(&cli_commands.exclude_columns_table))
+        .column_order(group_table_columns(&cli_commands.column_order))
+        .pivot(cli_commands.pivot.iter().cloned().collect())
+        .bool_columns(group_table_columns(&cli_commands.bool_columns))
+        .bool_as_int(cli_commands.bool_as_int)
+        .redact_columns(cli_commands.redact_columns.clone())
+        .redact_pattern(cli_commands.redact_pattern.clone())
+        .redact_hash(cli_commands.redact_hash)
+        .sort_columns(cli_commands.sort_columns)
+        .include_rowid(cli_commands.include_rowid)
+        .row_number(cli_commands.row_number)
+        .since_rowid(cli_commands.since_rowid.iter().cloned().collect())
+        .rowid_state_file(cli_commands.rowid_state_file.clone())
+        .since_timestamp(cli_commands.since_timestamp)
+        .timestamp_state_file(cli_commands.timestamp_state_file.clone())
+        .where_clause(cli_commands.where_clause.clone())
+        .table_where(cli_commands.table_where.iter().cloned().collect())
+        .limit(cli_commands.limit)
+        .offset(cli_commands.offset)
+        .sample(cli_commands.sample)
+        .sample_seed(cli_commands.sample_seed)
+        .order_by(cli_commands.order_by.clone())
+        .order_by_rowid(cli_commands.order_by_rowid)
+        .null_value(
+            cli_commands
+                .null_value
+                .as_deref()
+                .unwrap_or(NULL_VALUE_DEFAULT),
+        )
+        .max_cell_size(cli_commands.max_cell_size)
+        .truncation_marker(cli_commands.truncation_marker.as_str())
+        .max_blob_size(cli_commands.max_blob_size)
+        .blob_dir(cli_commands.blob_dir.clone())
+        .blob_inline_threshold(cli_commands.blob_inline_threshold)
+        .blob_format(cli_commands.blob_format)
+        .float_precision(cli_commands.float_precision)
+        .float_format(cli_commands.float_format)
+        .normalize_floats(cli_commands.normalize_floats)
+        .nan_value(&cli_commands.nan_value)
+        .inf_value(&cli_commands.inf_value)
+        .timestamp_columns(cli_commands.timestamp_columns.clone())
+        .epoch_unit(match (cli_commands.epoch_ms, cli_commands.epoch_us) {
+            (true, _) => EpochUnit::Millis,
+            (_, true) => EpochUnit::Micros,
+            _ => EpochUnit::Seconds,
+        })
+        .timestamp_format(cli_commands.timestamp_format.clone())
+        .timestamp_tz(cli_commands.timestamp_tz)
+        .flatten_json(cli_commands.flatten_json)
+        .flatten_json_depth(cli_commands.flatten_json_depth)
+        .no_header(cli_commands.no_header)
+        .append(cli_commands.append)
+        .overwrite(cli_commands.overwrite)
+        .progress(!cli_commands.quiet && std::io::stdout().is_terminal())
+        .cancel(cancel.clone())
+        .max_errors(match cli_commands.max_errors {
+            0 if cli_commands.continue_on_error => None,
+            0 => Some(1),
+            n => Some(n),
+        })
+        .keep_partial(cli_commands.keep_partial)
+        .vacuum_first(cli_commands.vacuum_first)
+        .backup(cli_commands.backup.clone())
+        .keep_backup(cli_commands.keep_backup)
+        .retry(cli_commands.retry)
+        .retry_delay(std::time::Duration::from_millis(
+            cli_commands.retry_delay_ms,
+        ))
+        .build()?;
+    if config.output_zip_password.is_some() {
+        eprintln!(
+            "Warning: ZIP password protection only encrypts the entries. It does not prove who \
+             made the archive, and a weak password can be brute-forced offline."
+        );
+    }
+    for db_path in &db_paths {
+        let mut config = config.clone();
+        config.db_path = db_path.clone();
+        if cli_commands.wal_checkpoint {
+            sqlitedumper::checkpoint_wal(&config)?;
+        }
+        if cli_commands.integrity_check || cli_commands.quick_check {
+            sqlitedumper::check_integrity(&config, cli_commands.quick_check)?;
+        }
+    }
+    let single_database_only = cli_commands.verify.is_some()
+        || cli_commands.table_info
+        || cli_commands.count_only
+        || cli_commands.dry_run
+        || cli_commands.train_zstd_dict.is_some()
+        || cli_commands.watch;
+    if db_paths.len() > 1 && single_database_only {
+        return Err(DumperError::InvalidConfig(
+            "--verify, --table-info, --count-only, --dry-run, --train-zstd-dict and --watch work on a single database"
+                .to_string(),
+        ));
+    }
+    if let Some(manifest_path) = &cli_commands.verify {
+        return print_verify(&config, manifest_path);
+    }
+    if cli_commands.table_info {
+        return print_table_info(&config);
+    }
+    if cli_commands.count_only {
+        return print_counts(&config);
+    }
+    if cli_commands.dry_run {
+        return print_dry_run(&config);
+    }
+    if let Some(dict_path) = &cli_commands.train_zstd_dict {
+        return sqlitedumper::train_zstd_dict(&config, dict_path);
+    }
+    cancel_on_signal(cancel.clone());
+    let stats = match db_paths.as_slice() {
+        [_] => sqlitedumper::dump_database(&config).await?,
+        _ => sqlitedumper::dump_databases(&config, &db_paths).await?,
+    };
+    if let Some(path) = &cli_commands.stats_file {
+        stats.write(path)?;
+    }
+    if let Some(path) = &cli_commands.metrics_file {
+        stats.write_metrics(path)?;
+    }
+    if cancel.is_cancelled() {
+        print_interrupted(&stats);
+        return Err(DumperError::Cancelled);
+    }
+    let failed = stats
+        .tables
+        .iter()
+        .filter(|table| table.error.is_some())
+        .count();
+    if failed > 0 {
+        return Err(DumperError::TablesFailed(failed));
+    }
 
-    log::info!(
-        "Dump {} completed. Elapsed {} ms",
-        cli_commands.file,
-        start_time.elapsed().as_millis()
+    let elapsed_ms = start_time.elapsed().as_millis() as u64;
+    tracing::info!(
+        databases = db_paths.join(", "),
+        elapsed_ms,
+        "Dump {} completed. Elapsed {elapsed_ms} ms",
+        db_paths.join(", ")
     );
+    if cli_commands.watch {
+        let debounce = std::time::Duration::from_millis(cli_commands.watch_debounce_ms);
+        sqlitedumper::watch(&config, debounce).await?;
+    }
     Ok(())
 }
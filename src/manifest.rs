@@ -0,0 +1,86 @@
+use std::path::Path;
+
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+use crate::output::FileRecord;
+
+/// One output file listed in the manifest written by `output_manifest`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ManifestEntry {
+    pub table: String,
+    /// Path of the file, relative to the directory of the manifest unless the
+    /// file is outside of it.
+    pub path: String,
+    pub rows: u64,
+    /// Size of the file in bytes, after compression.
+    pub bytes: u64,
+    /// Lowercase hex SHA-256 of the file contents.
+    pub sha256: String,
+    /// When the file was opened, in RFC 3339.
+    pub created: String,
+    /// Whether the table was dumped completely. Partial files kept with
+    /// `keep_partial` are not, and have no row count.
+    #[serde(default = "complete_default")]
+    pub complete: bool,
+    /// Whether the rows of the table were filtered, or come from a named
+    /// query, so that they are not compared with the rows of a table by
+    /// `verify_manifest`.
+    #[serde(default)]
+    pub filtered: bool,
+    /// Why the dump of a partial file stopped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+fn complete_default() -> bool {
+    true
+}
+
+/// Directory the paths in the manifest at `path` are relative to.
+pub(crate) fn dir(path: &str) -> &Path {
+    match Path::new(path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// `path` relative to the directory `dir`, or absolute if it is outside of it.
+fn relative_path(dir: &Path, path: &Path) -> Result<String, DumperError> {
+    let path = path.canonicalize()?;
+    Ok(path
+        .strip_prefix(dir)
+        .unwrap_or(&path)
+        .display()
+        .to_string())
+}
+
+/// Write the entries of the hashed `files` as a pretty printed JSON array to
+/// `path`.
+pub(crate) fn write(
+    config: &DumpConfig,
+    path: &str,
+    files: &[&FileRecord],
+) -> Result<(), DumperError> {
+    let manifest_dir = dir(path).canonicalize()?;
+    let mut entries = Vec::new();
+    for file in files {
+        let Some(sha256) = file.sha256.clone() else {
+            continue;
+        };
+        entries.push(ManifestEntry {
+            table: file.table.clone(),
+            path: relative_path(&manifest_dir, &file.path)?,
+            rows: file.rows,
+            bytes: file.bytes,
+            sha256,
+            created: file.created.to_rfc3339(),
+            complete: file.error.is_none(),
+            filtered: crate::query::is_filtered(config, &file.table),
+            error: file.error.clone(),
+        });
+    }
+    let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    serde_json::to_writer_pretty(file, &entries)?;
+    tracing::info!("Wrote manifest of {} files to {path}", entries.len());
+    Ok(())
+}
@@ -0,0 +1,194 @@
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+
+/// Name of the index list written with `include_indexes`.
+const INDEXES_FILE: &str = "_indexes.csv";
+/// Name of the index column list written with `include_indexes`.
+const INDEX_COLUMNS_FILE: &str = "_index_columns.csv";
+/// Name of the foreign key list written with `include_foreign_keys`.
+const FOREIGN_KEYS_FILE: &str = "_foreign_keys.csv";
+/// Name of the trigger list written with `include_triggers`.
+const TRIGGERS_FILE: &str = "_triggers.csv";
+
+/// Open the metadata file `name` in the output directory. The file
+/// describes the schema as it is now, so `append` replaces it like
+/// `overwrite` instead of repeating its rows.
+fn create_writer(
+    config: &DumpConfig,
+    name: &str,
+) -> Result<csv::Writer<std::fs::File>, DumperError> {
+    let path = std::path::Path::new(&config.output_dir).join(name);
+    let file = match config.append {
+        true => std::fs::File::create(&path),
+        false => crate::output::open_options(config).open(&path),
+    }
+    .map_err(|e| crate::output::open_error(&path, e))?;
+    Ok(csv::WriterBuilder::new()
+        .delimiter(config.delimiter)
+        .from_writer(file))
+}
+
+/// Write the indexes of `table_names` to `_indexes.csv`, and their columns
+/// to `_index_columns.csv`, in the output directory.
+pub(crate) fn write_indexes(
+    config: &DumpConfig,
+    table_names: &[String],
+) -> Result<(), DumperError> {
+    let conn = crate::create_db_connection_ro(config)?;
+    let mut indexes = create_writer(config, INDEXES_FILE)?;
+    indexes.write_record(["index", "table", "sql", "unique"])?;
+    let mut columns = create_writer(config, INDEX_COLUMNS_FILE)?;
+    columns.write_record(["index", "table", "seqno", "cid", "column"])?;
+
+    // Automatic indexes (e.g. for UNIQUE constraints) have no SQL.
+    let mut index_stmt = conn.prepare(
+        "SELECT m.name, m.sql, l.\"unique\" FROM sqlite_master m \
+         JOIN pragma_index_list(m.tbl_name) l ON l.name = m.name \
+         WHERE m.type = 'index' AND m.tbl_name = ?1 ORDER BY m.name",
+    )?;
+    let mut column_stmt = conn.prepare("SELECT seqno, cid, name FROM pragma_index_info(?1)")?;
+    for table_name in table_names {
+        let table_indexes = index_stmt
+            .query_map([table_name], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, bool>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        tracing::info!("Table {table_name} has {} indexes", table_indexes.len());
+        for (index, sql, unique) in table_indexes {
+            indexes.write_record([
+                index.as_str(),
+                table_name,
+                sql.as_deref().unwrap_or(""),
+                if unique { "1" } else { "0" },
+            ])?;
+            let mut rows = column_stmt.query([&index])?;
+            while let Some(row) = rows.next()? {
+                // Expression columns have no name and a cid of -2.
+                let name: Option<String> = row.get(2)?;
+                columns.write_record([
+                    index.as_str(),
+                    table_name,
+                    &row.get::<_, i64>(0)?.to_string(),
+                    &row.get::<_, i64>(1)?.to_string(),
+                    name.as_deref().unwrap_or(""),
+                ])?;
+            }
+        }
+    }
+    indexes.flush()?;
+    columns.flush()?;
+
+    drop(index_stmt);
+    drop(column_stmt);
+    crate::close_db_connection(conn, config);
+    Ok(())
+}
+
+/// Write the foreign keys of `table_names` to `_foreign_keys.csv` in the
+/// output directory.
+pub(crate) fn write_foreign_keys(
+    config: &DumpConfig,
+    table_names: &[String],
+) -> Result<(), DumperError> {
+    let conn = crate::create_db_connection_ro(config)?;
+    let mut writer = create_writer(config, FOREIGN_KEYS_FILE)?;
+    writer.write_record([
+        "id",
+        "seq",
+        "from_table",
+        "from_column",
+        "to_table",
+        "to_column",
+        "on_update",
+        "on_delete",
+        "match",
+    ])?;
+    let mut stmt = conn.prepare(
+        "SELECT id, seq, \"from\", \"table\", \"to\", on_update, on_delete, \"match\" \
+         FROM pragma_foreign_key_list(?1) ORDER BY id, seq",
+    )?;
+    for table_name in table_names {
+        let mut rows = stmt.query([table_name])?;
+        while let Some(row) = rows.next()? {
+            // A missing parent column refers to the primary key of the parent.
+            let to_column: Option<String> = row.get(4)?;
+            writer.write_record([
+                &row.get::<_, i64>(0)?.to_string(),
+                &row.get::<_, i64>(1)?.to_string(),
+                table_name,
+                &row.get::<_, String>(2)?,
+                &row.get::<_, String>(3)?,
+                to_column.as_deref().unwrap_or(""),
+                &row.get::<_, String>(5)?,
+                &row.get::<_, String>(6)?,
+                &row.get::<_, String>(7)?,
+            ])?;
+        }
+    }
+    writer.flush()?;
+
+    drop(stmt);
+    crate::close_db_connection(conn, config);
+    Ok(())
+}
+
+/// Timing and event of a `CREATE TRIGGER` statement. The timing defaults to
+/// `BEFORE` when the statement leaves it out.
+fn trigger_kind(sql: &str) -> (String, String) {
+    static KEYWORDS: std::sync::LazyLock<regex::Regex> = std::sync::LazyLock::new(|| {
+        regex::Regex::new(
+            r"(?is)\bTRIGGER\b.*?\b(?:(BEFORE|AFTER|INSTEAD\s+OF)\s+)?(DELETE|INSERT|UPDATE)\b",
+        )
+        .unwrap()
+    });
+    match KEYWORDS.captures(sql) {
+        Some(caps) => {
+            let timing = caps.get(1).map_or("BEFORE".to_string(), |m| {
+                m.as_str()
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+                    .to_uppercase()
+            });
+            (timing, caps[2].to_uppercase())
+        }
+        None => (String::new(), String::new()),
+    }
+}
+
+/// Write the triggers of `table_names` to `_triggers.csv` in the output
+/// directory.
+pub(crate) fn write_triggers(
+    config: &DumpConfig,
+    table_names: &[String],
+) -> Result<(), DumperError> {
+    let conn = crate::create_db_connection_ro(config)?;
+    let mut writer = create_writer(config, TRIGGERS_FILE)?;
+    writer.write_record(["trigger", "table", "timing", "event", "sql"])?;
+    let mut stmt = conn.prepare(
+        "SELECT name, sql FROM sqlite_master WHERE type = 'trigger' AND tbl_name = ?1 ORDER BY name",
+    )?;
+    for table_name in table_names {
+        let mut rows = stmt.query([table_name])?;
+        while let Some(row) = rows.next()? {
+            let sql: String = row.get(1)?;
+            let (timing, event) = trigger_kind(&sql);
+            writer.write_record([
+                row.get::<_, String>(0)?.as_str(),
+                table_name,
+                &timing,
+                &event,
+                &sql,
+            ])?;
+        }
+    }
+    writer.flush()?;
+
+    drop(stmt);
+    crate::close_db_connection(conn, config);
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+
+/// A SQL query whose result is dumped like a table, to a file named after
+/// the query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamedQuery {
+    pub name: String,
+    pub sql: String,
+}
+
+/// Parse a `name=SELECT ...` argument.
+pub fn parse_named_query(s: &str) -> Result<NamedQuery, DumperError> {
+    match s.split_once('=') {
+        Some((name, sql)) if !sql.trim().is_empty() => {
+            let query = NamedQuery {
+                name: name.trim().to_string(),
+                sql: sql.trim().to_string(),
+            };
+            validate_names(std::slice::from_ref(&query))?;
+            Ok(query)
+        }
+        _ => Err(DumperError::InvalidConfig(format!(
+            "expected name=SELECT ..., got '{s}'"
+        ))),
+    }
+}
+
+/// Read the `;` separated queries of a SQL file. A `-- name: foo` comment
+/// before a query names it, otherwise it is called `query_N`, counting from 1.
+pub fn read_query_file(path: &str) -> Result<Vec<NamedQuery>, DumperError> {
+    let text = std::fs::read_to_string(path)?;
+    Ok(parse_queries(&text))
+}
+
+/// Split `text` at the semicolons outside of string literals, quoted
+/// identifiers and comments.
+fn parse_queries(text: &str) -> Vec<NamedQuery> {
+    let bytes = text.as_bytes();
+    let mut queries = Vec::new();
+    let mut name: Option<String> = None;
+    let mut start = 0;
+    let mut has_code = false;
+    let mut i = 0;
+    // Index just after the next `close` at or after `from`, or the end.
+    let skip_to = |from: usize, close: &str| {
+        text[from..]
+            .find(close)
+            .map_or(text.len(), |n| from + n + close.len())
+    };
+    while i < bytes.len() {
+        match bytes[i] {
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                let end = text[i..].find('\n').map_or(text.len(), |n| i + n);
+                if !has_code && let Some(n) = text[i + 2..end].trim().strip_prefix("name:") {
+                    name = Some(n.trim().to_string());
+                }
+                i = end;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => i = skip_to(i + 2, "*/"),
+            // A doubled quote inside a literal is read as two adjacent literals.
+            b'\'' => (has_code, i) = (true, skip_to(i + 1, "'")),
+            b'"' => (has_code, i) = (true, skip_to(i + 1, "\"")),
+            b'`' => (has_code, i) = (true, skip_to(i + 1, "`")),
+            b'[' => (has_code, i) = (true, skip_to(i + 1, "]")),
+            b';' => {
+                if has_code {
+                    push_query(&mut queries, name.take(), &text[start..i]);
+                }
+                name = None;
+                has_code = false;
+                i += 1;
+                start = i;
+            }
+            c => {
+                has_code |= !c.is_ascii_whitespace();
+                i += 1;
+            }
+        }
+    }
+    if has_code {
+        push_query(&mut queries, name, &text[start..]);
+    }
+    queries
+}
+
+fn push_query(queries: &mut Vec<NamedQuery>, name: Option<String>, sql: &str) {
+    let name = name.unwrap_or_else(|| format!("query_{}", queries.len() + 1));
+    queries.push(NamedQuery {
+        name,
+        sql: sql.trim().to_string(),
+    });
+}
+
+/// Prepare `query`, failing unless it only reads.
+pub(crate) fn prepare<'c>(
+    conn: &'c rusqlite::Connection,
+    query: &NamedQuery,
+) -> Result<rusqlite::Statement<'c>, DumperError> {
+    let stmt = conn
+        .prepare(&query.sql)
+        .map_err(|source| DumperError::InvalidQuery {
+            name: query.name.clone(),
+            source,
+        })?;
+    if !stmt.readonly() {
+        return Err(DumperError::QueryNotReadOnly(query.name.clone()));
+    }
+    Ok(stmt)
+}
+
+/// Prepare every query of `config` so that syntax errors are reported before
+/// any output is written.
+pub(crate) fn check_queries(config: &DumpConfig) -> Result<(), DumperError> {
+    let conn = crate::create_db_connection_ro(config)?;
+    for query in &config.queries {
+        prepare(&conn, query)?;
+    }
+    crate::close_db_connection(conn, config);
+    Ok(())
+}
+
+/// Check that the query names are unique and usable as file names, i.e.
+/// only ASCII letters, digits and underscores.
+pub(crate) fn validate_names(queries: &[NamedQuery]) -> Result<(), DumperError> {
+    for (i, query) in queries.iter().enumerate() {
+        let valid = !query.name.is_empty()
+            && query
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(DumperError::InvalidConfig(format!(
+                "query name '{}' may only contain letters, digits and underscores",
+                query.name
+            )));
+        }
+        if queries[..i].iter().any(|other| other.name == query.name) {
+            return Err(DumperError::InvalidConfig(format!(
+                "query name '{}' is used more than once",
+                query.name
+            )));
+        }
+    }
+    Ok(())
+}
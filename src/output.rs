@@ -0,0 +1,295 @@
+use std::io::Write;
+
+use crate::checksum::{ChecksumAlgorithm, Hasher};
+use crate::compress::Compression;
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+
+/// Destination of a table dump.
+pub(crate) enum Sink {
+    File(Box<OutputFile>),
+    /// An entry of a ZIP archive. The entry must already be started, its
+    /// compression is handled by the archive.
+    Zip(Box<zip::ZipWriter<std::fs::File>>),
+    Memory(Vec<u8>),
+}
+
+impl Sink {
+    /// Bytes written so far to a file sink, 0 for other sinks.
+    pub(crate) fn len(&self) -> std::io::Result<u64> {
+        match self {
+            Sink::File(file) => Ok(file.file.metadata()?.len()),
+            Sink::Zip(_) | Sink::Memory(_) => Ok(0),
+        }
+    }
+
+    /// Path of a file sink, `None` for other sinks.
+    pub(crate) fn path(&self) -> Option<&std::path::Path> {
+        match self {
+            Sink::File(file) => Some(&file.path),
+            Sink::Zip(_) | Sink::Memory(_) => None,
+        }
+    }
+
+    /// Record of a finished file sink holding `rows` rows of `table_name`,
+    /// `None` for other sinks.
+    pub(crate) fn into_record(self, table_name: &str, rows: u64) -> Option<FileRecord> {
+        let Sink::File(file) = self else {
+            return None;
+        };
+        Some(FileRecord {
+            table: table_name.to_string(),
+            path: file.path,
+            rows,
+            bytes: file.bytes,
+            created: file.created,
+            sha256: file.sha256.map(Hasher::finish),
+            checksum: file.checksum.map(Hasher::finish),
+            error: None,
+        })
+    }
+}
+
+/// An output file, hashed while it is written when a manifest or checksums
+/// are requested.
+pub(crate) struct OutputFile {
+    file: std::fs::File,
+    path: std::path::PathBuf,
+    created: chrono::DateTime<chrono::Utc>,
+    sha256: Option<Hasher>,
+    checksum: Option<Hasher>,
+    bytes: u64,
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.file.write(buf)?;
+        for hasher in [&mut self.sha256, &mut self.checksum].into_iter().flatten() {
+            hasher.update(&buf[..n]);
+        }
+        self.bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Sink::File(w) => w.write(buf),
+            Sink::Zip(w) => w.write(buf),
+            Sink::Memory(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Sink::File(w) => w.flush(),
+            Sink::Zip(w) => w.flush(),
+            Sink::Memory(w) => w.flush(),
+        }
+    }
+}
+
+/// A finished output file and its hashes.
+#[derive(Debug, Clone)]
+pub(crate) struct FileRecord {
+    pub(crate) table: String,
+    pub(crate) path: std::path::PathBuf,
+    pub(crate) rows: u64,
+    /// Size of the file in bytes, after compression.
+    pub(crate) bytes: u64,
+    pub(crate) created: chrono::DateTime<chrono::Utc>,
+    pub(crate) sha256: Option<String>,
+    /// Digest of the `checksum` algorithm.
+    pub(crate) checksum: Option<String>,
+    /// Why the dump of the file stopped, for a partial file.
+    pub(crate) error: Option<String>,
+}
+
+impl FileRecord {
+    /// Record of the partial file `path` of `table_name`, hashed for the
+    /// manifest. Its rows are not counted and it gets no checksum.
+    pub(crate) fn partial(
+        config: &DumpConfig,
+        table_name: &str,
+        path: &std::path::Path,
+        error: &DumperError,
+    ) -> std::io::Result<FileRecord> {
+        let metadata = std::fs::metadata(path)?;
+        let sha256 = match &config.output_manifest {
+            Some(_) => {
+                let mut hasher = ChecksumAlgorithm::Sha256.hasher();
+                hasher.update(&std::fs::read(path)?);
+                Some(hasher.finish())
+            }
+            None => None,
+        };
+        Ok(FileRecord {
+            table: table_name.to_string(),
+            path: path.to_path_buf(),
+            rows: 0,
+            bytes: metadata.len(),
+            created: metadata
+                .created()
+                .map_or_else(|_| chrono::Utc::now(), Into::into),
+            sha256,
+            checksum: None,
+            error: Some(error.to_string()),
+        })
+    }
+}
+
+/// `name` made safe to use as one path component: path separators and
+/// `..` are replaced with `_`, so that it cannot leave its directory.
+pub(crate) fn path_component(name: &str) -> String {
+    name.replace(['/', '\\'], "_").replace("..", "__")
+}
+
+/// Output file name of `table_name`, relative to the output directory and
+/// without the compression extension, expanded from `output_pattern`.
+///
+/// The table name goes through [`path_component`]. With `part`,
+/// `_partNNN` is inserted before the format extension.
+pub(crate) fn file_name(config: &DumpConfig, table_name: &str, part: Option<usize>) -> String {
+    let now = chrono::Utc::now();
+    let mut name = config
+        .output_pattern
+        .replace("{table}", &path_component(table_name))
+        .replace("{date}", &now.format("%Y%m%d").to_string())
+        .replace("{datetime}", &now.format("%Y%m%dT%H%M%SZ").to_string())
+        .replace("{ext}", config.output_format.extension());
+    if let Some(part) = part {
+        let ext = format!(".{}", config.output_format.extension());
+        let at = name.strip_suffix(&ext).map_or(name.len(), str::len);
+        name.insert_str(at, &format!("_part{part:03}"));
+    }
+    name
+}
+
+/// Options for opening an output file. Fails if the file exists unless
+/// `append` or `overwrite` is set.
+pub(crate) fn open_options(config: &DumpConfig) -> std::fs::OpenOptions {
+    let mut options = std::fs::OpenOptions::new();
+    if config.append {
+        options.append(true).create(true);
+    } else if config.overwrite {
+        options.write(true).create(true).truncate(true);
+    } else {
+        options.write(true).create_new(true);
+    }
+    options
+}
+
+/// Open the output file `path`, creating its directory. Returns the file and
+/// whether it needs a header row.
+pub(crate) fn open_file(
+    config: &DumpConfig,
+    path: &std::path::Path,
+) -> Result<(Sink, bool), DumperError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let file = open_options(config)
+        .open(path)
+        .map_err(|e| open_error(path, e))?;
+    let header = !config.no_header && (!config.append || file.metadata()?.len() == 0);
+    let file = OutputFile {
+        file,
+        path: path.to_path_buf(),
+        created: chrono::Utc::now(),
+        sha256: config
+            .output_manifest
+            .as_ref()
+            .map(|_| ChecksumAlgorithm::Sha256.hasher()),
+        checksum: config.checksum.map(|algorithm| algorithm.hasher()),
+        bytes: 0,
+    };
+    Ok((Sink::File(Box::new(file)), header))
+}
+
+/// Turn a failure to open `path` into a [`DumperError`], explaining how to
+/// replace files that already exist.
+pub(crate) fn open_error(path: &std::path::Path, err: std::io::Error) -> DumperError {
+    if err.kind() == std::io::ErrorKind::AlreadyExists {
+        DumperError::OutputExists(path.to_path_buf())
+    } else {
+        err.into()
+    }
+}
+
+enum Encoder {
+    Plain(Sink),
+    Gzip(flate2::write::GzEncoder<Sink>),
+    Zstd(zstd::Encoder<'static, Sink>),
+    Lz4(lz4_flex::frame::FrameEncoder<Sink>),
+}
+
+/// Output of a table, compressed on the fly. Call [`OutputWriter::finish`]
+/// to write the compression trailer and get the sink back.
+pub(crate) struct OutputWriter {
+    encoder: Encoder,
+}
+
+impl OutputWriter {
+    /// The compression level is clamped to the range of the algorithm. It is
+    /// ignored for lz4.
+    pub(crate) fn new(sink: Sink, config: &DumpConfig) -> std::io::Result<OutputWriter> {
+        let level = config.compress_level;
+        let compression = match sink {
+            Sink::Zip(_) => Compression::None,
+            Sink::File(_) | Sink::Memory(_) => config.file_compression(),
+        };
+        let encoder = match compression {
+            Compression::None => Encoder::Plain(sink),
+            Compression::Gzip => {
+                let level = level.unwrap_or(6).clamp(1, 9) as u32;
+                Encoder::Gzip(flate2::write::GzEncoder::new(
+                    sink,
+                    flate2::Compression::new(level),
+                ))
+            }
+            Compression::Zstd => {
+                let level = level.unwrap_or(3).clamp(1, 22);
+                let dictionary = config.zstd_dictionary.as_deref().unwrap_or_default();
+                Encoder::Zstd(zstd::Encoder::with_dictionary(sink, level, dictionary)?)
+            }
+            Compression::Lz4 => Encoder::Lz4(lz4_flex::frame::FrameEncoder::new(sink)),
+        };
+        Ok(OutputWriter { encoder })
+    }
+
+    pub(crate) fn finish(self) -> std::io::Result<Sink> {
+        let mut sink = match self.encoder {
+            Encoder::Plain(sink) => sink,
+            Encoder::Gzip(encoder) => encoder.finish()?,
+            Encoder::Zstd(encoder) => encoder.finish()?,
+            Encoder::Lz4(encoder) => encoder.finish().map_err(std::io::Error::other)?,
+        };
+        sink.flush()?;
+        Ok(sink)
+    }
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match &mut self.encoder {
+            Encoder::Plain(w) => w.write(buf),
+            Encoder::Gzip(w) => w.write(buf),
+            Encoder::Zstd(w) => w.write(buf),
+            Encoder::Lz4(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.encoder {
+            Encoder::Plain(w) => w.flush(),
+            Encoder::Gzip(w) => w.flush(),
+            Encoder::Zstd(w) => w.flush(),
+            Encoder::Lz4(w) => w.flush(),
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+use crate::query::{Query, quote_identifier};
+
+/// Key and value columns of a table dumped in wide format: one row per
+/// timestamp and one column per distinct key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pivot {
+    pub key_column: String,
+    pub value_column: String,
+}
+
+/// The `SELECT` statement dumping `table_name` pivoted by `timestamp_column`,
+/// with the bounds of `since` like [`crate::query::build_query`].
+pub(crate) fn build_query(
+    conn: &rusqlite::Connection,
+    config: &DumpConfig,
+    table_name: &str,
+    pivot: &Pivot,
+    timestamp_column: &str,
+    since: &[(&str, i64)],
+) -> Result<Query, DumperError> {
+    let table = quote_identifier(table_name);
+    let stmt = conn.prepare(&format!("SELECT * FROM {table}"))?;
+    let names = stmt.column_names();
+    for column in [&pivot.key_column, &pivot.value_column] {
+        if !names.contains(&column.as_str()) {
+            return Err(DumperError::ColumnNotFound {
+                table: table_name.to_string(),
+                column: column.clone(),
+            });
+        }
+    }
+    drop(stmt);
+
+    let key = quote_identifier(&pivot.key_column);
+    let value = quote_identifier(&pivot.value_column);
+    let timestamp = quote_identifier(timestamp_column);
+    let mut stmt = conn.prepare(&format!(
+        "SELECT DISTINCT {key} FROM {table} WHERE {key} IS NOT NULL ORDER BY {key}"
+    ))?;
+    let keys = stmt
+        .query_map([], |row| row.get::<_, rusqlite::types::Value>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    tracing::info!("Table {table_name}: pivoting {} keys", keys.len());
+    let null_keys: u64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM {table} WHERE {key} IS NULL"),
+        [],
+        |row| row.get(0),
+    )?;
+    if null_keys > 0 {
+        tracing::warn!("Table {table_name}: dropping the values of {null_keys} rows without a key");
+    }
+
+    let mut columns = vec![timestamp.clone()];
+    let mut names = vec![timestamp_column.to_string()];
+    for key_value in &keys {
+        let (literal, key_name) = literal(key_value);
+        let name = unique_name(&names, &key_name);
+        if name != key_name {
+            tracing::warn!(
+                "Table {table_name}: key {literal} is written to column {name}, {key_name} is taken"
+            );
+        }
+        names.push(name.clone());
+        columns.push(format!(
+            "MAX(CASE WHEN {key} = {literal} THEN {value} END) AS {}",
+            quote_identifier(&name)
+        ));
+    }
+    let mut sql = format!("SELECT {} FROM {table}", columns.join(", "));
+    let mut params = Vec::new();
+    let mut clause = crate::query::where_clause(config, table_name);
+    for &(column, bound) in since {
+        let predicate = format!("{} > ?", quote_identifier(column));
+        clause = Some(match clause {
+            Some(clause) => format!("({clause}) AND {predicate}"),
+            None => predicate,
+        });
+        params.push(bound);
+    }
+    if let Some(clause) = clause {
+        sql.push_str(&format!(" WHERE {clause}"));
+    }
+    sql.push_str(&format!(" GROUP BY {timestamp} ORDER BY {timestamp}"));
+    if config.limit.is_some() || config.offset.is_some() {
+        sql.push_str(" LIMIT ? OFFSET ?");
+        params.push(config.limit.map_or(-1, |n| n as i64));
+        params.push(config.offset.unwrap_or(0) as i64);
+    }
+    tracing::debug!("Query for {table_name}: {sql} {params:?}");
+    Ok(Query { sql, params })
+}
+
+/// `name`, or the first of `name_2`, `name_3`... not in `names`. Keys of
+/// different types, like `1` and `'1'`, have the same name.
+fn unique_name(names: &[String], name: &str) -> String {
+    if !names.iter().any(|taken| taken == name) {
+        return name.to_string();
+    }
+    (2..)
+        .map(|n| format!("{name}_{n}"))
+        .find(|candidate| !names.contains(candidate))
+        .expect("there are fewer names than suffixes")
+}
+
+/// SQL literal of a key and the name of its column.
+fn literal(value: &rusqlite::types::Value) -> (String, String) {
+    use rusqlite::types::Value;
+
+    match value {
+        Value::Integer(i) => (i.to_string(), i.to_string()),
+        Value::Real(f) => (f.to_string(), f.to_string()),
+        Value::Text(s) => (format!("'{}'", s.replace('\'', "''")), s.clone()),
+        Value::Blob(b) => {
+            let hex: String = b.iter().map(|byte| format!("{byte:02X}")).collect();
+            (format!("X'{hex}'"), hex)
+        }
+        Value::Null => ("NULL".to_string(), String::new()),
+    }
+}
@@ -0,0 +1,85 @@
+use crate::compress::Compression;
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+use crate::output::Sink;
+
+/// Rows rendered to estimate the output size of a table.
+const SAMPLE_ROWS: u64 = 100;
+
+/// What a dump of one table would produce.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TablePlan {
+    pub table: String,
+    /// Rows that would be written, after `where`, `limit`, `offset` and the
+    /// `since` bounds.
+    pub row_count: u64,
+    /// Uncompressed output size extrapolated from the first rows.
+    pub estimated_bytes: u64,
+}
+
+fn count_rows(config: &DumpConfig, table_name: &str) -> Result<u64, DumperError> {
+    let conn = crate::create_db_connection_ro(config)?;
+    let query = crate::prepare_table(&conn, config, table_name)?.query;
+    let count = conn.query_row(
+        &format!("SELECT COUNT(*) FROM ({})", query.sql),
+        rusqlite::params_from_iter(&query.params),
+        |row| row.get(0),
+    )?;
+    Ok(count)
+}
+
+fn estimate_bytes(
+    config: &DumpConfig,
+    table_name: &str,
+    row_count: u64,
+) -> Result<u64, DumperError> {
+    let mut sample_config = config.clone();
+    sample_config.compression = Compression::None;
+    // Nothing is written, so BLOBs stay in the cells.
+    sample_config.blob_dir = None;
+    // A sample already ends in a LIMIT, so a smaller sample stands in for it.
+    match config.sample {
+        Some(sample) => sample_config.sample = Some(sample.min(SAMPLE_ROWS)),
+        None => {
+            sample_config.limit = Some(config.limit.map_or(SAMPLE_ROWS, |n| n.min(SAMPLE_ROWS)))
+        }
+    }
+    let (sink, sample) = crate::write_table(
+        &sample_config,
+        table_name,
+        Sink::Memory(Vec::new()),
+        !config.no_header,
+        None,
+        None,
+    )?;
+    let Sink::Memory(bytes) = sink else {
+        unreachable!("write_table returns the sink it was given");
+    };
+    let sample_bytes = bytes.len() as u64;
+    let sample_rows = sample.rows;
+    if sample_rows == 0 {
+        return Ok(sample_bytes);
+    }
+    Ok(sample_bytes * row_count / sample_rows)
+}
+
+/// Row counts and estimated output sizes of the tables `config` would dump,
+/// without writing anything.
+pub fn plan_dump(config: &DumpConfig) -> Result<Vec<TablePlan>, DumperError> {
+    let mut config = config.clone();
+    crate::state::apply(&mut config)?;
+    let config = &config;
+    let table_names = crate::get_tables(config)?;
+    let table_names = config.tables_filter.apply(table_names)?;
+    let mut plans = Vec::new();
+    for table_name in table_names {
+        let row_count = count_rows(config, &table_name)?;
+        let estimated_bytes = estimate_bytes(config, &table_name, row_count)?;
+        plans.push(TablePlan {
+            table: table_name,
+            row_count,
+            estimated_bytes,
+        });
+    }
+    Ok(plans)
+}
@@ -0,0 +1,60 @@
+use crate::error::DumperError;
+
+/// Pragmas that take effect on a read-only connection. Others, like
+/// `journal_mode` or `auto_vacuum`, need to write to the database and fail or
+/// do nothing.
+pub const READ_ONLY_PRAGMAS: &[&str] = &[
+    "automatic_index",
+    "busy_timeout",
+    "cache_size",
+    "cache_spill",
+    "case_sensitive_like",
+    "cell_size_check",
+    "foreign_keys",
+    "mmap_size",
+    "page_size",
+    "query_only",
+    "recursive_triggers",
+    "reverse_unordered_selects",
+    "temp_store",
+    "threads",
+    "trusted_schema",
+];
+
+/// Check that the pragma names and values are plain words, and warn about
+/// pragmas that do not work on a read-only connection.
+pub(crate) fn validate(pragmas: &[(String, String)]) -> Result<(), DumperError> {
+    let is_word = |s: &str| {
+        !s.is_empty()
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '+'))
+    };
+    for (name, value) in pragmas {
+        if !is_word(name) || !is_word(value) || name.starts_with(['-', '+', '.']) {
+            return Err(DumperError::UnsafeSqlFragment(format!("{name}={value}")));
+        }
+        if !READ_ONLY_PRAGMAS.contains(&name.to_lowercase().as_str()) {
+            tracing::warn!("PRAGMA {name} may not work on a read-only connection");
+        }
+    }
+    Ok(())
+}
+
+/// Run `PRAGMA name = value` for each of `pragmas` on `conn`.
+pub(crate) fn apply(
+    conn: &rusqlite::Connection,
+    pragmas: &[(String, String)],
+) -> Result<(), DumperError> {
+    for (name, value) in pragmas {
+        let pragma = format!("PRAGMA {name} = {value}");
+        let run = || -> rusqlite::Result<()> {
+            let mut stmt = conn.prepare(&pragma)?;
+            // Some pragmas return the new value, which is ignored.
+            let mut rows = stmt.query([])?;
+            while rows.next()?.is_some() {}
+            Ok(())
+        };
+        run().map_err(|source| DumperError::PragmaFailed { pragma, source })?;
+    }
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+
+/// Rows written between two updates of a table bar.
+pub(crate) const UPDATE_INTERVAL: u64 = 1000;
+
+/// One bar for the tables done so far and one bar per table being dumped.
+pub(crate) struct DumpProgress {
+    multi: MultiProgress,
+    overall: ProgressBar,
+}
+
+impl DumpProgress {
+    pub(crate) fn new(table_count: usize) -> DumpProgress {
+        let multi = MultiProgress::new();
+        let overall = multi.add(ProgressBar::new(table_count as u64));
+        overall.set_style(
+            ProgressStyle::with_template("{prefix:>20} [{bar:40}] {pos}/{len} tables {elapsed}")
+                .unwrap()
+                .progress_chars("=> "),
+        );
+        overall.set_prefix("total");
+        DumpProgress { multi, overall }
+    }
+
+    pub(crate) fn table_bar(&self, table_name: &str, row_count: u64) -> ProgressBar {
+        let bar = self
+            .multi
+            .insert_before(&self.overall, ProgressBar::new(row_count));
+        bar.set_style(
+            ProgressStyle::with_template("{prefix:>20} [{bar:40}] {pos}/{len} rows {per_sec}")
+                .unwrap()
+                .progress_chars("=> "),
+        );
+        bar.set_prefix(table_name.to_string());
+        bar
+    }
+
+    pub(crate) fn table_done(&self, bar: Option<ProgressBar>) {
+        if let Some(bar) = bar {
+            bar.finish_and_clear();
+            self.multi.remove(&bar);
+        }
+        self.overall.inc(1);
+    }
+
+    pub(crate) fn finish(&self) {
+        self.overall.finish();
+    }
+}
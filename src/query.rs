@@ -0,0 +1,242 @@
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+
+/// Reject SQL fragments that could end the statement or hide the rest of it.
+pub(crate) fn validate_sql_fragment(fragment: &str) -> Result<(), DumperError> {
+    if [";", "--", "/*", "*/"]
+        .iter()
+        .any(|token| fragment.contains(token))
+    {
+        return Err(DumperError::UnsafeSqlFragment(fragment.to_string()));
+    }
+    Ok(())
+}
+
+/// Quote `name` as an SQL identifier.
+pub(crate) fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Check that the columns selected for `table_name`, and its boolean and
+/// redacted columns, exist.
+pub(crate) fn validate_columns(
+    conn: &rusqlite::Connection,
+    config: &DumpConfig,
+    table_name: &str,
+) -> Result<(), DumperError> {
+    let redacted = config
+        .redact_columns
+        .iter()
+        .filter(|rule| rule.table == table_name)
+        .map(|rule| &rule.column);
+    let columns: Vec<&String> = [&config.columns, &config.bool_columns]
+        .into_iter()
+        .filter_map(|columns| columns.get(table_name))
+        .flatten()
+        .chain(redacted)
+        .collect();
+    if columns.is_empty() {
+        return Ok(());
+    }
+    let stmt = conn.prepare(&format!("SELECT * FROM {}", quote_identifier(table_name)))?;
+    let names = stmt.column_names();
+    for column in columns {
+        if !names.contains(&column.as_str()) {
+            return Err(DumperError::ColumnNotFound {
+                table: table_name.to_string(),
+                column: column.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Check that the columns of `column_order` exist in the tables of
+/// `table_names` they are listed for.
+pub(crate) fn check_column_order(
+    config: &DumpConfig,
+    table_names: &[String],
+) -> Result<(), DumperError> {
+    if config.column_order.is_empty() {
+        return Ok(());
+    }
+    let conn = crate::create_db_connection_ro(config)?;
+    for (table_name, columns) in &config.column_order {
+        if !table_names.contains(table_name) {
+            tracing::warn!("Column order of {table_name} is not used, the table is not dumped");
+            continue;
+        }
+        let stmt = conn.prepare(&format!("SELECT * FROM {}", quote_identifier(table_name)))?;
+        let names = stmt.column_names();
+        if let Some(column) = columns.iter().find(|c| !names.contains(&c.as_str())) {
+            return Err(DumperError::ColumnNotFound {
+                table: table_name.clone(),
+                column: column.clone(),
+            });
+        }
+    }
+    crate::close_db_connection(conn, config);
+    Ok(())
+}
+
+/// Whether `column` of `table_name` is dropped by `exclude_columns` or
+/// `table_exclude_columns`. Names are compared case-insensitively.
+pub(crate) fn is_excluded(config: &DumpConfig, table_name: &str, column: &str) -> bool {
+    config
+        .exclude_columns
+        .iter()
+        .chain(
+            config
+                .table_exclude_columns
+                .get(table_name)
+                .into_iter()
+                .flatten(),
+        )
+        .any(|excluded| excluded.eq_ignore_ascii_case(column))
+}
+
+/// Predicate for `table_name`, combining `where_clause` and `table_where`.
+pub(crate) fn where_clause(config: &DumpConfig, table_name: &str) -> Option<String> {
+    let clauses: Vec<&str> = config
+        .where_clause
+        .iter()
+        .chain(config.table_where.get(table_name))
+        .map(String::as_str)
+        .collect();
+    match clauses.as_slice() {
+        [] => None,
+        [clause] => Some(clause.to_string()),
+        _ => Some(
+            clauses
+                .iter()
+                .map(|c| format!("({c})"))
+                .collect::<Vec<_>>()
+                .join(" AND "),
+        ),
+    }
+}
+
+/// Whether the rows dumped from `table_name` are selected by a predicate, a
+/// lower bound, a sample or a limit, so that they can be fewer than the rows
+/// of the table, or by a named query, so that there is no table to count.
+pub(crate) fn is_filtered(config: &DumpConfig, table_name: &str) -> bool {
+    config.queries.iter().any(|q| q.name == table_name)
+        || where_clause(config, table_name).is_some()
+        || config.since_rowid.contains_key(table_name)
+        || crate::timestamp::since(config, table_name).is_some()
+        || config.sample.is_some()
+        || config.limit.is_some()
+        || config.offset.is_some()
+}
+
+/// Ordering terms, with `rowid` first when `order_by_rowid` is set.
+fn order_by(config: &DumpConfig) -> Option<String> {
+    match (config.order_by_rowid, &config.order_by) {
+        (true, Some(order_by)) => Some(format!("rowid, {order_by}")),
+        (true, None) => Some("rowid".to_string()),
+        (false, order_by) => order_by.clone(),
+    }
+}
+
+/// A `SELECT` statement and the values bound to its `?` parameters.
+pub(crate) struct Query {
+    pub(crate) sql: String,
+    pub(crate) params: Vec<i64>,
+}
+
+/// Whether `SELECT rowid, *` adds a new column for `table_name`. False for
+/// views, `WITHOUT ROWID` tables and tables with a column named `rowid`.
+pub(crate) fn has_rowid(
+    conn: &rusqlite::Connection,
+    table_name: &str,
+) -> Result<bool, DumperError> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?1)")?;
+    let mut columns = stmt.query_map([table_name], |row| row.get::<_, String>(0))?;
+    if columns.any(|name| name.is_ok_and(|name| name.eq_ignore_ascii_case("rowid"))) {
+        return Ok(false);
+    }
+    Ok(!crate::is_view(conn, table_name)
+        && conn
+            .prepare(&format!(
+                "SELECT rowid FROM {} LIMIT 0",
+                quote_identifier(table_name)
+            ))
+            .is_ok())
+}
+
+/// Multipliers and prime modulus of the seeded sample order, a cheap hash of
+/// the rowid and the seed that stays within 64 bits for rowids below 2^32.
+const SAMPLE_MULTIPLIER: i64 = 1_103_515_245;
+const SAMPLE_SEED_MULTIPLIER: i64 = 1_664_525;
+const SAMPLE_MODULUS: i64 = 2_147_483_629;
+
+/// The `SELECT` statement used to dump `table_name`, with the `rowid` as the
+/// first column when `rowid` is set. Each `(column, value)` of `since` keeps
+/// only the rows with `column > value`.
+pub(crate) fn build_query(
+    config: &DumpConfig,
+    table_name: &str,
+    rowid: bool,
+    since: &[(&str, i64)],
+) -> Query {
+    let mut columns = match config.columns.get(table_name) {
+        Some(names) => names
+            .iter()
+            .map(|name| quote_identifier(name))
+            .collect::<Vec<_>>()
+            .join(", "),
+        None => "*".to_string(),
+    };
+    if rowid {
+        columns.insert_str(0, "rowid, ");
+    }
+    let mut sql = format!("SELECT {columns} FROM {}", quote_identifier(table_name));
+    let mut params = Vec::new();
+    let mut clause = where_clause(config, table_name);
+    for &(column, value) in since {
+        let predicate = match column {
+            "rowid" => "rowid > ?".to_string(),
+            column => format!("{} > ?", quote_identifier(column)),
+        };
+        clause = Some(match clause {
+            Some(clause) => format!("({clause}) AND {predicate}"),
+            None => predicate,
+        });
+        params.push(value);
+        tracing::info!("Table {table_name}: rows after {column} {value}");
+    }
+    if let Some(clause) = clause {
+        sql.push_str(&format!(" WHERE {clause}"));
+    }
+    if let Some(sample) = config.sample {
+        match (config.sample_seed, rowid) {
+            (Some(seed), true) => {
+                sql.push_str(&format!(
+                    " ORDER BY (rowid * {SAMPLE_MULTIPLIER} + ?) % {SAMPLE_MODULUS}, rowid LIMIT ?"
+                ));
+                params.push(seed as i64 * SAMPLE_SEED_MULTIPLIER % SAMPLE_MODULUS);
+            }
+            _ => sql.push_str(" ORDER BY RANDOM() LIMIT ?"),
+        }
+        params.push(sample as i64);
+        tracing::info!("Table {table_name}: sample of {sample} rows");
+    }
+    if let Some(order_by) = order_by(config) {
+        sql.push_str(&format!(" ORDER BY {order_by}"));
+    }
+    if config.limit.is_some() || config.offset.is_some() {
+        // SQLite only accepts OFFSET after a LIMIT, -1 means no limit.
+        sql.push_str(" LIMIT ? OFFSET ?");
+        params.push(config.limit.map_or(-1, |n| n as i64));
+        params.push(config.offset.unwrap_or(0) as i64);
+        tracing::info!(
+            "Table {table_name}: LIMIT {} OFFSET {}",
+            config
+                .limit
+                .map_or("unlimited".to_string(), |n| n.to_string()),
+            config.offset.unwrap_or(0)
+        );
+    }
+    tracing::debug!("Query for {table_name}: {sql} {params:?}");
+    Query { sql, params }
+}
@@ -0,0 +1,83 @@
+use crate::checksum::ChecksumAlgorithm;
+use crate::config::DumpConfig;
+use crate::formats::Field;
+
+/// Text written for redacted values without `redact_hash`.
+const REDACTED: &str = "[REDACTED]";
+
+/// A column whose values are redacted, all of them or only those whose text
+/// matches `pattern`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RedactColumn {
+    pub table: String,
+    pub column: String,
+    pub pattern: Option<glob::Pattern>,
+}
+
+/// The rules of `redact_columns` for each of the `source` columns of
+/// `table_name`.
+pub(crate) fn column_rules<'c>(
+    config: &'c DumpConfig,
+    table_name: &str,
+    source: &[String],
+) -> Vec<Vec<&'c RedactColumn>> {
+    source
+        .iter()
+        .map(|column| {
+            config
+                .redact_columns
+                .iter()
+                .filter(|rule| rule.table == table_name && rule.column == *column)
+                .collect()
+        })
+        .collect()
+}
+
+/// `field` with a value matching one of `rules` replaced, and the parts of a
+/// TEXT value matching `redact_pattern`. NULL is kept.
+pub(crate) fn redact(field: Field, rules: &[&RedactColumn], config: &DumpConfig) -> Field {
+    if !rules.is_empty() {
+        let value = match &field {
+            Field::Null => None,
+            Field::Integer(i) => Some(i.to_string().into_bytes()),
+            Field::Real(f) => Some(f.to_string().into_bytes()),
+            Field::Text(s) => Some(s.as_bytes().to_vec()),
+            Field::Blob(b) => Some(b.clone()),
+        };
+        if let Some(value) = value {
+            // Patterns only match text, BLOBs are only redacted as a whole.
+            let text = match &field {
+                Field::Blob(_) => None,
+                _ => std::str::from_utf8(&value).ok(),
+            };
+            let matched = rules.iter().any(|rule| match (&rule.pattern, text) {
+                (None, _) => true,
+                (Some(pattern), Some(text)) => pattern.matches(text),
+                (Some(_), None) => false,
+            });
+            if matched {
+                return Field::Text(replacement(&value, config));
+            }
+        }
+    }
+    match (&field, &config.redact_pattern) {
+        (Field::Text(s), Some(re)) if re.is_match(s) => Field::Text(
+            re.replace_all(s, |caps: &regex::Captures<'_>| {
+                replacement(caps[0].as_bytes(), config)
+            })
+            .into_owned(),
+        ),
+        _ => field,
+    }
+}
+
+/// What a redacted `value` is written as: [`REDACTED`], or its SHA-256 hex
+/// digest with `redact_hash`.
+fn replacement(value: &[u8], config: &DumpConfig) -> String {
+    if !config.redact_hash {
+        return REDACTED.to_string();
+    }
+    let mut hasher = ChecksumAlgorithm::Sha256.hasher();
+    hasher.update(value);
+    hasher.finish()
+}
@@ -0,0 +1,141 @@
+use std::io::Write;
+
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+
+/// `CREATE` statements of the table or view `name`, followed by its indexes
+/// and triggers when the config asks for them.
+fn object_ddl(
+    conn: &rusqlite::Connection,
+    config: &DumpConfig,
+    name: &str,
+) -> Result<Vec<String>, DumperError> {
+    let mut statements = Vec::new();
+    let sql: Option<String> = conn.query_row(
+        "SELECT sql FROM sqlite_master WHERE name = ?1 AND type IN ('table', 'view')",
+        [name],
+        |row| row.get(0),
+    )?;
+    statements.extend(sql);
+
+    let mut types = Vec::new();
+    if config.include_indexes {
+        types.push("'index'");
+    }
+    if config.include_triggers {
+        types.push("'trigger'");
+    }
+    if !types.is_empty() {
+        // Automatic indexes (e.g. for UNIQUE constraints) have no SQL.
+        let mut stmt = conn.prepare(&format!(
+            "SELECT sql FROM sqlite_master WHERE tbl_name = ?1 AND type IN ({}) \
+             AND sql IS NOT NULL ORDER BY type, name",
+            types.join(", ")
+        ))?;
+        for sql in stmt.query_map([name], |row| row.get::<_, String>(0))? {
+            statements.push(sql?);
+        }
+    }
+    Ok(statements)
+}
+
+fn create_file(config: &DumpConfig, path: &std::path::Path) -> Result<std::fs::File, DumperError> {
+    crate::output::open_options(config)
+        .open(path)
+        .map_err(|e| crate::output::open_error(path, e))
+}
+
+/// Write the DDL of every table in `table_names` instead of its rows.
+///
+/// One `<table>.sql` per table, or a single `schema.sql` with `schema_file`.
+pub(crate) fn dump_schema(config: &DumpConfig, table_names: &[String]) -> Result<(), DumperError> {
+    let conn = crate::create_db_connection_ro(config)?;
+    let dir = std::path::Path::new(&config.output_dir);
+    let mut schema_file = if config.schema_file {
+        Some(create_file(config, &dir.join("schema.sql"))?)
+    } else {
+        None
+    };
+
+    for (i, table_name) in table_names.iter().enumerate() {
+        tracing::info!("Dumping schema of {table_name}");
+        let statements = object_ddl(&conn, config, table_name)?;
+        let ddl: String = statements.iter().map(|sql| format!("{sql};\n")).collect();
+        match schema_file.as_mut() {
+            Some(file) => {
+                if i > 0 {
+                    file.write_all(b"\n")?;
+                }
+                file.write_all(ddl.as_bytes())?;
+            }
+            None => {
+                let name = format!("{}.sql", crate::output::path_component(table_name));
+                let mut file = create_file(config, &dir.join(name))?;
+                file.write_all(ddl.as_bytes())?;
+            }
+        }
+    }
+
+    drop(schema_file);
+    crate::close_db_connection(conn, config);
+    Ok(())
+}
+
+/// One column as reported by `PRAGMA table_info`.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ColumnInfo {
+    pub cid: i64,
+    pub name: String,
+    /// Declared type, empty when the column has none.
+    pub decl_type: String,
+    pub not_null: bool,
+    pub default_value: Option<String>,
+    /// Position in the primary key starting at 1, 0 when not part of it.
+    pub primary_key: i64,
+    /// The column gets a parsed timestamp sibling when dumped.
+    pub timestamp: bool,
+}
+
+/// The columns of a table.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TableInfo {
+    pub table: String,
+    pub columns: Vec<ColumnInfo>,
+}
+
+/// Column information for every table selected by `config`.
+pub fn table_info(config: &DumpConfig) -> Result<Vec<TableInfo>, DumperError> {
+    let table_names = crate::get_tables(config)?;
+    let table_names = config.tables_filter.apply(table_names)?;
+    let conn = crate::create_db_connection_ro(config)?;
+    let mut tables = Vec::with_capacity(table_names.len());
+    for table_name in table_names {
+        let mut stmt = conn.prepare("SELECT * FROM pragma_table_info(?1)")?;
+        let mut columns = stmt
+            .query_map([&table_name], |row| {
+                Ok(ColumnInfo {
+                    cid: row.get("cid")?,
+                    name: row.get("name")?,
+                    decl_type: row.get("type")?,
+                    not_null: row.get("notnull")?,
+                    default_value: row.get("dflt_value")?,
+                    primary_key: row.get("pk")?,
+                    timestamp: false,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        let names: Vec<String> = columns.iter().map(|column| column.name.clone()).collect();
+        for (column, timestamp) in columns
+            .iter_mut()
+            .zip(crate::timestamp::detect_columns(&names, config))
+        {
+            column.timestamp = timestamp;
+        }
+        tables.push(TableInfo {
+            table: table_name,
+            columns,
+        });
+    }
+    crate::close_db_connection(conn, config);
+    Ok(tables)
+}
@@ -0,0 +1,49 @@
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+use crate::formats::{SingleFileWriter, TableWriter};
+use crate::output::{self, OutputWriter};
+use crate::stats::TableStats;
+
+/// Dump the tables one after another into the single file `path`, with a
+/// leading `_table` column naming the table of each row.
+pub(crate) fn dump_single_file(
+    config: &DumpConfig,
+    table_names: &[String],
+    path: &str,
+) -> Result<Vec<TableStats>, DumperError> {
+    let mut columns: Vec<String> = Vec::new();
+    for table_name in table_names {
+        for column in crate::table_columns(config, table_name)? {
+            if !columns.contains(&column) {
+                columns.push(column);
+            }
+        }
+    }
+
+    let path = std::path::Path::new(path);
+    let (sink, header) = output::open_file(config, path)?;
+    let writer =
+        crate::formats::new_table_writer(config, "", OutputWriter::new(sink, config)?, header)?;
+    let mut writer = SingleFileWriter::new(writer, columns)?;
+    let mut tables = Vec::with_capacity(table_names.len());
+    for table_name in table_names {
+        tracing::info!("Dumping table {table_name} into {}", path.display());
+        let start_time = std::time::Instant::now();
+        writer.start_table(table_name);
+        let written = crate::write_rows(config, table_name, &mut writer, None)?;
+        tracing::info!(
+            table = table_name,
+            rows = written.rows,
+            "Table {table_name}: {} rows",
+            written.rows
+        );
+        let mut stats = TableStats::new(table_name, start_time);
+        stats.rows = Some(written.rows);
+        stats.max_rowid = written.max_rowid;
+        stats.max_timestamp = written.max_timestamp;
+        tables.push(stats);
+    }
+    Box::new(writer).finish()?;
+    tracing::info!("Wrote {}", path.display());
+    Ok(tables)
+}
@@ -0,0 +1,66 @@
+use std::collections::BTreeMap;
+
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+
+/// Per-table lower bound of an incremental dump, kept between runs as a JSON
+/// object mapping table names to integers.
+pub(crate) type State = BTreeMap<String, i64>;
+
+/// Read the state file at `path`. A missing file is an empty state, so the
+/// first run dumps everything.
+pub(crate) fn read(path: &str) -> Result<State, DumperError> {
+    match std::fs::File::open(path) {
+        Ok(file) => Ok(serde_json::from_reader(std::io::BufReader::new(file))?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(State::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Add the bounds of the rowid and timestamp state files of `config` to its
+/// `since_rowid` and `table_since_timestamp`. A `since_rowid` given for a
+/// table wins over its state. Returns the states read, to be updated after
+/// the dump.
+pub(crate) fn apply(
+    config: &mut DumpConfig,
+) -> Result<(Option<State>, Option<State>), DumperError> {
+    let rowid_state = match &config.rowid_state_file {
+        Some(path) => {
+            let rowid_state = read(path)?;
+            for (table_name, rowid) in &rowid_state {
+                config
+                    .since_rowid
+                    .entry(table_name.clone())
+                    .or_insert(*rowid);
+            }
+            Some(rowid_state)
+        }
+        None => None,
+    };
+    let timestamp_state = match &config.timestamp_state_file {
+        Some(path) => {
+            let timestamp_state = read(path)?;
+            config.table_since_timestamp.extend(timestamp_state.clone());
+            Some(timestamp_state)
+        }
+        None => None,
+    };
+    Ok((rowid_state, timestamp_state))
+}
+
+/// Raise the bounds of `state` to the `(table, value)` pairs and write it to
+/// `path`.
+pub(crate) fn update(
+    path: &str,
+    mut state: State,
+    values: impl IntoIterator<Item = (String, i64)>,
+) -> Result<(), DumperError> {
+    for (table, value) in values {
+        let bound = state.entry(table).or_insert(value);
+        *bound = (*bound).max(value);
+    }
+    let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    serde_json::to_writer_pretty(file, &state)?;
+    tracing::info!("Wrote state of {} tables to {path}", state.len());
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use crate::error::DumperError;
+use crate::output::FileRecord;
+
+/// Outcome of dumping one table.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TableStats {
+    pub table: String,
+    pub elapsed_ms: u64,
+    /// Rows written, `None` when no rows were dumped.
+    pub rows: Option<u64>,
+    /// Largest rowid dumped, when the rowid was read.
+    pub max_rowid: Option<i64>,
+    /// Largest integer value of the timestamp column dumped, when the rows
+    /// were selected by timestamp.
+    pub max_timestamp: Option<i64>,
+    /// Size of the output files of the table, `None` when it is not a file
+    /// of its own.
+    pub bytes: Option<u64>,
+    /// Why the table failed, if it did.
+    pub error: Option<String>,
+    /// Output files of the table, for the manifest and checksum file.
+    #[serde(skip)]
+    pub(crate) files: Vec<FileRecord>,
+}
+
+impl TableStats {
+    pub(crate) fn new(table: &str, started: std::time::Instant) -> TableStats {
+        TableStats {
+            table: table.to_string(),
+            elapsed_ms: started.elapsed().as_millis() as u64,
+            rows: None,
+            max_rowid: None,
+            max_timestamp: None,
+            bytes: None,
+            error: None,
+            files: Vec::new(),
+        }
+    }
+}
+
+/// Timing, row counts and errors of a whole dump.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct DumpStats {
+    pub elapsed_ms: u64,
+    pub tables: Vec<TableStats>,
+    /// With a compared database, the tables found in only one of the two.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unmatched_tables: Vec<UnmatchedTable>,
+}
+
+/// Throughput of one dumped table, as written by [`DumpStats::write_metrics`].
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct TableMetrics {
+    pub rows: u64,
+    /// Size of the output files, compressed if compression is on. `None`
+    /// when the table is not a file of its own.
+    pub bytes: Option<u64>,
+    pub duration_ms: u64,
+    pub throughput_rows_per_sec: f64,
+    pub throughput_mb_per_sec: Option<f64>,
+}
+
+impl TableMetrics {
+    /// Metrics of a table that was dumped without an error.
+    fn from_stats(stats: &TableStats) -> Option<TableMetrics> {
+        let rows = match (stats.rows, &stats.error) {
+            (Some(rows), None) => rows,
+            _ => return None,
+        };
+        // A millisecond at least, so that fast tables have a throughput.
+        let secs = stats.elapsed_ms.max(1) as f64 / 1000.0;
+        Some(TableMetrics {
+            rows,
+            bytes: stats.bytes,
+            duration_ms: stats.elapsed_ms,
+            throughput_rows_per_sec: rows as f64 / secs,
+            throughput_mb_per_sec: stats.bytes.map(|bytes| bytes as f64 / 1e6 / secs),
+        })
+    }
+}
+
+/// A table of a delta dump that only one of the databases has.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct UnmatchedTable {
+    pub table: String,
+    /// Path of the database that has the table.
+    pub database: String,
+}
+
+impl DumpStats {
+    /// Write the stats as pretty printed JSON to `path`.
+    pub fn write(&self, path: &str) -> Result<(), DumperError> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    /// Metrics of the tables dumped without an error, by table name.
+    pub fn metrics(&self) -> Vec<(String, TableMetrics)> {
+        self.tables
+            .iter()
+            .filter_map(|table| Some((table.table.clone(), TableMetrics::from_stats(table)?)))
+            .collect()
+    }
+
+    /// Write the [`metrics`](Self::metrics) as a pretty printed JSON object
+    /// keyed by table name to `path`.
+    pub fn write_metrics(&self, path: &str) -> Result<(), DumperError> {
+        let metrics: serde_json::Map<String, serde_json::Value> = self
+            .metrics()
+            .into_iter()
+            .map(|(table, metrics)| Ok((table, serde_json::to_value(metrics)?)))
+            .collect::<Result<_, serde_json::Error>>()?;
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer_pretty(file, &metrics)?;
+        Ok(())
+    }
+}
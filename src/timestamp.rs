@@ -0,0 +1,173 @@
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+use crate::formats::Field;
+
+/// Unit of integer epoch timestamps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EpochUnit {
+    #[default]
+    Seconds,
+    Millis,
+    Micros,
+}
+
+impl EpochUnit {
+    fn to_datetime(self, ts: i64) -> Option<chrono::DateTime<chrono::Utc>> {
+        use chrono::TimeZone;
+        let (secs, nanos) = match self {
+            EpochUnit::Seconds => (ts, 0),
+            EpochUnit::Millis => (ts.div_euclid(1_000), ts.rem_euclid(1_000) * 1_000_000),
+            EpochUnit::Micros => (ts.div_euclid(1_000_000), ts.rem_euclid(1_000_000) * 1_000),
+        };
+        chrono::Utc.timestamp_opt(secs, nanos as u32).single()
+    }
+
+    fn to_datetime_f64(self, ts: f64) -> Option<chrono::DateTime<chrono::Utc>> {
+        let nanos_per_unit = match self {
+            EpochUnit::Seconds => 1e9,
+            EpochUnit::Millis => 1e6,
+            EpochUnit::Micros => 1e3,
+        };
+        let nanos = (ts * nanos_per_unit).round();
+        // Out of range and NaN values have no timestamp.
+        if !(i64::MIN as f64..=i64::MAX as f64).contains(&nanos) {
+            return None;
+        }
+        Some(chrono::DateTime::from_timestamp_nanos(nanos as i64))
+    }
+
+    pub(crate) fn epoch(self, datetime: chrono::DateTime<chrono::Utc>) -> i64 {
+        match self {
+            EpochUnit::Seconds => datetime.timestamp(),
+            EpochUnit::Millis => datetime.timestamp_millis(),
+            EpochUnit::Micros => datetime.timestamp_micros(),
+        }
+    }
+
+    fn seconds_format(self) -> chrono::SecondsFormat {
+        match self {
+            EpochUnit::Seconds => chrono::SecondsFormat::Secs,
+            EpochUnit::Millis => chrono::SecondsFormat::Millis,
+            EpochUnit::Micros => chrono::SecondsFormat::Micros,
+        }
+    }
+}
+
+/// Columns always parsed as timestamps.
+const DEFAULT_TIMESTAMP_COLUMNS: [&str; 2] = ["sm_timestamp", "timestamp"];
+
+/// Flag the columns that get a parsed timestamp sibling column.
+pub(crate) fn detect_columns(columns: &[String], config: &DumpConfig) -> Vec<bool> {
+    columns
+        .iter()
+        .map(|column| {
+            DEFAULT_TIMESTAMP_COLUMNS.contains(&column.as_str())
+                || config.timestamp_columns.contains(column)
+        })
+        .collect()
+}
+
+/// The first timestamp column of `table_name`, if it has one.
+pub(crate) fn table_column(
+    conn: &rusqlite::Connection,
+    config: &DumpConfig,
+    table_name: &str,
+) -> Result<Option<String>, DumperError> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?1)")?;
+    let columns = stmt
+        .query_map([table_name], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    let is_timestamp = detect_columns(&columns, config);
+    Ok(columns
+        .into_iter()
+        .zip(is_timestamp)
+        .find_map(|(column, is_timestamp)| is_timestamp.then_some(column)))
+}
+
+/// Lower bound of the timestamp column of `table_name` as an epoch value: the
+/// later of `since_timestamp` and the bound from the state file.
+pub(crate) fn since(config: &DumpConfig, table_name: &str) -> Option<i64> {
+    let global = config
+        .since_timestamp
+        .map(|datetime| config.epoch_unit.epoch(datetime));
+    let table = config.table_since_timestamp.get(table_name).copied();
+    global.max(table)
+}
+
+/// Parse the `--since-timestamp` value, an ISO 8601 timestamp read as UTC
+/// without an offset.
+pub fn parse_since_timestamp(s: &str) -> Result<chrono::DateTime<chrono::Utc>, DumperError> {
+    parse_text(s.trim()).ok_or_else(|| DumperError::InvalidTimestamp(s.to_string()))
+}
+
+/// Name of the parsed sibling of the timestamp column `name`.
+pub(crate) fn parsed_column_name(name: &str, columns: &[String]) -> String {
+    // `sm_timestamp` keeps its historical `timestamp_parsed` sibling unless it clashes.
+    if name == "sm_timestamp" && !columns.iter().any(|column| column == "timestamp") {
+        return "timestamp_parsed".to_string();
+    }
+    format!("{name}_parsed")
+}
+
+/// Layouts tried for text timestamps without a UTC offset, read as UTC.
+const NAIVE_FORMATS: [&str; 2] = ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"];
+
+/// Parse an ISO 8601 text timestamp.
+fn parse_text(s: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    if let Ok(datetime) = chrono::DateTime::parse_from_rfc3339(s) {
+        return Some(datetime.to_utc());
+    }
+    if let Some(datetime) = NAIVE_FORMATS
+        .iter()
+        .find_map(|format| chrono::NaiveDateTime::parse_from_str(s, format).ok())
+    {
+        return Some(datetime.and_utc());
+    }
+    chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|datetime| datetime.and_utc())
+}
+
+/// Convert an epoch or ISO 8601 column value into a formatted timestamp.
+///
+/// REAL values are epoch timestamps with a fraction. NULL, BLOBs, epoch
+/// values out of range and text that is neither an integer nor an ISO 8601
+/// timestamp give an empty string.
+pub(crate) fn parse_timestamp(field: &Field, config: &DumpConfig) -> String {
+    let datetime = match field {
+        Field::Integer(i) => config.epoch_unit.to_datetime(*i),
+        Field::Real(f) => config.epoch_unit.to_datetime_f64(*f),
+        Field::Text(s) => match s.parse::<i64>() {
+            Ok(ts) => config.epoch_unit.to_datetime(ts),
+            Err(_) => parse_text(s),
+        },
+        Field::Null | Field::Blob(_) => None,
+    };
+    let Some(datetime) = datetime else {
+        return String::new();
+    };
+    match config.timestamp_tz {
+        Some(tz) => format_datetime(datetime.with_timezone(&tz), config),
+        None => format_datetime(datetime, config),
+    }
+}
+
+fn format_datetime<Tz>(datetime: chrono::DateTime<Tz>, config: &DumpConfig) -> String
+where
+    Tz: chrono::TimeZone,
+    Tz::Offset: std::fmt::Display,
+{
+    match &config.timestamp_format {
+        Some(format) => datetime.format(format).to_string(),
+        None => datetime.to_rfc3339_opts(config.epoch_unit.seconds_format(), true),
+    }
+}
+
+/// Check that `format` is a valid `chrono` format string.
+pub(crate) fn validate_format(format: &str) -> Result<(), DumperError> {
+    chrono::format::StrftimeItems::new(format)
+        .parse()
+        .map(|_| ())
+        .map_err(|_| DumperError::InvalidConfig(format!("invalid timestamp format '{format}'")))
+}
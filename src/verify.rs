@@ -0,0 +1,120 @@
+use std::io::Read;
+
+use crate::checksum::ChecksumAlgorithm;
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+use crate::manifest::ManifestEntry;
+
+/// Outcome of checking the files of one table listed in a manifest.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TableVerification {
+    pub table: String,
+    /// Sum of the rows of the table's files in the manifest.
+    pub expected_rows: u64,
+    /// Rows in the database now, `None` if they could not be counted or the
+    /// rows were filtered.
+    pub actual_rows: Option<u64>,
+    /// Whether the rows were filtered when dumped, so that they were not
+    /// compared with the rows in the database.
+    pub filtered: bool,
+    /// What did not match, empty if the table passed.
+    pub failures: Vec<String>,
+}
+
+impl TableVerification {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Check a manifest written by `output_manifest` against the files it lists
+/// and the database of `config`: the size and SHA-256 of every file, and the
+/// row count of every table that was dumped without filtering its rows.
+pub fn verify_manifest(
+    config: &DumpConfig,
+    manifest_path: &str,
+) -> Result<Vec<TableVerification>, DumperError> {
+    let file = std::io::BufReader::new(std::fs::File::open(manifest_path)?);
+    let entries: Vec<ManifestEntry> = serde_json::from_reader(file)?;
+    let manifest_dir = crate::manifest::dir(manifest_path);
+
+    let mut tables: Vec<TableVerification> = Vec::new();
+    for entry in &entries {
+        let index = match tables.iter().position(|t| t.table == entry.table) {
+            Some(index) => index,
+            None => {
+                tables.push(TableVerification {
+                    table: entry.table.clone(),
+                    expected_rows: 0,
+                    actual_rows: None,
+                    filtered: false,
+                    failures: Vec::new(),
+                });
+                tables.len() - 1
+            }
+        };
+        let table = &mut tables[index];
+        table.expected_rows += entry.rows;
+        table.filtered |= entry.filtered;
+        if !entry.complete {
+            let error = entry.error.as_deref().unwrap_or("unknown error");
+            table
+                .failures
+                .push(format!("{}: partial dump. {error}", entry.path));
+        } else if let Err(failure) = verify_file(manifest_dir, entry) {
+            table.failures.push(failure);
+        }
+    }
+
+    let conn = crate::create_db_connection_ro(config)?;
+    for table in tables.iter_mut().filter(|table| !table.filtered) {
+        let sql = format!(
+            "SELECT COUNT(*) FROM {}",
+            crate::query::quote_identifier(&table.table)
+        );
+        match conn.query_row(&sql, [], |row| row.get::<_, u64>(0)) {
+            Ok(count) => {
+                table.actual_rows = Some(count);
+                if count != table.expected_rows {
+                    table.failures.push(format!(
+                        "manifest has {} rows, database has {count}",
+                        table.expected_rows
+                    ));
+                }
+            }
+            Err(e) => table.failures.push(format!("cannot count rows. {e}")),
+        }
+    }
+    crate::close_db_connection(conn, config);
+    Ok(tables)
+}
+
+/// Compare the size and hash of the file of `entry`, in the manifest
+/// directory `manifest_dir`, with the manifest.
+fn verify_file(manifest_dir: &std::path::Path, entry: &ManifestEntry) -> Result<(), String> {
+    let mut file = std::fs::File::open(manifest_dir.join(&entry.path))
+        .map_err(|e| format!("{}: {e}", entry.path))?;
+    let mut hasher = ChecksumAlgorithm::Sha256.hasher();
+    let mut bytes = 0;
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = file
+            .read(&mut buf)
+            .map_err(|e| format!("{}: {e}", entry.path))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        bytes += n as u64;
+    }
+    if bytes != entry.bytes {
+        return Err(format!(
+            "{}: manifest has {} bytes, file has {bytes}",
+            entry.path, entry.bytes
+        ));
+    }
+    if hasher.finish() != entry.sha256 {
+        return Err(format!("{}: SHA-256 does not match", entry.path));
+    }
+    Ok(())
+}
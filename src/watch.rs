@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+use crate::filter::TableFilter;
+
+/// Row count and schema of a table. The table is dumped again when either
+/// changes.
+type Fingerprint = (u64, Option<String>);
+
+/// Watch the database of `config` and its WAL file, and dump again the
+/// tables whose row count or schema changed, overwriting their output.
+/// Changes are collected until none came for `debounce`. Errors are logged
+/// and watching goes on until `config.cancel` is cancelled.
+pub async fn watch(config: &DumpConfig, debounce: std::time::Duration) -> Result<(), DumperError> {
+    use notify::Watcher;
+
+    let db_path = std::path::absolute(&config.db_path)?;
+    let watched: Vec<std::path::PathBuf> = ["", "-wal"]
+        .iter()
+        .map(|suffix| {
+            let mut path = db_path.clone().into_os_string();
+            path.push(suffix);
+            path.into()
+        })
+        .collect();
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        // The receiver is only gone once watching stopped.
+        let _ = tx.send(event);
+    })?;
+    // The directory is watched, the files may be replaced or not exist yet.
+    let dir = db_path.parent().unwrap_or(std::path::Path::new("."));
+    watcher.watch(dir, notify::RecursiveMode::NonRecursive)?;
+
+    let mut config = config.clone();
+    config.overwrite = true;
+    config.append = false;
+    let filter = std::mem::take(&mut config.tables_filter);
+    let mut known = fingerprints(&config, &filter)?;
+    tracing::info!(database = config.db_path, "Watching {}", config.db_path);
+    loop {
+        // Wait for a change, then until none came for `debounce`.
+        let mut changed = false;
+        loop {
+            let event = tokio::select! {
+                event = rx.recv() => event,
+                _ = tokio::time::sleep(debounce), if changed => break,
+                _ = config.cancel.cancelled() => return Ok(()),
+            };
+            match event {
+                Some(Ok(event)) => {
+                    changed |= (event.kind.is_modify() || event.kind.is_create())
+                        && event.paths.iter().any(|path| watched.contains(path));
+                }
+                Some(Err(e)) => tracing::warn!("Error while watching {}. {e}", dir.display()),
+                None => return Ok(()),
+            }
+        }
+
+        let current = match fingerprints(&config, &filter) {
+            Ok(current) => current,
+            Err(e) => {
+                tracing::warn!(error = %e, "Cannot read the tables of {}. {e}", config.db_path);
+                continue;
+            }
+        };
+        let tables: Vec<String> = current
+            .iter()
+            .filter(|&(table, fingerprint)| known.get(table) != Some(fingerprint))
+            .map(|(table, _)| table.clone())
+            .collect();
+        known = current;
+        if tables.is_empty() {
+            tracing::debug!("{} changed, but none of its tables", config.db_path);
+            continue;
+        }
+        tracing::info!(
+            database = config.db_path,
+            tables = tables.join(", "),
+            "Tables changed, dumping {}",
+            tables.join(", ")
+        );
+        config.tables_filter = TableFilter {
+            include: tables,
+            ..Default::default()
+        };
+        match crate::dump_database(&config).await {
+            Ok(stats) => tracing::info!(
+                database = config.db_path,
+                elapsed_ms = stats.elapsed_ms,
+                "Dumped {} changed tables. Elapsed {} ms",
+                stats.tables.len(),
+                stats.elapsed_ms
+            ),
+            Err(e) => {
+                tracing::error!(error = %e, "Error while dumping {}. {e}", config.db_path)
+            }
+        }
+    }
+}
+
+/// Fingerprints of the tables of `config` selected by `filter`.
+fn fingerprints(
+    config: &DumpConfig,
+    filter: &TableFilter,
+) -> Result<HashMap<String, Fingerprint>, DumperError> {
+    let mut config = config.clone();
+    config.tables_filter = filter.clone();
+    let counts = crate::count_tables(&config)?;
+    let conn = crate::create_db_connection_ro(&config)?;
+    let mut stmt = conn
+        .prepare("SELECT tbl_name, group_concat(sql, ';') FROM sqlite_master GROUP BY tbl_name")?;
+    let mut schemas = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get(1)?)))?
+        .collect::<Result<HashMap<String, Option<String>>, _>>()?;
+    drop(stmt);
+    crate::close_db_connection(conn, &config);
+    Ok(counts
+        .into_iter()
+        .map(|(table, count)| {
+            let schema = schemas.remove(&table).flatten();
+            (table, (count, schema))
+        })
+        .collect())
+}
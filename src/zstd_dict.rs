@@ -0,0 +1,57 @@
+use crate::config::DumpConfig;
+use crate::error::DumperError;
+use crate::formats::Field;
+
+/// Rows sampled from each table when no `limit` is configured.
+const SAMPLE_ROWS_PER_TABLE: u64 = 1000;
+/// Same default as `zstd --train`.
+const MAX_DICT_SIZE: usize = 112_640;
+
+/// Render every sampled row as one CSV record, like the dump would.
+fn sample_table(
+    conn: &rusqlite::Connection,
+    config: &DumpConfig,
+    table_name: &str,
+    samples: &mut Vec<Vec<u8>>,
+) -> Result<(), DumperError> {
+    let limit = config.limit.unwrap_or(SAMPLE_ROWS_PER_TABLE);
+    let mut stmt = conn.prepare(&format!(
+        "SELECT * FROM {} LIMIT ?",
+        crate::query::quote_identifier(table_name)
+    ))?;
+    let column_count = stmt.column_count();
+    let mut rows = stmt.query([limit as i64])?;
+    while let Some(row) = rows.next()? {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(config.delimiter)
+            .from_writer(Vec::new());
+        for i in 0..column_count {
+            let field = Field::from_value_ref(row.get_ref(i)?)?;
+            writer.write_field(field.to_text(config))?;
+        }
+        writer.write_record(None::<&[u8]>)?;
+        samples.push(writer.into_inner().map_err(|e| e.into_error())?);
+    }
+    Ok(())
+}
+
+/// Train a zstd dictionary from rows of the selected tables and write it to `dict_path`.
+pub fn train_zstd_dict(config: &DumpConfig, dict_path: &str) -> Result<(), DumperError> {
+    let table_names = crate::get_tables(config)?;
+    let table_names = config.tables_filter.apply(table_names)?;
+    let conn = crate::create_db_connection_ro(config)?;
+    let mut samples = Vec::new();
+    for table_name in &table_names {
+        tracing::info!("Sampling table {table_name}");
+        sample_table(&conn, config, table_name, &mut samples)?;
+    }
+    tracing::info!("Training zstd dictionary from {} samples", samples.len());
+    let dictionary = zstd::dict::from_samples(&samples, MAX_DICT_SIZE)?;
+    std::fs::write(dict_path, &dictionary)?;
+    tracing::info!(
+        "Wrote zstd dictionary {dict_path} ({} bytes)",
+        dictionary.len()
+    );
+    crate::close_db_connection(conn, config);
+    Ok(())
+}
@@ -0,0 +1,3512 @@
+use sqlitedumper::DumpConfig;
+
+/// Tables of the database most tests dump.
+const TEST_DB: &str =
+    "CREATE TABLE stats (id INTEGER PRIMARY KEY, sm_timestamp INTEGER, name TEXT, value REAL);
+     INSERT INTO stats VALUES (1, 1700000000, 'a,b', 1.5);
+     INSERT INTO stats VALUES (2, 1700000060, NULL, 0.25);
+     CREATE TABLE other (k TEXT, v INTEGER);
+     INSERT INTO other VALUES ('x', 3);";
+
+/// A database in a temporary directory and the `out` directory it is dumped
+/// into.
+struct TestDb {
+    dir: tempfile::TempDir,
+    db: String,
+    out: std::path::PathBuf,
+}
+
+impl TestDb {
+    /// The `stats` and `other` tables of [`TEST_DB`].
+    fn new() -> Self {
+        Self::with_sql(TEST_DB)
+    }
+
+    /// A database with the tables created by `sql`.
+    fn with_sql(sql: &str) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("test.sqlite");
+        rusqlite::Connection::open(&db)
+            .unwrap()
+            .execute_batch(sql)
+            .unwrap();
+        let out = dir.path().join("out");
+        Self {
+            db: db.to_str().unwrap().to_string(),
+            dir,
+            out,
+        }
+    }
+
+    fn conn(&self) -> rusqlite::Connection {
+        rusqlite::Connection::open(&self.db).unwrap()
+    }
+
+    /// `name` in the temporary directory, next to the database.
+    fn path(&self, name: &str) -> std::path::PathBuf {
+        self.dir.path().join(name)
+    }
+
+    /// A builder dumping the database into `out`.
+    fn builder(&self) -> sqlitedumper::DumpConfigBuilder {
+        DumpConfig::builder()
+            .db_path(self.db.as_str())
+            .output_dir(self.out.to_str().unwrap())
+    }
+
+    /// The output file `name`.
+    fn read(&self, name: &str) -> String {
+        std::fs::read_to_string(self.out.join(name)).unwrap()
+    }
+
+    fn read_bytes(&self, name: &str) -> Vec<u8> {
+        std::fs::read(self.out.join(name)).unwrap()
+    }
+
+    /// Sorted names of the files in `out`.
+    fn files(&self) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(&self.out)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Run the binary on the database and `out` with further `args`.
+    fn run(&self, args: &[&str]) -> std::process::Output {
+        std::process::Command::new(env!("CARGO_BIN_EXE_sqlitedumper"))
+            .args(["-f", &self.db, "-d", self.out.to_str().unwrap()])
+            .args(args)
+            .output()
+            .unwrap()
+    }
+
+    /// Dump `table` with `config` and read its output file `name`.
+    async fn dump(&self, config: &DumpConfig, table: &str, name: &str) -> String {
+        sqlitedumper::dump_table(config, table).await.unwrap();
+        self.read(name)
+    }
+}
+
+/// Log events of the current thread, as JSON lines, while the guard is held.
+fn capture_logs() -> (
+    tracing::subscriber::DefaultGuard,
+    std::sync::Arc<std::sync::Mutex<Vec<u8>>>,
+) {
+    #[derive(Clone)]
+    struct Logs(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let logs = Logs(Default::default());
+    let buffer = logs.0.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .json()
+        .with_writer(move || logs.clone())
+        .finish();
+    (tracing::subscriber::set_default(subscriber), buffer)
+}
+
+#[test]
+fn get_tables_lists_user_tables() {
+    let t = TestDb::new();
+    let config = t.builder().build().unwrap();
+
+    let mut tables = sqlitedumper::get_tables(&config).unwrap();
+    tables.sort();
+    assert_eq!(tables, vec!["other", "stats"]);
+}
+
+#[test]
+fn file_argument_is_the_database_dumped() {
+    let t = TestDb::new();
+    // A database at the default path, which must not be the one read.
+    rusqlite::Connection::open(t.path("appliance_stats.sqlite"))
+        .unwrap()
+        .execute_batch("CREATE TABLE other (k TEXT); INSERT INTO other VALUES ('default');")
+        .unwrap();
+
+    for (flag, dir) in [("-f", "short"), ("--file", "long")] {
+        let status = std::process::Command::new(env!("CARGO_BIN_EXE_sqlitedumper"))
+            .current_dir(t.dir.path())
+            .args([flag, "test.sqlite", "-d", dir])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let csv = std::fs::read_to_string(t.path(dir).join("other.csv")).unwrap();
+        assert_eq!(csv, "k,v\nx,3\n");
+    }
+}
+
+#[test]
+fn text_logs_show_the_message_and_json_logs_the_fields() {
+    let t = TestDb::new();
+    let run = |format: &str| {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_sqlitedumper"))
+            .args(["-f", &t.db, "-d", t.out.to_str().unwrap(), "-l", "info"])
+            .args(["--overwrite", "--log-format", format])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stderr).unwrap()
+    };
+
+    let text = run("text");
+    let line = text
+        .lines()
+        .find(|line| line.contains("Dumping table other"))
+        .unwrap();
+    assert!(line.starts_with('['));
+    assert!(line.ends_with("INFO  sqlitedumper] Dumping table other"));
+
+    let json = run("json");
+    let event: serde_json::Value = json
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .find(|event| event["message"] == "Dumping table other")
+        .unwrap();
+    assert_eq!(event["table"], "other");
+}
+
+#[tokio::test]
+async fn dump_table_writes_csv_with_parsed_timestamp() {
+    let t = TestDb::new();
+    std::fs::create_dir(&t.out).unwrap();
+    let config = t.builder().build().unwrap();
+
+    let csv = t.dump(&config, "stats", "stats.csv").await;
+
+    assert_eq!(
+        csv,
+        "id,sm_timestamp,timestamp_parsed,name,value\n\
+         1,1700000000,2023-11-14T22:13:20Z,\"a,b\",1.5\n\
+         2,1700000060,2023-11-14T22:14:20Z,null,0.25\n"
+    );
+}
+
+#[tokio::test]
+async fn json_and_json_lines_write_one_object_per_row() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE blobs (id INTEGER, data BLOB, note TEXT, value REAL);
+         INSERT INTO blobs VALUES (1, X'4869', 'a\"b', 1.5), (2, NULL, NULL, NULL);
+         CREATE TABLE empty (id INTEGER);",
+    );
+    let rows = serde_json::json!([
+        {"id": 1, "data": "SGk=", "note": "a\"b", "value": 1.5},
+        {"id": 2, "data": null, "note": null, "value": null},
+    ]);
+    let config = t
+        .builder()
+        .output_format(sqlitedumper::OutputFormat::Json)
+        .build()
+        .unwrap();
+    let json = t.dump(&config, "blobs", "blobs.json").await;
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+        rows
+    );
+    assert_eq!(t.dump(&config, "empty", "empty.json").await, "[]\n");
+
+    let mut config = config;
+    config.output_format = sqlitedumper::OutputFormat::Jsonl;
+    let jsonl = t.dump(&config, "blobs", "blobs.jsonl").await;
+    let lines: Vec<serde_json::Value> = jsonl
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(serde_json::Value::Array(lines), rows);
+
+    // An explicit BLOB format replaces the base64 default.
+    config.overwrite = true;
+    config.blob_format = Some(sqlitedumper::BlobFormat::Hex);
+    let jsonl = t.dump(&config, "blobs", "blobs.jsonl").await;
+    let first: serde_json::Value = serde_json::from_str(jsonl.lines().next().unwrap()).unwrap();
+    assert_eq!(first["data"], "4869");
+}
+
+#[test]
+fn builder_rejects_unsafe_where_clause() {
+    let result = DumpConfig::builder()
+        .db_path("test.sqlite")
+        .where_clause(Some("1=1; DROP TABLE stats".to_string()))
+        .build();
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::UnsafeSqlFragment(_))
+    ));
+}
+
+#[tokio::test]
+async fn dump_table_compresses_output() {
+    let t = TestDb::new();
+    let config = t
+        .builder()
+        .compression(sqlitedumper::Compression::Zstd)
+        .build()
+        .unwrap();
+
+    sqlitedumper::dump_table(&config, "other").await.unwrap();
+
+    let csv = zstd::decode_all(t.read_bytes("other.csv.zst").as_slice()).unwrap();
+    assert_eq!(csv, b"k,v\nx,3\n");
+}
+
+#[tokio::test]
+async fn dump_table_parses_iso_8601_text_timestamps() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE events (timestamp TEXT);
+         INSERT INTO events VALUES ('2024-01-15T21:00:00+09:00');
+         INSERT INTO events VALUES ('2024-01-15 12:30:00');
+         INSERT INTO events VALUES ('not a date');",
+    );
+    let config = t.builder().build().unwrap();
+
+    let csv = t.dump(&config, "events", "events.csv").await;
+
+    assert_eq!(
+        csv,
+        "timestamp,timestamp_parsed\n\
+         2024-01-15T21:00:00+09:00,2024-01-15T12:00:00Z\n\
+         2024-01-15 12:30:00,2024-01-15T12:30:00Z\n\
+         not a date,\n"
+    );
+}
+
+#[tokio::test]
+async fn timestamps_that_cannot_be_parsed_are_empty() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE events (id INTEGER, timestamp);
+         INSERT INTO events VALUES (1, NULL), (2, 1700000000.5), (3, X'00'), (4, 9223372036854775807);
+         CREATE TABLE other (k TEXT);
+         INSERT INTO other VALUES ('x');",
+    );
+    let config = t.builder().build().unwrap();
+
+    let stats = sqlitedumper::dump_database(&config).await.unwrap();
+
+    assert!(stats.tables.iter().all(|table| table.error.is_none()));
+    assert_eq!(
+        t.read("events.csv"),
+        "id,timestamp,timestamp_parsed\n\
+         1,null,\n\
+         2,1700000000.5,2023-11-14T22:13:20Z\n\
+         3,00,\n\
+         4,9223372036854775807,\n"
+    );
+    assert_eq!(t.read("other.csv"), "k\nx\n");
+}
+
+#[tokio::test]
+async fn table_and_column_names_are_quoted() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE \"it's\" (\"a \"\"b\"\"\" TEXT, c INTEGER);
+         INSERT INTO \"it's\" VALUES ('x', 1);",
+    );
+    let table = "it's".to_string();
+    let config = t
+        .builder()
+        .columns(std::collections::HashMap::from([(
+            table.clone(),
+            vec!["c".to_string(), "a \"b\"".to_string()],
+        )]))
+        .column_order(std::collections::HashMap::from([(
+            table.clone(),
+            vec!["a \"b\"".to_string()],
+        )]))
+        .include_rowid(true)
+        .build()
+        .unwrap();
+
+    let stats = sqlitedumper::dump_database(&config).await.unwrap();
+
+    assert!(stats.tables.iter().all(|table| table.error.is_none()));
+    assert_eq!(t.read("it's.csv"), "\"a \"\"b\"\"\",rowid,c\nx,1,1\n");
+}
+
+#[test]
+fn count_tables_counts_tables_with_quotes_in_their_names() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE \"it's\" (v INTEGER);
+         INSERT INTO \"it's\" VALUES (1), (2);
+         CREATE TABLE other (v INTEGER);",
+    );
+    let config = t.builder().build().unwrap();
+
+    let mut counts = sqlitedumper::count_tables(&config).unwrap();
+    counts.sort();
+    assert_eq!(counts, [("it's".to_string(), 2), ("other".to_string(), 0)]);
+    let plans = sqlitedumper::plan_dump(&config).unwrap();
+    let plan = plans.iter().find(|plan| plan.table == "it's").unwrap();
+    assert_eq!(plan.row_count, 2);
+}
+
+#[tokio::test]
+async fn table_names_cannot_leave_the_output_directory() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE \"../x\" (\"..\\c\" BLOB);
+         INSERT INTO \"../x\" VALUES (X'00');",
+    );
+    let blobs = t.out.join("blobs");
+    let config = t
+        .builder()
+        .blob_dir(Some(blobs.to_str().unwrap().to_string()))
+        .build()
+        .unwrap();
+
+    sqlitedumper::dump_database(&config).await.unwrap();
+    let mut config = config;
+    config.schema_only = true;
+    sqlitedumper::dump_database(&config).await.unwrap();
+
+    assert_eq!(t.read("___x.csv"), "..\\c\nblobs/___x/___c/1.bin\n");
+    assert!(t.read("___x.sql").starts_with("CREATE TABLE"));
+    let mut names: Vec<_> = std::fs::read_dir(t.dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["out", "test.sqlite"]);
+}
+
+#[tokio::test]
+async fn dump_database_writes_output_manifest() {
+    use sha2::Digest;
+
+    let t = TestDb::new();
+    let manifest = t.path("manifest.json");
+    let config = t
+        .builder()
+        .output_manifest(Some(manifest.to_str().unwrap().to_string()))
+        .build()
+        .unwrap();
+
+    sqlitedumper::dump_database(&config).await.unwrap();
+
+    let entries: Vec<sqlitedumper::ManifestEntry> =
+        serde_json::from_slice(&std::fs::read(&manifest).unwrap()).unwrap();
+    let entry = entries.iter().find(|e| e.table == "stats").unwrap();
+    assert_eq!(entry.path, "out/stats.csv");
+    let contents = std::fs::read(t.path(&entry.path)).unwrap();
+    assert_eq!(entry.rows, 2);
+    assert_eq!(entry.bytes, contents.len() as u64);
+    assert_eq!(entry.sha256, hex::encode(sha2::Sha256::digest(&contents)));
+    assert_eq!(entries.len(), 2);
+}
+
+#[tokio::test]
+async fn verify_manifest_checks_files_and_rows_of_unfiltered_tables() {
+    let t = TestDb::new();
+    let manifest = t.path("manifest.json");
+    let manifest = manifest.to_str().unwrap();
+    let config = t
+        .builder()
+        .output_manifest(Some(manifest.to_string()))
+        .build()
+        .unwrap();
+    sqlitedumper::dump_database(&config).await.unwrap();
+
+    let tables = sqlitedumper::verify_manifest(&config, manifest).unwrap();
+    assert!(tables.iter().all(|table| table.passed() && !table.filtered));
+    assert_eq!(tables[0].actual_rows, Some(2));
+
+    t.conn()
+        .execute("INSERT INTO stats (name) VALUES ('new')", [])
+        .unwrap();
+    std::fs::write(t.out.join("other.csv"), "changed").unwrap();
+    let tables = sqlitedumper::verify_manifest(&config, manifest).unwrap();
+    let stats = tables.iter().find(|table| table.table == "stats").unwrap();
+    assert_eq!(stats.failures, ["manifest has 2 rows, database has 3"]);
+    let other = tables.iter().find(|table| table.table == "other").unwrap();
+    assert!(other.failures[0].ends_with("bytes, file has 7"));
+
+    // A limited dump has fewer rows than the table, only its files are checked.
+    let mut config = t.builder().limit(Some(1)).build().unwrap();
+    config.output_manifest = Some(manifest.to_string());
+    config.overwrite = true;
+    sqlitedumper::dump_database(&config).await.unwrap();
+    let tables = sqlitedumper::verify_manifest(&config, manifest).unwrap();
+    let stats = tables.iter().find(|table| table.table == "stats").unwrap();
+    assert!(stats.passed() && stats.filtered);
+    assert_eq!((stats.expected_rows, stats.actual_rows), (1, None));
+}
+
+#[tokio::test]
+async fn dump_database_writes_delta_against_compare_db() {
+    let t = TestDb::new();
+    let old = t.path("old.sqlite");
+    std::fs::copy(&t.db, &old).unwrap();
+    let conn = rusqlite::Connection::open(&old).unwrap();
+    conn.execute_batch(
+        "UPDATE stats SET name = 'old' WHERE id = 1;
+         DELETE FROM other;
+         CREATE TABLE only_old (x);",
+    )
+    .unwrap();
+    drop(conn);
+    let config = t
+        .builder()
+        .compare_db(Some(old.to_str().unwrap().to_string()))
+        .build()
+        .unwrap();
+
+    let stats = sqlitedumper::dump_database(&config).await.unwrap();
+
+    assert!(
+        t.read("stats_added.csv")
+            .ends_with("\n1,1700000000,2023-11-14T22:13:20Z,\"a,b\",1.5\n")
+    );
+    assert!(
+        t.read("stats_deleted.csv")
+            .ends_with("\n1,1700000000,2023-11-14T22:13:20Z,old,1.5\n")
+    );
+    assert_eq!(t.read("other_added.csv"), "k,v\nx,3\n");
+    assert_eq!(t.read("other_deleted.csv"), "k,v\n");
+    assert_eq!(stats.unmatched_tables.len(), 1);
+    assert_eq!(stats.unmatched_tables[0].table, "only_old");
+}
+
+#[tokio::test]
+async fn dump_database_writes_multi_line_triggers() {
+    let t = TestDb::new();
+    let trigger = "CREATE TRIGGER other_log AFTER UPDATE OF v ON other\nBEGIN\n  INSERT INTO stats (name, value) VALUES ('v, \"changed\"', NEW.v);\nEND";
+    t.conn().execute_batch(&format!("{trigger};")).unwrap();
+    let config = t.builder().include_triggers(true).build().unwrap();
+
+    sqlitedumper::dump_database(&config).await.unwrap();
+
+    let mut reader = csv::Reader::from_path(t.out.join("_triggers.csv")).unwrap();
+    let records: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+    assert_eq!(records.len(), 1);
+    assert_eq!(
+        records[0].iter().collect::<Vec<_>>(),
+        ["other_log", "other", "AFTER", "UPDATE", trigger]
+    );
+}
+
+#[tokio::test]
+async fn dump_table_writes_parquet_with_inferred_types() {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::RowAccessor;
+
+    let t = TestDb::new();
+    let config = t
+        .builder()
+        .output_format(sqlitedumper::OutputFormat::Parquet)
+        .parquet_row_group_size(1)
+        .build()
+        .unwrap();
+
+    sqlitedumper::dump_table(&config, "stats").await.unwrap();
+
+    let file = std::fs::File::open(t.out.join("stats.parquet")).unwrap();
+    let reader = SerializedFileReader::new(file).unwrap();
+    let metadata = reader.metadata();
+    assert_eq!(metadata.num_row_groups(), 2);
+    let schema = metadata.file_metadata().schema_descr();
+    let types: Vec<_> = schema
+        .columns()
+        .iter()
+        .map(|column| (column.name().to_string(), column.physical_type()))
+        .collect();
+    use parquet::basic::Type;
+    assert_eq!(
+        types,
+        [
+            ("id".to_string(), Type::INT64),
+            ("sm_timestamp".to_string(), Type::INT64),
+            ("timestamp_parsed".to_string(), Type::BYTE_ARRAY),
+            ("name".to_string(), Type::BYTE_ARRAY),
+            ("value".to_string(), Type::DOUBLE),
+        ]
+    );
+    let rows: Vec<_> = reader
+        .get_row_iter(None)
+        .unwrap()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].get_string(3).unwrap(), "a,b");
+    assert_eq!(rows[1].get_long(0).unwrap(), 2);
+    assert!(rows[1].get_string(3).is_err());
+    assert_eq!(rows[1].get_double(4).unwrap(), 0.25);
+}
+
+#[tokio::test]
+async fn parquet_infers_types_around_nulls_and_mixed_values() {
+    use parquet::basic::Type;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::RowAccessor;
+
+    let t = TestDb::with_sql(
+        "CREATE TABLE mixed (late_int, mixed, numbers, empty);
+         INSERT INTO mixed VALUES (NULL, 1, 1, NULL), (2, 'x', 2.5, NULL);",
+    );
+    let config = t
+        .builder()
+        .output_format(sqlitedumper::OutputFormat::Parquet)
+        .build()
+        .unwrap();
+
+    sqlitedumper::dump_table(&config, "mixed").await.unwrap();
+
+    let file = std::fs::File::open(t.out.join("mixed.parquet")).unwrap();
+    let reader = SerializedFileReader::new(file).unwrap();
+    let types: Vec<_> = reader
+        .metadata()
+        .file_metadata()
+        .schema_descr()
+        .columns()
+        .iter()
+        .map(|column| column.physical_type())
+        .collect();
+    // A leading NULL does not decide the type, integers and reals give
+    // doubles, text or no value at all gives strings.
+    assert_eq!(
+        types,
+        [
+            Type::INT64,
+            Type::BYTE_ARRAY,
+            Type::DOUBLE,
+            Type::BYTE_ARRAY
+        ]
+    );
+    let rows: Vec<_> = reader
+        .get_row_iter(None)
+        .unwrap()
+        .map(Result::unwrap)
+        .collect();
+    assert!(rows[0].get_long(0).is_err());
+    assert_eq!(rows[1].get_long(0).unwrap(), 2);
+    assert_eq!(rows[0].get_string(1).unwrap(), "1");
+    assert_eq!(rows[0].get_double(2).unwrap(), 1.0);
+    assert!(rows[1].get_string(3).is_err());
+}
+
+#[tokio::test]
+async fn dump_table_writes_arrow_with_timestamps() {
+    use arrow::array::{Array, AsArray};
+    use arrow::datatypes::{DataType, TimeUnit, TimestampSecondType};
+
+    let t = TestDb::new();
+    let config = t
+        .builder()
+        .output_format(sqlitedumper::OutputFormat::Arrow)
+        .build()
+        .unwrap();
+
+    sqlitedumper::dump_table(&config, "stats").await.unwrap();
+
+    let file = std::fs::File::open(t.out.join("stats.arrow")).unwrap();
+    let reader = arrow::ipc::reader::StreamReader::try_new(file, None).unwrap();
+    let schema = reader.schema();
+    assert_eq!(
+        schema
+            .field_with_name("timestamp_parsed")
+            .unwrap()
+            .data_type(),
+        &DataType::Timestamp(TimeUnit::Second, Some("UTC".into()))
+    );
+    let name = schema.field_with_name("name").unwrap();
+    assert_eq!(name.data_type(), &DataType::Utf8);
+    assert!(name.is_nullable());
+    let batches: Vec<_> = reader.map(Result::unwrap).collect();
+    assert_eq!(batches.len(), 1);
+    let parsed = batches[0]
+        .column_by_name("timestamp_parsed")
+        .unwrap()
+        .as_primitive::<TimestampSecondType>();
+    assert_eq!(parsed.values(), &[1700000000, 1700000060]);
+    assert!(batches[0].column_by_name("name").unwrap().is_null(1));
+}
+
+#[tokio::test]
+async fn dump_table_writes_sql_that_sqlite_can_load() {
+    let t = TestDb::new();
+    let config = t
+        .builder()
+        .output_format(sqlitedumper::OutputFormat::Sql)
+        .build()
+        .unwrap();
+
+    let sql = t.dump(&config, "stats", "stats.sql").await;
+
+    assert_eq!(
+        sql,
+        "BEGIN TRANSACTION;\n\
+         INSERT INTO \"stats\" (\"id\", \"sm_timestamp\", \"timestamp_parsed\", \"name\", \"value\") \
+         VALUES (1, 1700000000, '2023-11-14T22:13:20Z', 'a,b', 1.5);\n\
+         INSERT INTO \"stats\" (\"id\", \"sm_timestamp\", \"timestamp_parsed\", \"name\", \"value\") \
+         VALUES (2, 1700000060, '2023-11-14T22:14:20Z', NULL, 0.25);\n\
+         COMMIT;\n"
+    );
+    let conn = rusqlite::Connection::open_in_memory().unwrap();
+    conn.execute_batch("CREATE TABLE stats (id, sm_timestamp, timestamp_parsed, name, value);")
+        .unwrap();
+    conn.execute_batch(&sql).unwrap();
+    let count: i64 = conn
+        .query_row("SELECT count(*) FROM stats WHERE name IS NULL", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(count, 1);
+}
+
+#[tokio::test]
+async fn sql_dialects_escape_strings_and_blobs() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE notes (text TEXT, data BLOB);
+         INSERT INTO notes VALUES ('it''s C:\\tmp', X'00FF');",
+    );
+    let config = t
+        .builder()
+        .output_format(sqlitedumper::OutputFormat::Sql)
+        .sql_dialect(sqlitedumper::SqlDialect::Mysql)
+        .build()
+        .unwrap();
+    let sql = t.dump(&config, "notes", "notes.sql").await;
+    assert!(
+        sql.contains("INSERT INTO `notes` (`text`, `data`) VALUES ('it''s C:\\\\tmp', X'00ff');")
+    );
+
+    let mut config = config;
+    config.overwrite = true;
+    config.sql_dialect = sqlitedumper::SqlDialect::Postgresql;
+    let sql = t.dump(&config, "notes", "notes.sql").await;
+    assert!(sql.contains("VALUES ('it''s C:\\tmp', '\\x00ff'::bytea);"));
+}
+
+#[tokio::test]
+async fn dump_table_writes_markdown_with_alignment() {
+    let t = TestDb::new();
+    t.conn().execute("UPDATE other SET k = 'x|y'", []).unwrap();
+    let config = t
+        .builder()
+        .output_format(sqlitedumper::OutputFormat::Markdown)
+        .markdown_align(
+            [("v".to_string(), sqlitedumper::MarkdownAlign::Right)]
+                .into_iter()
+                .collect(),
+        )
+        .build()
+        .unwrap();
+
+    let markdown = t.dump(&config, "other", "other.md").await;
+    assert_eq!(markdown, "| k | v |\n|---|---:|\n| x\\|y | 3 |\n");
+
+    let mut config = config;
+    config.overwrite = true;
+    config.max_cell_size = Some(1);
+    let markdown = t.dump(&config, "other", "other.md").await;
+    assert_eq!(markdown, "| k | v |\n|---|---:|\n| x… | 3 |\n");
+}
+
+#[tokio::test]
+async fn dump_table_writes_html_through_a_template() {
+    let t = TestDb::new();
+    t.conn().execute("UPDATE other SET k = '<b>'", []).unwrap();
+    let template = t.path("page.html");
+    std::fs::write(
+        &template,
+        "<h1>{{ table }}</h1>{% for row in rows %}<p class=\"{{ class }}\">{{ row[0] }}={{ row[1] }}</p>{% endfor %}",
+    )
+    .unwrap();
+    let builder = || {
+        t.builder()
+            .output_format(sqlitedumper::OutputFormat::Html)
+            .html_class(Some("data".to_string()))
+            .overwrite(true)
+    };
+
+    let html = t
+        .dump(&builder().build().unwrap(), "other", "other.html")
+        .await;
+    assert!(html.contains("<table class=\"data\">\n<thead>\n<tr><th>k</th><th>v</th></tr>"));
+    assert!(html.contains("<tr><td>&lt;b&gt;</td><td>3</td></tr>\n</tbody>"));
+
+    let config = builder()
+        .html_template(Some(template.to_str().unwrap().to_string()))
+        .build()
+        .unwrap();
+    let html = t.dump(&config, "other", "other.html").await;
+    assert_eq!(html, "<h1>other</h1><p class=\"data\">&lt;b&gt;=3</p>");
+
+    std::fs::write(&template, "{% for row in rows %}").unwrap();
+    let result = builder()
+        .html_template(Some(template.to_str().unwrap().to_string()))
+        .build();
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::InvalidTemplate { .. })
+    ));
+}
+
+#[test]
+fn config_file_args_are_replaced_by_the_command_line() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("dump.toml");
+    std::fs::write(
+        &path,
+        "[defaults]\nformat = \"jsonl\"\nno-header = true\nlimit = 5\n\n\
+         [table.events]\nwhere = \"id > 1\"\ncolumns = [\"id\", \"name\"]\n",
+    )
+    .unwrap();
+    let command = clap::Command::new("test").args([
+        clap::Arg::new("format").long("format"),
+        clap::Arg::new("no_header")
+            .long("no-header")
+            .action(clap::ArgAction::SetTrue),
+        clap::Arg::new("limit").long("limit"),
+        clap::Arg::new("table_where")
+            .long("table-where")
+            .action(clap::ArgAction::Append),
+        clap::Arg::new("columns")
+            .long("columns")
+            .action(clap::ArgAction::Append),
+    ]);
+
+    let config_file = sqlitedumper::read_config_file(path.to_str().unwrap()).unwrap();
+    let args = config_file.args(&command, |id| id == "limit").unwrap();
+
+    assert_eq!(
+        args,
+        [
+            "--format=jsonl",
+            "--no-header",
+            "--columns=events:id,name",
+            "--table-where=events=id > 1",
+        ]
+    );
+}
+
+#[test]
+fn config_file_rejects_unknown_sections_and_keys() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("dump.toml");
+    let command = clap::Command::new("test").arg(clap::Arg::new("format").long("format"));
+
+    std::fs::write(&path, "[default]\nformat = \"jsonl\"\n").unwrap();
+    assert!(matches!(
+        sqlitedumper::read_config_file(path.to_str().unwrap()),
+        Err(sqlitedumper::DumperError::InvalidConfigFile { .. })
+    ));
+
+    std::fs::write(&path, "[defaults]\nformt = \"jsonl\"\n").unwrap();
+    let config_file = sqlitedumper::read_config_file(path.to_str().unwrap()).unwrap();
+    assert!(matches!(
+        config_file.args(&command, |_| false),
+        Err(sqlitedumper::DumperError::InvalidConfigFile { .. })
+    ));
+}
+
+#[test]
+fn config_file_profile_overrides_defaults() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("dump.toml");
+    std::fs::write(
+        &path,
+        "[defaults]\nformat = \"jsonl\"\nlimit = 5\n\n[profile.staging]\nlimit = 10\n",
+    )
+    .unwrap();
+    let command = clap::Command::new("test").args([
+        clap::Arg::new("format").long("format"),
+        clap::Arg::new("limit").long("limit"),
+    ]);
+
+    let mut config_file = sqlitedumper::read_config_file(path.to_str().unwrap()).unwrap();
+    assert!(matches!(
+        config_file.select_profile("production"),
+        Err(sqlitedumper::DumperError::InvalidConfigFile { .. })
+    ));
+    config_file.select_profile("staging").unwrap();
+
+    assert_eq!(
+        config_file.args(&command, |_| false).unwrap(),
+        ["--format=jsonl", "--limit=10"]
+    );
+}
+
+#[tokio::test]
+async fn dump_database_reports_table_metrics() {
+    let t = TestDb::new();
+    let config = t.builder().build().unwrap();
+
+    let stats = sqlitedumper::dump_database(&config).await.unwrap();
+    let metrics_path = t.path("metrics.json");
+    stats.write_metrics(metrics_path.to_str().unwrap()).unwrap();
+
+    let metrics: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(metrics_path).unwrap()).unwrap();
+    let table = &metrics["stats"];
+    assert_eq!(table["rows"], 2);
+    assert_eq!(
+        table["bytes"],
+        std::fs::metadata(t.out.join("stats.csv")).unwrap().len()
+    );
+    assert!(table["throughput_rows_per_sec"].as_f64().unwrap() > 0.0);
+    assert!(table["throughput_mb_per_sec"].as_f64().unwrap() > 0.0);
+}
+
+#[tokio::test]
+async fn cancelled_dump_fails_tables_without_output() {
+    let t = TestDb::new();
+    let cancel = tokio_util::sync::CancellationToken::new();
+    let config = t.builder().cancel(cancel.clone()).build().unwrap();
+    cancel.cancel();
+
+    let stats = sqlitedumper::dump_database(&config).await.unwrap();
+
+    assert_eq!(stats.tables.len(), 2);
+    for table in &stats.tables {
+        assert_eq!(table.error.as_deref(), Some("Dump cancelled"));
+    }
+    assert_eq!(std::fs::read_dir(&t.out).unwrap().count(), 0);
+}
+
+#[tokio::test]
+async fn failed_table_keeps_partial_file_in_manifest() {
+    let t = TestDb::new();
+    let conn = t.conn();
+    conn.execute("INSERT INTO other VALUES (X'FF', 4)", [])
+        .unwrap();
+    conn.execute("UPDATE other SET k = CAST(X'FF' AS TEXT) WHERE v = 4", [])
+        .unwrap();
+    let manifest = t.path("manifest.json");
+    let builder = || {
+        t.builder()
+            .tables_filter(sqlitedumper::TableFilter {
+                include: vec!["other".to_string()],
+                ..Default::default()
+            })
+            .output_manifest(Some(manifest.to_str().unwrap().to_string()))
+    };
+
+    let stats = sqlitedumper::dump_database(&builder().build().unwrap())
+        .await
+        .unwrap();
+    assert!(stats.tables[0].error.is_some());
+    assert!(!t.out.join("other.csv").exists());
+
+    let config = builder().keep_partial(true).build().unwrap();
+    sqlitedumper::dump_database(&config).await.unwrap();
+    assert!(t.out.join("other.csv.partial").exists());
+    let entries: Vec<sqlitedumper::ManifestEntry> =
+        serde_json::from_str(&std::fs::read_to_string(&manifest).unwrap()).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert!(!entries[0].complete);
+    assert!(entries[0].path.ends_with("other.csv.partial"));
+    assert!(entries[0].error.is_some());
+}
+
+#[tokio::test]
+async fn max_errors_cancels_the_remaining_tables() {
+    let t = TestDb::new();
+    t.conn()
+        .execute("UPDATE other SET k = CAST(X'FF' AS TEXT)", [])
+        .unwrap();
+    let config = t
+        .builder()
+        .tables_filter(sqlitedumper::TableFilter {
+            include: vec!["other".to_string(), "stats".to_string()],
+            ..Default::default()
+        })
+        .sequential(true)
+        .max_errors(Some(1))
+        .build()
+        .unwrap();
+
+    let stats = sqlitedumper::dump_database(&config).await.unwrap();
+
+    assert!(stats.tables[0].error.is_some());
+    assert_eq!(stats.tables[1].error.as_deref(), Some("Dump cancelled"));
+    assert!(!config.cancel.is_cancelled());
+
+    let mut config = config;
+    config.max_errors = Some(2);
+    config.overwrite = true;
+    let stats = sqlitedumper::dump_database(&config).await.unwrap();
+    assert!(stats.tables[0].error.is_some());
+    assert!(stats.tables[1].error.is_none());
+}
+
+#[tokio::test]
+async fn busy_table_is_retried() {
+    let t = TestDb::new();
+    let conn = t.conn();
+    conn.execute_batch("BEGIN EXCLUSIVE").unwrap();
+    let unlock = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        conn.execute_batch("COMMIT").unwrap();
+    });
+    let config = t
+        .builder()
+        .pragmas(vec![("busy_timeout".to_string(), "0".to_string())])
+        .retry(20)
+        .retry_delay(std::time::Duration::from_millis(50))
+        .build()
+        .unwrap();
+
+    sqlitedumper::dump_table(&config, "other").await.unwrap();
+    unlock.join().unwrap();
+    assert!(t.out.join("other.csv").exists());
+}
+
+#[tokio::test]
+async fn busy_table_fails_once_the_retries_run_out() {
+    let t = TestDb::new();
+    let conn = t.conn();
+    conn.execute_batch("BEGIN EXCLUSIVE").unwrap();
+    let config = t
+        .builder()
+        .pragmas(vec![("busy_timeout".to_string(), "0".to_string())])
+        .retry(2)
+        .retry_delay(std::time::Duration::from_millis(10))
+        .build()
+        .unwrap();
+
+    let err = sqlitedumper::dump_table(&config, "other")
+        .await
+        .unwrap_err();
+
+    let sqlitedumper::DumperError::Sqlite(source) = err else {
+        panic!("not a SQLite error: {err}");
+    };
+    assert_eq!(
+        source.sqlite_error_code(),
+        Some(rusqlite::ErrorCode::DatabaseBusy)
+    );
+    assert!(!t.out.join("other.csv").exists());
+    conn.execute_batch("COMMIT").unwrap();
+}
+
+#[test]
+fn sqlite_compile_options_lists_the_build_options() {
+    let options = sqlitedumper::sqlite_compile_options().unwrap();
+    assert!(
+        options
+            .iter()
+            .any(|option| option.starts_with("THREADSAFE="))
+    );
+}
+
+#[tokio::test]
+async fn vacuum_first_dumps_a_compacted_copy() {
+    let t = TestDb::new();
+    let config = t.builder().vacuum_first(true).build().unwrap();
+
+    let stats = sqlitedumper::dump_database(&config).await.unwrap();
+
+    assert!(stats.tables.iter().all(|table| table.error.is_none()));
+    assert!(t.read("other.csv").starts_with("k,v\n"));
+}
+
+#[tokio::test]
+async fn backup_is_dumped_and_removed_unless_kept() {
+    let t = TestDb::new();
+    let backup = t.path("backup.sqlite");
+    let config = t
+        .builder()
+        .backup(Some(backup.to_str().unwrap().to_string()))
+        .build()
+        .unwrap();
+
+    let stats = sqlitedumper::dump_database(&config).await.unwrap();
+    assert!(stats.tables.iter().all(|table| table.error.is_none()));
+    assert!(t.out.join("stats.csv").exists());
+    assert!(!backup.exists());
+
+    let mut config = config;
+    config.keep_backup = true;
+    config.overwrite = true;
+    sqlitedumper::dump_database(&config).await.unwrap();
+    let conn = rusqlite::Connection::open(&backup).unwrap();
+    let count: i64 = conn
+        .query_row("SELECT COUNT(*) FROM other", [], |row| row.get(0))
+        .unwrap();
+    assert!(count > 0);
+}
+
+#[tokio::test]
+async fn backup_does_not_replace_an_existing_file() {
+    let t = TestDb::new();
+    let backup = t.path("backup.sqlite");
+    std::fs::write(&backup, "keep me").unwrap();
+    let config = t
+        .builder()
+        .backup(Some(backup.to_str().unwrap().to_string()))
+        .build()
+        .unwrap();
+
+    let err = sqlitedumper::dump_database(&config).await.unwrap_err();
+
+    assert!(matches!(err, sqlitedumper::DumperError::OutputExists(_)));
+    assert_eq!(std::fs::read_to_string(&backup).unwrap(), "keep me");
+    let result = t
+        .builder()
+        .backup(Some(backup.to_str().unwrap().to_string()))
+        .vacuum_first(true)
+        .build();
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::InvalidConfig(_))
+    ));
+}
+
+#[tokio::test]
+async fn pivot_writes_one_column_per_key() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE readings (timestamp INTEGER, sensor TEXT, value REAL);
+         INSERT INTO readings VALUES (2, 'b', 4.5), (1, 'a', 1.5), (1, 'b', 2.5), (2, 'a''s', 3.5);",
+    );
+    let pivot = |key_column: &str| {
+        std::collections::HashMap::from([(
+            "readings".to_string(),
+            sqlitedumper::Pivot {
+                key_column: key_column.to_string(),
+                value_column: "value".to_string(),
+            },
+        )])
+    };
+    let config = t.builder().pivot(pivot("sensor")).build().unwrap();
+
+    let csv = t.dump(&config, "readings", "readings.csv").await;
+
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "timestamp,timestamp_parsed,a,a's,b",
+            "1,1970-01-01T00:00:01Z,1.5,null,2.5",
+            "2,1970-01-01T00:00:02Z,null,3.5,4.5",
+        ]
+    );
+
+    let mut config = config;
+    config.overwrite = true;
+    config.pivot = pivot("sensr");
+    let err = sqlitedumper::dump_table(&config, "readings")
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        sqlitedumper::DumperError::ColumnNotFound { .. }
+    ));
+}
+
+#[tokio::test]
+async fn pivot_names_keys_apart_and_warns_of_null_keys() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE readings (timestamp INTEGER, sensor, value REAL);
+         INSERT INTO readings VALUES (1, 1, 1.5), (1, '1', 2.5), (1, 1.5, 3.5), (2, NULL, 4.5);",
+    );
+    let config = t
+        .builder()
+        .pivot(std::collections::HashMap::from([(
+            "readings".to_string(),
+            sqlitedumper::Pivot {
+                key_column: "sensor".to_string(),
+                value_column: "value".to_string(),
+            },
+        )]))
+        .build()
+        .unwrap();
+
+    let (_guard, logs) = capture_logs();
+    let csv = t.dump(&config, "readings", "readings.csv").await;
+    assert_eq!(
+        csv.lines().collect::<Vec<_>>(),
+        [
+            "timestamp,timestamp_parsed,1,1.5,1_2",
+            "1,1970-01-01T00:00:01Z,1.5,3.5,2.5",
+            "2,1970-01-01T00:00:02Z,null,null,null",
+        ]
+    );
+    let logs = String::from_utf8(logs.lock().unwrap().clone()).unwrap();
+    assert!(logs.contains("dropping the values of 1 rows without a key"));
+    assert!(logs.contains("key '1' is written to column 1_2"));
+}
+
+#[tokio::test]
+async fn pivot_schema_and_dictionary_quote_table_names() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE \"it's\" (timestamp INTEGER, sensor TEXT, value REAL);
+         WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 1000)
+         INSERT INTO \"it's\" SELECT i, 'sensor ' || (i % 3), i * 1.5 FROM n;",
+    );
+    let config = t
+        .builder()
+        .pivot(std::collections::HashMap::from([(
+            "it's".to_string(),
+            sqlitedumper::Pivot {
+                key_column: "sensor".to_string(),
+                value_column: "value".to_string(),
+            },
+        )]))
+        .limit(Some(2))
+        .build()
+        .unwrap();
+
+    let csv = t.dump(&config, "it's", "it's.csv").await;
+    assert_eq!(
+        csv.lines().next(),
+        Some("timestamp,timestamp_parsed,sensor 0,sensor 1,sensor 2")
+    );
+
+    let info = sqlitedumper::table_info(&config).unwrap();
+    let names: Vec<&str> = info[0]
+        .columns
+        .iter()
+        .map(|column| column.name.as_str())
+        .collect();
+    assert_eq!(names, ["timestamp", "sensor", "value"]);
+    assert!(info[0].columns[0].timestamp);
+
+    let dict = t.path("dict");
+    let mut config = config;
+    config.limit = None;
+    sqlitedumper::train_zstd_dict(&config, dict.to_str().unwrap()).unwrap();
+    assert!(std::fs::metadata(&dict).unwrap().len() > 0);
+}
+
+#[tokio::test]
+async fn normalize_floats_replaces_infinities() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE floats (value REAL);
+         INSERT INTO floats VALUES (1.5), (9e999), (-9e999);",
+    );
+    let config = t.builder().build().unwrap();
+    let csv = t.dump(&config, "floats", "floats.csv").await;
+    assert_eq!(csv, "value\n1.5\ninf\n-inf\n");
+
+    let mut config = config;
+    config.normalize_floats = true;
+    config.overwrite = true;
+    let csv = t.dump(&config, "floats", "floats.csv").await;
+    assert_eq!(csv, "value\n1.5\nInf\n-Inf\n");
+
+    config.inf_value = "Infinity".to_string();
+    let csv = t.dump(&config, "floats", "floats.csv").await;
+    assert_eq!(csv, "value\n1.5\nInfinity\n-Infinity\n");
+}
+
+#[tokio::test]
+async fn bool_columns_are_written_as_true_and_false() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE flags (id INTEGER, enabled INTEGER, label TEXT);
+         INSERT INTO flags VALUES (0, 1, 'true'), (1, 0, 'FALSE'), (2, NULL, NULL);",
+    );
+    let config = t
+        .builder()
+        .bool_columns(std::collections::HashMap::from([(
+            "flags".to_string(),
+            vec!["enabled".to_string()],
+        )]))
+        .build()
+        .unwrap();
+    let csv = t.dump(&config, "flags", "flags.csv").await;
+    assert_eq!(
+        csv,
+        "id,enabled,label\n0,true,true\n1,false,FALSE\n2,null,null\n"
+    );
+
+    let mut config = config;
+    config.overwrite = true;
+    config.bool_as_int = true;
+    config.bool_columns =
+        std::collections::HashMap::from([("flags".to_string(), vec!["label".to_string()])]);
+    let csv = t.dump(&config, "flags", "flags.csv").await;
+    assert_eq!(csv, "id,enabled,label\n0,1,1\n1,0,0\n2,null,null\n");
+
+    config.bool_columns =
+        std::collections::HashMap::from([("flags".to_string(), vec!["missing".to_string()])]);
+    let err = sqlitedumper::dump_table(&config, "flags")
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        sqlitedumper::DumperError::ColumnNotFound { .. }
+    ));
+}
+
+#[tokio::test]
+async fn csv_bom_starts_the_file() {
+    let t = TestDb::new();
+    let config = t.builder().csv_bom(true).build().unwrap();
+
+    sqlitedumper::dump_table(&config, "other").await.unwrap();
+
+    let bytes = t.read_bytes("other.csv");
+    assert!(bytes.starts_with(b"\xEF\xBB\xBFk,v\n"));
+    // A reader that strips the BOM sees the first header unchanged.
+    let text = String::from_utf8(bytes).unwrap();
+    let mut reader = csv::Reader::from_reader(text.trim_start_matches('\u{feff}').as_bytes());
+    assert_eq!(reader.headers().unwrap().get(0), Some("k"));
+
+    // Rows appended without a header do not get a second BOM.
+    let mut config = config;
+    config.append = true;
+    config.no_header = true;
+    sqlitedumper::dump_table(&config, "other").await.unwrap();
+    assert_eq!(t.read_bytes("other.csv"), b"\xEF\xBB\xBFk,v\nx,3\nx,3\n");
+}
+
+#[tokio::test]
+async fn crlf_line_ending_survives_compression() {
+    let t = TestDb::new();
+    let config = t
+        .builder()
+        .line_ending(sqlitedumper::LineEnding::Crlf)
+        .compression(sqlitedumper::Compression::Zstd)
+        .build()
+        .unwrap();
+
+    sqlitedumper::dump_table(&config, "other").await.unwrap();
+
+    let csv = zstd::decode_all(t.read_bytes("other.csv.zst").as_slice()).unwrap();
+    assert_eq!(csv, b"k,v\r\nx,3\r\n");
+
+    let mut config = config;
+    config.overwrite = true;
+    config.compression = sqlitedumper::Compression::None;
+    config.line_ending = sqlitedumper::LineEnding::Platform;
+    let csv = t.dump(&config, "other", "other.csv").await;
+    let expected = if cfg!(target_os = "windows") {
+        "k,v\r\nx,3\r\n"
+    } else {
+        "k,v\nx,3\n"
+    };
+    assert_eq!(csv, expected);
+}
+
+#[tokio::test]
+async fn encoding_transcodes_csv_and_replaces_unmappable_characters() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE words (café TEXT);
+         INSERT INTO words VALUES ('naïve €5'), ('日本');",
+    );
+    let config = t
+        .builder()
+        .encoding(Some(encoding_rs::WINDOWS_1252))
+        .build()
+        .unwrap();
+
+    let (guard, logs) = capture_logs();
+    sqlitedumper::dump_table(&config, "words").await.unwrap();
+    drop(guard);
+
+    assert_eq!(t.read_bytes("words.csv"), b"caf\xE9\nna\xEFve \x805\n??\n");
+    let logs = String::from_utf8(logs.lock().unwrap().clone()).unwrap();
+    let warning: serde_json::Value = logs
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .find(|event| event["fields"]["unmappable"].is_number())
+        .unwrap();
+    assert_eq!(warning["level"], "WARN");
+    assert_eq!(warning["fields"]["unmappable"], 2);
+
+    let result = t
+        .builder()
+        .encoding(Some(encoding_rs::WINDOWS_1252))
+        .output_format(sqlitedumper::OutputFormat::Jsonl)
+        .build();
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::InvalidConfig(_))
+    ));
+}
+
+#[tokio::test]
+async fn quote_escape_and_quoting_shape_csv_fields() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE quotes (id INTEGER, text TEXT);
+         INSERT INTO quotes VALUES (1, 'say \"hi\", bye');",
+    );
+    let config = t.builder().escape_char(b'\\').build().unwrap();
+    let csv = t.dump(&config, "quotes", "quotes.csv").await;
+    assert_eq!(csv, "id,text\n1,\"say \\\"hi\\\", bye\"\n");
+
+    let mut config = config;
+    config.overwrite = true;
+    config.escape_char = b'\'';
+    config.quote_char = b'\'';
+    config.quoting = sqlitedumper::Quoting::Always;
+    let csv = t.dump(&config, "quotes", "quotes.csv").await;
+    assert_eq!(csv, "'id','text'\n'1','say \"hi\", bye'\n");
+
+    let result = t.builder().quote_char(b',').build();
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::InvalidConfig(_))
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn watch_dumps_changed_tables_again() {
+    let t = TestDb::new();
+    let config = t.builder().build().unwrap();
+    sqlitedumper::dump_database(&config).await.unwrap();
+    let stats_modified = || {
+        std::fs::metadata(t.out.join("stats.csv"))
+            .unwrap()
+            .modified()
+            .unwrap()
+    };
+    let stats_csv = stats_modified();
+
+    let watcher = {
+        let config = config.clone();
+        tokio::spawn(async move {
+            sqlitedumper::watch(&config, std::time::Duration::from_millis(50)).await
+        })
+    };
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    t.conn()
+        .execute("INSERT INTO other VALUES ('y', 4)", [])
+        .unwrap();
+
+    let mut csv = String::new();
+    for _ in 0..100 {
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        csv = t.read("other.csv");
+        if csv.contains("y,4") {
+            break;
+        }
+    }
+    config.cancel.cancel();
+    watcher.await.unwrap().unwrap();
+    assert_eq!(csv, "k,v\nx,3\ny,4\n");
+    // Tables that did not change are not dumped again.
+    assert_eq!(stats_modified(), stats_csv);
+}
+
+#[tokio::test]
+async fn output_tar_compresses_entries_once_and_only_when_complete() {
+    use std::io::Read;
+
+    let t = TestDb::new();
+    let archive = t.path("dump.tar.gz");
+    let config = t
+        .builder()
+        .output_tar(Some(archive.to_str().unwrap().to_string()))
+        .compression(sqlitedumper::Compression::Gzip)
+        .build()
+        .unwrap();
+
+    sqlitedumper::dump_database(&config).await.unwrap();
+
+    let gz = flate2::read::GzDecoder::new(std::fs::File::open(&archive).unwrap());
+    let mut tar = tar::Archive::new(gz);
+    let mut entries = std::collections::BTreeMap::new();
+    for entry in tar.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let mut contents = String::new();
+        entry.read_to_string(&mut contents).unwrap();
+        let name = entry.path().unwrap().to_string_lossy().into_owned();
+        entries.insert(name, contents);
+    }
+    assert_eq!(
+        entries.keys().collect::<Vec<_>>(),
+        ["other.csv", "stats.csv"]
+    );
+    assert_eq!(entries["other.csv"], "k,v\nx,3\n");
+
+    // A failed table leaves the archive of the last complete dump.
+    let before = std::fs::read(&archive).unwrap();
+    t.conn()
+        .execute("UPDATE other SET k = CAST(X'FF' AS TEXT)", [])
+        .unwrap();
+    let mut config = config;
+    config.max_errors = None;
+    let stats = sqlitedumper::dump_database(&config).await.unwrap();
+    assert!(stats.tables.iter().any(|table| table.error.is_some()));
+    assert_eq!(std::fs::read(&archive).unwrap(), before);
+}
+
+#[test]
+fn archives_are_not_written_when_a_table_fails() {
+    let t = TestDb::new();
+    t.conn()
+        .execute("UPDATE other SET k = CAST(X'FF' AS TEXT)", [])
+        .unwrap();
+    for (flag, name) in [("--output-zip", "dump.zip"), ("--output-tar", "dump.tar")] {
+        let archive = t.path(name);
+        let archive = archive.to_str().unwrap();
+        let stats = t.path("stats.json");
+        let stats = stats.to_str().unwrap();
+        let output = t.run(&[flag, archive, "--stats-file", stats]);
+        assert_eq!(output.status.code(), Some(2), "{flag}");
+        assert!(!std::path::Path::new(archive).exists(), "{flag}");
+        let stats: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(stats).unwrap()).unwrap();
+        let other = stats["tables"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|table| table["table"] == "other")
+            .unwrap();
+        assert!(other["error"].is_string(), "{flag}");
+
+        for args in [&["--continue-on-error"][..], &["--max-errors", "2"]] {
+            let output = t.run(&[&[flag, archive][..], args].concat());
+            assert!(!output.status.success(), "{flag} {args:?}");
+            let stderr = String::from_utf8(output.stderr).unwrap();
+            assert!(stderr.contains("cannot be used with"), "{flag} {args:?}");
+        }
+    }
+}
+
+#[tokio::test]
+async fn output_zip_password_encrypts_the_entries() {
+    use std::io::Read;
+
+    let t = TestDb::new();
+    let archive = t.path("dump.zip");
+    let config = t
+        .builder()
+        .output_zip(Some(archive.to_str().unwrap().to_string()))
+        .output_zip_password(Some(sqlitedumper::ZipPassword::new("secret")))
+        .build()
+        .unwrap();
+    assert!(!format!("{config:?}").contains("secret"));
+
+    sqlitedumper::dump_database(&config).await.unwrap();
+
+    let mut zip = zip::ZipArchive::new(std::fs::File::open(&archive).unwrap()).unwrap();
+    assert!(zip.by_name("other.csv").is_err());
+    assert!(matches!(
+        zip.by_name_decrypt("other.csv", b"wrong"),
+        Err(zip::result::ZipError::InvalidPassword)
+    ));
+    let mut csv = String::new();
+    zip.by_name_decrypt("other.csv", b"secret")
+        .unwrap()
+        .read_to_string(&mut csv)
+        .unwrap();
+    assert_eq!(csv, "k,v\nx,3\n");
+
+    let result = t
+        .builder()
+        .output_zip_password(Some(sqlitedumper::ZipPassword::new("secret")))
+        .build();
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::InvalidConfig(_))
+    ));
+}
+
+#[tokio::test]
+async fn redacted_values_are_replaced() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE users (id INTEGER, email TEXT, note TEXT);
+         INSERT INTO users VALUES (0, 'a@example.com', 'call 555-1234'), (1, 'none', NULL);",
+    );
+    let email = |pattern: Option<&str>| sqlitedumper::RedactColumn {
+        table: "users".to_string(),
+        column: "email".to_string(),
+        pattern: pattern.map(|pattern| glob::Pattern::new(pattern).unwrap()),
+    };
+    let config = t
+        .builder()
+        .redact_columns(vec![email(Some("*@*"))])
+        .redact_pattern(Some(regex::Regex::new(r"\d{3}-\d{4}").unwrap()))
+        .build()
+        .unwrap();
+    let csv = t.dump(&config, "users", "users.csv").await;
+    assert_eq!(
+        csv,
+        "id,email,note\n0,[REDACTED],call [REDACTED]\n1,none,null\n"
+    );
+
+    let mut config = config;
+    config.overwrite = true;
+    config.redact_hash = true;
+    config.redact_pattern = None;
+    let csv = t.dump(&config, "users", "users.csv").await;
+    let digest = <sha2::Sha256 as sha2::Digest>::digest(b"a@example.com");
+    let hex: String = digest.iter().map(|byte| format!("{byte:02x}")).collect();
+    assert_eq!(
+        csv,
+        format!("id,email,note\n0,{hex},call 555-1234\n1,none,null\n")
+    );
+}
+
+#[tokio::test]
+async fn redaction_leaves_unmatched_values_and_checks_columns() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE users (id INTEGER, email TEXT, avatar BLOB);
+         INSERT INTO users VALUES (0, 'a@example.com', X'00FF');",
+    );
+    let rule = |column: &str, pattern: Option<&str>| sqlitedumper::RedactColumn {
+        table: "users".to_string(),
+        column: column.to_string(),
+        pattern: pattern.map(|pattern| glob::Pattern::new(pattern).unwrap()),
+    };
+    let config = t
+        .builder()
+        .redact_columns(vec![
+            rule("email", Some("*@test.com")),
+            rule("avatar", Some("*")),
+        ])
+        .redact_pattern(Some(regex::Regex::new(r"\d{3}-\d{4}").unwrap()))
+        .build()
+        .unwrap();
+    // Neither the glob nor the regex match, and patterns never match BLOBs.
+    let csv = t.dump(&config, "users", "users.csv").await;
+    assert_eq!(csv, "id,email,avatar\n0,a@example.com,00ff\n");
+
+    let mut config = config;
+    config.overwrite = true;
+    config.redact_columns = vec![rule("avatar", None)];
+    let csv = t.dump(&config, "users", "users.csv").await;
+    assert_eq!(csv, "id,email,avatar\n0,a@example.com,[REDACTED]\n");
+
+    config.redact_columns = vec![rule("e_mail", None)];
+    let err = sqlitedumper::dump_table(&config, "users")
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        sqlitedumper::DumperError::ColumnNotFound { .. }
+    ));
+}
+
+#[tokio::test]
+async fn delimiter_separates_and_quotes_csv_fields() {
+    let t = TestDb::new();
+    let config = t.builder().delimiter(b';').build().unwrap();
+    let csv = t.dump(&config, "stats", "stats.csv").await;
+    assert_eq!(
+        csv.lines().nth(1).unwrap(),
+        "1;1700000000;2023-11-14T22:13:20Z;a,b;1.5"
+    );
+
+    let mut config = config;
+    config.overwrite = true;
+    config.delimiter = b',';
+    let csv = t.dump(&config, "stats", "stats.csv").await;
+    assert!(csv.contains("\"a,b\""));
+
+    let status = std::process::Command::new(env!("CARGO_BIN_EXE_sqlitedumper"))
+        .args(["-f", &t.db, "-d", t.out.to_str().unwrap(), "--overwrite"])
+        .args(["--delimiter", "\\t"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(t.read("other.csv"), "k\tv\nx\t3\n");
+
+    let status = std::process::Command::new(env!("CARGO_BIN_EXE_sqlitedumper"))
+        .args(["-f", &t.db, "--delimiter", "::"])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .unwrap();
+    assert!(!status.success());
+}
+
+#[tokio::test]
+async fn tables_filter_dumps_only_the_included_tables() {
+    let t = TestDb::new();
+    let config = t
+        .builder()
+        .tables_filter(sqlitedumper::TableFilter {
+            include: vec!["other".to_string()],
+            ..Default::default()
+        })
+        .build()
+        .unwrap();
+    let stats = sqlitedumper::dump_database(&config).await.unwrap();
+    assert_eq!(stats.tables.len(), 1);
+    assert_eq!(t.files(), ["other.csv"]);
+
+    let mut config = config;
+    config.tables_filter.include.push("missing".to_string());
+    let result = sqlitedumper::dump_database(&config).await;
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::TableNotFound(name)) if name == "missing"
+    ));
+}
+
+#[tokio::test]
+async fn exclude_tables_skips_tables_matching_a_glob() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE stats (x); CREATE TABLE tmp_a (x); CREATE TABLE tmp_b (x); CREATE TABLE other (x);",
+    );
+    let filter = |exclude: &[&str]| sqlitedumper::TableFilter {
+        exclude: exclude
+            .iter()
+            .map(|pattern| glob::Pattern::new(pattern).unwrap())
+            .collect(),
+        ..Default::default()
+    };
+    let config = t
+        .builder()
+        .tables_filter(filter(&["tmp_*", "other"]))
+        .build()
+        .unwrap();
+    sqlitedumper::dump_database(&config).await.unwrap();
+    assert_eq!(t.files(), ["stats.csv"]);
+}
+
+#[tokio::test]
+async fn table_pattern_dumps_the_matching_tables() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE stats_2023 (x); CREATE TABLE stats_2024 (x); CREATE TABLE old_stats (x);",
+    );
+    let config = t
+        .builder()
+        .tables_filter(sqlitedumper::TableFilter {
+            pattern: Some(regex::Regex::new("^stats_\\d+$").unwrap()),
+            ..Default::default()
+        })
+        .build()
+        .unwrap();
+    sqlitedumper::dump_database(&config).await.unwrap();
+    assert_eq!(t.files(), ["stats_2023.csv", "stats_2024.csv"]);
+
+    // The pattern narrows the included tables, the exclusions come last.
+    let mut config = config;
+    config.overwrite = true;
+    std::fs::remove_dir_all(&t.out).unwrap();
+    config.tables_filter.include = vec!["stats_2024".to_string(), "old_stats".to_string()];
+    config.tables_filter.exclude = vec![glob::Pattern::new("*_2023").unwrap()];
+    sqlitedumper::dump_database(&config).await.unwrap();
+    assert_eq!(t.files(), ["stats_2024.csv"]);
+}
+
+#[test]
+fn log_level_none_logs_nothing_and_unknown_levels_fail() {
+    let t = TestDb::new();
+    let output = t.run(&["-l", "none"]);
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    assert_eq!(t.read("other.csv"), "k,v\nx,3\n");
+
+    let output = t.run(&["-l", "loud", "--overwrite"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid log level 'loud'"));
+}
+
+#[tokio::test]
+async fn errors_can_be_matched_by_kind() {
+    let t = TestDb::new();
+    let config = t.builder().build().unwrap();
+    // The output directory is a file.
+    std::fs::write(&t.out, "").unwrap();
+    let result = sqlitedumper::dump_table(&config, "other").await;
+    assert!(matches!(result, Err(sqlitedumper::DumperError::Io(_))));
+
+    std::fs::remove_file(&t.out).unwrap();
+    let result = sqlitedumper::dump_table(&config, "missing").await;
+    assert!(matches!(result, Err(sqlitedumper::DumperError::Sqlite(_))));
+}
+
+#[test]
+fn builder_requires_a_database_and_a_table_in_the_pattern() {
+    let result = DumpConfig::builder().output_dir("out").build();
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::InvalidConfig(_))
+    ));
+
+    let result = DumpConfig::builder()
+        .db_path("test.sqlite")
+        .output_pattern("dump.csv")
+        .build();
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::InvalidConfig(_))
+    ));
+
+    let config = DumpConfig::builder()
+        .db_path("test.sqlite")
+        .build()
+        .unwrap();
+    assert_eq!(config.delimiter, b',');
+    assert_eq!(config.output_format, sqlitedumper::OutputFormat::Csv);
+}
+
+#[tokio::test]
+async fn views_are_dumped_like_tables() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE stats (id INTEGER, value REAL);
+         INSERT INTO stats VALUES (1, 1.5), (2, 0.5);
+         CREATE VIEW big AS SELECT id FROM stats WHERE value > 1;
+         CREATE TABLE gone (x);
+         CREATE VIEW broken AS SELECT x FROM gone;
+         DROP TABLE gone;",
+    );
+    let config = t.builder().build().unwrap();
+    assert_eq!(sqlitedumper::get_tables(&config).unwrap(), ["stats"]);
+
+    let mut config = t.builder().include_views(true).build().unwrap();
+    let mut tables = sqlitedumper::get_tables(&config).unwrap();
+    tables.sort();
+    assert_eq!(tables, ["big", "broken", "stats"]);
+    assert_eq!(t.dump(&config, "big", "big.csv").await, "id\n1\n");
+
+    config.include_views = false;
+    config.views_only = true;
+    let mut tables = sqlitedumper::get_tables(&config).unwrap();
+    tables.sort();
+    assert_eq!(tables, ["big", "broken"]);
+
+    let result = sqlitedumper::dump_table(&config, "broken").await;
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::BrokenView { view, .. }) if view == "broken"
+    ));
+}
+
+#[tokio::test]
+async fn schema_only_writes_the_ddl_instead_of_rows() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE a (x INTEGER UNIQUE);
+         CREATE INDEX a_x ON a (x);
+         INSERT INTO a VALUES (1);
+         CREATE TABLE b (y TEXT);",
+    );
+    let config = t.builder().schema_only(true).build().unwrap();
+    sqlitedumper::dump_database(&config).await.unwrap();
+    assert_eq!(t.files(), ["a.sql", "b.sql"]);
+    assert_eq!(t.read("a.sql"), "CREATE TABLE a (x INTEGER UNIQUE);\n");
+
+    // Indexes without SQL, like the one of UNIQUE, are left out.
+    let mut config = config;
+    config.overwrite = true;
+    config.include_indexes = true;
+    config.schema_file = true;
+    config.tables_filter.include = vec!["a".to_string(), "b".to_string()];
+    sqlitedumper::dump_database(&config).await.unwrap();
+    assert_eq!(
+        t.read("schema.sql"),
+        "CREATE TABLE a (x INTEGER UNIQUE);\n\
+         CREATE INDEX a_x ON a (x);\n\
+         \n\
+         CREATE TABLE b (y TEXT);\n"
+    );
+}
+
+#[tokio::test]
+async fn where_clauses_filter_the_rows() {
+    let t = TestDb::new();
+    let config = t
+        .builder()
+        .where_clause(Some("rowid > 0".to_string()))
+        .table_where([("stats".to_string(), "value < 1".to_string())].into())
+        .build()
+        .unwrap();
+    let csv = t.dump(&config, "stats", "stats.csv").await;
+    assert_eq!(csv.lines().count(), 2);
+    assert!(csv.ends_with("\n2,1700000060,2023-11-14T22:14:20Z,null,0.25\n"));
+    // Only the global clause applies to other tables.
+    assert_eq!(t.dump(&config, "other", "other.csv").await, "k,v\nx,3\n");
+
+    let result = t
+        .builder()
+        .table_where([("stats".to_string(), "1=1 -- x".to_string())].into())
+        .build();
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::UnsafeSqlFragment(_))
+    ));
+}
+
+#[tokio::test]
+async fn limit_and_offset_select_a_page_of_rows() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE n (i INTEGER); INSERT INTO n VALUES (1), (2), (3), (4), (5);
+         CREATE TABLE m (i INTEGER); INSERT INTO m VALUES (1), (2);",
+    );
+    let config = t
+        .builder()
+        .limit(Some(2))
+        .offset(Some(1))
+        .order_by(Some("i".to_string()))
+        .build()
+        .unwrap();
+    assert_eq!(t.dump(&config, "n", "n.csv").await, "i\n2\n3\n");
+
+    // An offset without a limit skips the rows and dumps the rest.
+    let mut config = config;
+    config.overwrite = true;
+    config.limit = None;
+    config.offset = Some(3);
+    assert_eq!(t.dump(&config, "n", "n.csv").await, "i\n4\n5\n");
+
+    let result = t.builder().limit(Some(0)).build();
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::InvalidConfig(_))
+    ));
+}
+
+#[tokio::test]
+async fn order_by_sorts_the_rows() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE n (i INTEGER, s TEXT);
+         INSERT INTO n VALUES (2, 'b'), (3, 'a'), (1, 'b');",
+    );
+    let config = t
+        .builder()
+        .order_by(Some("s, i DESC".to_string()))
+        .build()
+        .unwrap();
+    assert_eq!(t.dump(&config, "n", "n.csv").await, "i,s\n3,a\n2,b\n1,b\n");
+
+    // rowid comes first, before the given order.
+    let mut config = config;
+    config.overwrite = true;
+    config.order_by_rowid = true;
+    assert_eq!(t.dump(&config, "n", "n.csv").await, "i,s\n2,b\n3,a\n1,b\n");
+
+    let result = t
+        .builder()
+        .order_by(Some("i; DELETE FROM n".to_string()))
+        .build();
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::UnsafeSqlFragment(_))
+    ));
+}
+
+#[tokio::test]
+async fn gzip_compresses_at_the_given_level() {
+    use std::io::Read;
+
+    let t = TestDb::new();
+    let config = t
+        .builder()
+        .compression(sqlitedumper::Compression::Gzip)
+        .compress_level(Some(9))
+        .build()
+        .unwrap();
+    sqlitedumper::dump_table(&config, "other").await.unwrap();
+
+    let gz = t.read_bytes("other.csv.gz");
+    let mut csv = String::new();
+    flate2::read::GzDecoder::new(gz.as_slice())
+        .read_to_string(&mut csv)
+        .unwrap();
+    assert_eq!(csv, "k,v\nx,3\n");
+    // The extra flags byte of the gzip header records the best compression.
+    assert_eq!(gz[8], 2);
+}
+
+#[tokio::test]
+async fn zstd_dictionary_is_trained_and_needed_to_decompress() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE readings (id INTEGER, sensor TEXT, value REAL);
+         WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 1000)
+         INSERT INTO readings SELECT i, 'sensor ' || (i % 7), i * 0.25 FROM n;",
+    );
+    let dict = t.path("dict");
+    let config = t.builder().build().unwrap();
+    sqlitedumper::train_zstd_dict(&config, dict.to_str().unwrap()).unwrap();
+    let dictionary = std::fs::read(&dict).unwrap();
+
+    let config = t
+        .builder()
+        .compression(sqlitedumper::Compression::Zstd)
+        .zstd_dictionary(Some(dictionary.clone()))
+        .build()
+        .unwrap();
+    sqlitedumper::dump_table(&config, "readings").await.unwrap();
+    let zst = t.read_bytes("readings.csv.zst");
+
+    let mut decoder = zstd::stream::Decoder::with_dictionary(zst.as_slice(), &dictionary).unwrap();
+    let mut csv = String::new();
+    std::io::Read::read_to_string(&mut decoder, &mut csv).unwrap();
+    assert_eq!(csv.lines().count(), 1001);
+    assert!(zstd::decode_all(zst.as_slice()).is_err());
+
+    let result = t.builder().zstd_dictionary(Some(dictionary)).build();
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::InvalidConfig(_))
+    ));
+}
+
+#[tokio::test]
+async fn output_zip_bundles_the_tables_compressed_as_configured() {
+    use std::io::Read;
+
+    let t = TestDb::new();
+    let archive = t.path("dump.zip");
+    let config = t
+        .builder()
+        .output_zip(Some(archive.to_str().unwrap().to_string()))
+        .compression(sqlitedumper::Compression::Gzip)
+        .build()
+        .unwrap();
+    sqlitedumper::dump_database(&config).await.unwrap();
+    assert!(!t.out.join("other.csv").exists());
+
+    let mut zip = zip::ZipArchive::new(std::fs::File::open(&archive).unwrap()).unwrap();
+    let mut names: Vec<String> = (0..zip.len())
+        .map(|i| zip.by_index(i).unwrap().name().unwrap().to_string())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["other.csv", "stats.csv"]);
+    let mut entry = zip.by_name("other.csv").unwrap();
+    assert_eq!(entry.compression(), zip::CompressionMethod::Deflated);
+    let mut csv = String::new();
+    entry.read_to_string(&mut csv).unwrap();
+    assert_eq!(csv, "k,v\nx,3\n");
+
+    let result = t
+        .builder()
+        .output_zip(Some(archive.to_str().unwrap().to_string()))
+        .compression(sqlitedumper::Compression::Lz4)
+        .build();
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::InvalidConfig(_))
+    ));
+}
+
+#[tokio::test]
+async fn progress_bars_do_not_change_the_output() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE n (i INTEGER);
+         WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 2500)
+         INSERT INTO n SELECT i FROM n;",
+    );
+    let config = t.builder().progress(true).build().unwrap();
+    let stats = sqlitedumper::dump_database(&config).await.unwrap();
+    assert_eq!(stats.tables[0].rows, Some(2500));
+    let csv = t.read("n.csv");
+    assert_eq!(csv.lines().count(), 2501);
+    assert!(csv.ends_with("\n2500\n"));
+}
+
+#[test]
+fn log_file_logs_below_the_stderr_level_with_timestamps() {
+    let t = TestDb::new();
+    let log = t.path("dump.log");
+    let output = t.run(&["--log-file", log.to_str().unwrap()]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "");
+
+    let contents = std::fs::read_to_string(&log).unwrap();
+    assert!(contents.contains("DEBUG sqlitedumper: Start thread for table stats"));
+    assert!(contents.contains("INFO sqlitedumper: Dumping table stats"));
+    for line in contents
+        .lines()
+        .filter(|line| line.contains(" sqlitedumper: "))
+    {
+        let (timestamp, _) = line.split_once(' ').unwrap();
+        assert!(
+            chrono::DateTime::parse_from_rfc3339(timestamp).is_ok(),
+            "{line}"
+        );
+    }
+
+    // A second run appends to the file.
+    let output = t.run(&["--log-file", log.to_str().unwrap(), "--overwrite"]);
+    assert!(output.status.success());
+    let appended = std::fs::read_to_string(&log).unwrap();
+    assert!(appended.starts_with(&contents) && appended.len() > contents.len());
+}
+
+#[test]
+fn quiet_logs_only_errors_and_verbose_logs_everything() {
+    let t = TestDb::new();
+    let stderr = |args: &[&str]| {
+        let output = t.run(args);
+        assert!(output.status.success());
+        String::from_utf8(output.stderr).unwrap()
+    };
+    assert_eq!(stderr(&["-q", "-l", "info"]), "");
+    assert!(stderr(&["-v", "--overwrite"]).contains("DEBUG sqlitedumper] Start thread for table"));
+    assert!(!stderr(&["-l", "info", "--overwrite"]).contains("DEBUG"));
+
+    let output = t.run(&["-q", "-v", "--overwrite"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn dry_run_plans_the_dump_without_writing_files() {
+    let t = TestDb::new();
+    let config = t
+        .builder()
+        .where_clause(Some("rowid = 1".to_string()))
+        .build()
+        .unwrap();
+    let mut plans = sqlitedumper::plan_dump(&config).unwrap();
+    plans.sort_by(|a, b| a.table.cmp(&b.table));
+    let counts: Vec<(&str, u64)> = plans
+        .iter()
+        .map(|plan| (plan.table.as_str(), plan.row_count))
+        .collect();
+    assert_eq!(counts, [("other", 1), ("stats", 1)]);
+    assert_eq!(plans[0].estimated_bytes, "k,v\nx,3\n".len() as u64);
+
+    let output = t.run(&["--dry-run"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("table"));
+    assert!(stdout.contains("stats"));
+    assert!(!t.out.exists());
+}
+
+#[test]
+fn dry_run_plans_a_sample() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE a (id INTEGER, v TEXT);
+         INSERT INTO a VALUES (1, 'x'), (2, 'y'), (3, 'z');",
+    );
+    let config = t.builder().sample(Some(2)).build().unwrap();
+    let plans = sqlitedumper::plan_dump(&config).unwrap();
+    assert_eq!(plans[0].row_count, 2);
+    assert_eq!(plans[0].estimated_bytes, "id,v\n1,x\n2,y\n".len() as u64);
+
+    let output = t.run(&["--dry-run", "--sample", "2"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().contains("a "));
+}
+
+#[test]
+fn dry_run_writes_no_blob_files() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE a (id INTEGER, b BLOB);
+         INSERT INTO a VALUES (1, zeroblob(200));",
+    );
+    let blobs = t.path("blobs");
+    let output = t.run(&[
+        "--dry-run",
+        "--blob-dir",
+        blobs.to_str().unwrap(),
+        "--max-blob-size",
+        "100",
+    ]);
+    assert!(output.status.success());
+    assert!(!blobs.exists());
+    assert!(!t.out.exists());
+}
+
+#[test]
+fn dry_run_counts_rows_after_the_since_bounds() {
+    let t = TestDb::new();
+    let row_count = |config: &DumpConfig, table: &str| {
+        let plans = sqlitedumper::plan_dump(config).unwrap();
+        plans
+            .iter()
+            .find(|plan| plan.table == table)
+            .unwrap()
+            .row_count
+    };
+    let config = t
+        .builder()
+        .since_rowid([("stats".to_string(), 1)].into())
+        .build()
+        .unwrap();
+    assert_eq!(row_count(&config, "stats"), 1);
+    assert_eq!(row_count(&config, "other"), 1);
+
+    let since = sqlitedumper::parse_since_timestamp("2023-11-14T22:13:20").unwrap();
+    let config = t.builder().since_timestamp(Some(since)).build().unwrap();
+    assert_eq!(row_count(&config, "stats"), 1);
+
+    let rowids = t.path("rowids.json");
+    std::fs::write(&rowids, r#"{"stats": 2}"#).unwrap();
+    let timestamps = t.path("timestamps.json");
+    std::fs::write(&timestamps, r#"{"stats": 1700000000}"#).unwrap();
+    let config = t
+        .builder()
+        .rowid_state_file(Some(rowids.to_str().unwrap().to_string()))
+        .build()
+        .unwrap();
+    assert_eq!(row_count(&config, "stats"), 0);
+    let config = t
+        .builder()
+        .timestamp_state_file(Some(timestamps.to_str().unwrap().to_string()))
+        .build()
+        .unwrap();
+    assert_eq!(row_count(&config, "stats"), 1);
+
+    let output = t.run(&["--dry-run", "--since-rowid", "stats:1"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stats = stdout
+        .lines()
+        .find(|line| line.starts_with("stats"))
+        .unwrap();
+    assert_eq!(stats.split_whitespace().nth(1), Some("1"));
+}
+
+#[tokio::test]
+async fn append_adds_rows_without_a_second_header() {
+    let t = TestDb::new();
+    let config = t.builder().append(true).build().unwrap();
+    t.dump(&config, "other", "other.csv").await;
+    let csv = t.dump(&config, "other", "other.csv").await;
+    assert_eq!(csv, "k,v\nx,3\nx,3\n");
+
+    let config = t
+        .builder()
+        .append(true)
+        .output_format(sqlitedumper::OutputFormat::Jsonl)
+        .build()
+        .unwrap();
+    t.dump(&config, "other", "other.jsonl").await;
+    let jsonl = t.dump(&config, "other", "other.jsonl").await;
+    assert_eq!(jsonl, "{\"k\":\"x\",\"v\":3}\n{\"k\":\"x\",\"v\":3}\n");
+
+    let result = t
+        .builder()
+        .append(true)
+        .output_format(sqlitedumper::OutputFormat::Json)
+        .build();
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::InvalidConfig(_))
+    ));
+}
+
+#[tokio::test]
+async fn existing_files_are_replaced_only_with_overwrite() {
+    let t = TestDb::new();
+    std::fs::create_dir(&t.out).unwrap();
+    std::fs::write(t.out.join("other.csv"), "old contents that are longer\n").unwrap();
+
+    let config = t.builder().build().unwrap();
+    let result = sqlitedumper::dump_table(&config, "other").await;
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::OutputExists(path)) if path.ends_with("other.csv")
+    ));
+    assert_eq!(t.read("other.csv"), "old contents that are longer\n");
+
+    let config = t.builder().overwrite(true).build().unwrap();
+    assert_eq!(t.dump(&config, "other", "other.csv").await, "k,v\nx,3\n");
+
+    let result = t.builder().overwrite(true).append(true).build();
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::InvalidConfig(_))
+    ));
+}
+
+#[tokio::test]
+async fn no_header_leaves_out_the_header_row() {
+    let t = TestDb::new();
+    let config = t.builder().no_header(true).build().unwrap();
+    assert_eq!(t.dump(&config, "other", "other.csv").await, "x,3\n");
+
+    let mut config = config;
+    config.output_format = sqlitedumper::OutputFormat::Markdown;
+    assert_eq!(t.dump(&config, "other", "other.md").await, "| x | 3 |\n");
+}
+
+#[tokio::test]
+async fn null_value_replaces_null_in_text_formats() {
+    let t = TestDb::with_sql("CREATE TABLE n (a, b); INSERT INTO n VALUES (NULL, '');");
+    let config = t.builder().build().unwrap();
+    assert_eq!(t.dump(&config, "n", "n.csv").await, "a,b\nnull,\n");
+
+    let mut config = t.builder().null_value("\\N").build().unwrap();
+    config.overwrite = true;
+    assert_eq!(t.dump(&config, "n", "n.csv").await, "a,b\n\\N,\n");
+
+    // JSON keeps its own null.
+    config.output_format = sqlitedumper::OutputFormat::Jsonl;
+    assert_eq!(
+        t.dump(&config, "n", "n.jsonl").await,
+        "{\"a\":null,\"b\":\"\"}\n"
+    );
+}
+
+#[tokio::test]
+async fn blob_format_encodes_blobs_in_csv() {
+    let t = TestDb::with_sql("CREATE TABLE b (data BLOB); INSERT INTO b VALUES (X'48690aff');");
+    let mut config = t.builder().overwrite(true).build().unwrap();
+    for (format, expected) in [
+        (None, "48690aff"),
+        (Some(sqlitedumper::BlobFormat::Base64), "SGkK/w=="),
+        (Some(sqlitedumper::BlobFormat::Base64url), "SGkK_w"),
+        (
+            Some(sqlitedumper::BlobFormat::Debug),
+            "\"[72, 105, 10, 255]\"",
+        ),
+        (Some(sqlitedumper::BlobFormat::Skip), "\"\""),
+    ] {
+        config.blob_format = format;
+        let csv = t.dump(&config, "b", "b.csv").await;
+        assert_eq!(csv, format!("data\n{expected}\n"), "{format:?}");
+    }
+}
+
+#[tokio::test]
+async fn float_precision_rounds_real_values() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE f (value REAL, n INTEGER);
+         INSERT INTO f VALUES (2.0 / 3, 7), (1234567.0, 8), (0.00001234, 9);",
+    );
+    let config = t.builder().build().unwrap();
+    assert_eq!(
+        t.dump(&config, "f", "f.csv").await,
+        "value,n\n0.6666666666666666,7\n1234567,8\n0.00001234,9\n"
+    );
+
+    let mut config = t.builder().float_precision(Some(2)).build().unwrap();
+    config.overwrite = true;
+    assert_eq!(
+        t.dump(&config, "f", "f.csv").await,
+        "value,n\n0.67,7\n1234567.00,8\n1.23e-5,9\n"
+    );
+
+    config.float_format = sqlitedumper::FloatFormat::Fixed;
+    assert_eq!(
+        t.dump(&config, "f", "f.csv").await,
+        "value,n\n0.67,7\n1234567.00,8\n0.00,9\n"
+    );
+}
+
+#[tokio::test]
+async fn timestamp_format_and_time_zone_shape_parsed_timestamps() {
+    let t = TestDb::new();
+    let config = t
+        .builder()
+        .timestamp_format(Some("%Y-%m-%d %H:%M:%S %Z".to_string()))
+        .timestamp_tz(Some(chrono_tz::Asia::Tokyo))
+        .build()
+        .unwrap();
+    let csv = t.dump(&config, "stats", "stats.csv").await;
+    assert_eq!(
+        csv.lines().nth(1).unwrap(),
+        "1,1700000000,2023-11-15 07:13:20 JST,\"a,b\",1.5"
+    );
+
+    let mut config = t
+        .builder()
+        .timestamp_tz(Some(chrono_tz::Asia::Tokyo))
+        .build()
+        .unwrap();
+    config.overwrite = true;
+    let csv = t.dump(&config, "stats", "stats.csv").await;
+    assert!(csv.contains(",2023-11-15T07:13:20+09:00,"));
+
+    let result = t
+        .builder()
+        .timestamp_format(Some("%Y-%Q".to_string()))
+        .build();
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::InvalidConfig(_))
+    ));
+}
+
+#[tokio::test]
+async fn timestamp_columns_each_get_a_parsed_column() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE events (id INTEGER, created_at INTEGER, updated_at INTEGER);
+         INSERT INTO events VALUES (1, 1700000000, 1700000060);",
+    );
+    let config = t
+        .builder()
+        .timestamp_columns(vec!["created_at".to_string(), "updated_at".to_string()])
+        .build()
+        .unwrap();
+    assert_eq!(
+        t.dump(&config, "events", "events.csv").await,
+        "id,created_at,created_at_parsed,updated_at,updated_at_parsed\n\
+         1,1700000000,2023-11-14T22:13:20Z,1700000060,2023-11-14T22:14:20Z\n"
+    );
+
+    let config = t.builder().overwrite(true).build().unwrap();
+    assert_eq!(
+        t.dump(&config, "events", "events.csv").await,
+        "id,created_at,updated_at\n1,1700000000,1700000060\n"
+    );
+}
+
+#[tokio::test]
+async fn epoch_unit_reads_milliseconds_and_microseconds() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE ms (timestamp INTEGER); INSERT INTO ms VALUES (1700000000123), (-1);
+         CREATE TABLE us (timestamp INTEGER); INSERT INTO us VALUES (1700000000123456);",
+    );
+    let config = t
+        .builder()
+        .epoch_unit(sqlitedumper::EpochUnit::Millis)
+        .build()
+        .unwrap();
+    assert_eq!(
+        t.dump(&config, "ms", "ms.csv").await,
+        "timestamp,timestamp_parsed\n\
+         1700000000123,2023-11-14T22:13:20.123Z\n\
+         -1,1969-12-31T23:59:59.999Z\n"
+    );
+
+    let mut config = config;
+    config.epoch_unit = sqlitedumper::EpochUnit::Micros;
+    assert_eq!(
+        t.dump(&config, "us", "us.csv").await,
+        "timestamp,timestamp_parsed\n1700000000123456,2023-11-14T22:13:20.123456Z\n"
+    );
+
+    let output = t.run(&["--epoch-ms", "--epoch-us"]);
+    assert!(!output.status.success());
+}
+
+#[tokio::test]
+async fn include_rowid_prepends_the_rowid_when_the_table_has_one() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE r (v TEXT); INSERT INTO r (rowid, v) VALUES (5, 'a'), (9, 'b');
+         CREATE TABLE w (k TEXT PRIMARY KEY, v TEXT) WITHOUT ROWID;
+         INSERT INTO w VALUES ('x', 'y');",
+    );
+    let config = t.builder().include_rowid(true).build().unwrap();
+    assert_eq!(t.dump(&config, "r", "r.csv").await, "rowid,v\n5,a\n9,b\n");
+    assert_eq!(t.dump(&config, "w", "w.csv").await, "k,v\nx,y\n");
+}
+
+#[test]
+fn count_only_prints_row_counts_without_dumping() {
+    let t = TestDb::new();
+    let output = t.run(&["--count-only", "--tables", "stats"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("stats  {:>12}\n", 2)
+    );
+    assert!(!t.out.exists());
+
+    let output = t.run(&["--count-only", "--format", "json"]);
+    let counts: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(counts, serde_json::json!({"other": 1, "stats": 2}));
+}
+
+#[test]
+fn table_info_describes_the_columns() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT NOT NULL DEFAULT 'none', sm_timestamp, extra);",
+    );
+    let config = t.builder().build().unwrap();
+    let info = sqlitedumper::table_info(&config).unwrap();
+    assert_eq!(info.len(), 1);
+    assert_eq!(info[0].table, "t");
+    let columns: Vec<(&str, &str, bool, i64, bool)> = info[0]
+        .columns
+        .iter()
+        .map(|c| {
+            let name = c.name.as_str();
+            (
+                name,
+                c.decl_type.as_str(),
+                c.not_null,
+                c.primary_key,
+                c.timestamp,
+            )
+        })
+        .collect();
+    assert_eq!(
+        columns,
+        [
+            ("id", "INTEGER", false, 1, false),
+            ("name", "TEXT", true, 0, false),
+            ("sm_timestamp", "", false, 0, true),
+            ("extra", "", false, 0, false),
+        ]
+    );
+    assert_eq!(info[0].columns[1].default_value.as_deref(), Some("'none'"));
+    assert!(!t.out.exists());
+}
+
+#[test]
+fn integrity_check_reports_a_corrupt_index() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE t (x TEXT);
+         CREATE INDEX t_x ON t (x);
+         INSERT INTO t VALUES ('a'), ('b');",
+    );
+    let config = t.builder().build().unwrap();
+    sqlitedumper::check_integrity(&config, false).unwrap();
+
+    // Declaring the index descending puts its entries out of order.
+    t.conn()
+        .execute_batch(
+            "PRAGMA writable_schema = ON;
+             UPDATE sqlite_master SET sql = 'CREATE INDEX t_x ON t (x DESC)' WHERE name = 't_x';",
+        )
+        .unwrap();
+    let result = sqlitedumper::check_integrity(&config, false);
+    let Err(sqlitedumper::DumperError::IntegrityCheckFailed(messages)) = result else {
+        panic!("integrity check passed: {result:?}");
+    };
+    assert!(
+        messages
+            .iter()
+            .any(|m| m.contains("missing from index t_x"))
+    );
+    // The quick check does not compare indexes with their tables.
+    sqlitedumper::check_integrity(&config, true).unwrap();
+}
+
+#[tokio::test]
+async fn columns_selects_columns_per_table_in_the_given_order() {
+    let t = TestDb::new();
+    let config = t
+        .builder()
+        .columns(
+            [(
+                "stats".to_string(),
+                vec!["value".to_string(), "sm_timestamp".to_string()],
+            )]
+            .into(),
+        )
+        .build()
+        .unwrap();
+    assert_eq!(
+        t.dump(&config, "stats", "stats.csv").await,
+        "value,sm_timestamp,timestamp_parsed\n\
+         1.5,1700000000,2023-11-14T22:13:20Z\n\
+         0.25,1700000060,2023-11-14T22:14:20Z\n"
+    );
+    assert_eq!(t.dump(&config, "other", "other.csv").await, "k,v\nx,3\n");
+
+    let mut config = config;
+    config.overwrite = true;
+    config
+        .columns
+        .insert("stats".to_string(), vec!["missing".to_string()]);
+    let result = sqlitedumper::dump_table(&config, "stats").await;
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::ColumnNotFound { table, column })
+            if table == "stats" && column == "missing"
+    ));
+}
+
+#[tokio::test]
+async fn exclude_columns_drops_columns_from_every_table() {
+    let t = TestDb::new();
+    let config = t
+        .builder()
+        .exclude_columns(vec!["NAME".to_string(), "v".to_string()])
+        .table_exclude_columns([("stats".to_string(), vec!["value".to_string()])].into())
+        .build()
+        .unwrap();
+    assert_eq!(
+        t.dump(&config, "stats", "stats.csv").await,
+        "id,sm_timestamp,timestamp_parsed\n\
+         1,1700000000,2023-11-14T22:13:20Z\n\
+         2,1700000060,2023-11-14T22:14:20Z\n"
+    );
+    assert_eq!(t.dump(&config, "other", "other.csv").await, "k\nx\n");
+}
+
+#[tokio::test]
+async fn max_cell_size_truncates_text_at_a_character_boundary() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE t (s TEXT, n INTEGER); INSERT INTO t VALUES ('abcdef', 1234567), ('aéé', 1);",
+    );
+    let config = t
+        .builder()
+        .max_cell_size(Some(4))
+        .truncation_marker("...")
+        .build()
+        .unwrap();
+    // 'aéé' is five bytes, the cut falls inside the second 'é'. Integers
+    // are not truncated.
+    assert_eq!(
+        t.dump(&config, "t", "t.csv").await,
+        "s,n\nabcd...,1234567\naé...,1\n"
+    );
+}
+
+#[tokio::test]
+async fn max_blob_size_truncates_oversized_blobs() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE b (data BLOB); INSERT INTO b VALUES (X'0102'), (X'01020304');",
+    );
+    let config = t.builder().max_blob_size(Some(2)).build().unwrap();
+    let (_guard, logs) = capture_logs();
+    assert_eq!(t.dump(&config, "b", "b.csv").await, "data\n0102\n0102\n");
+    let logs = String::from_utf8(logs.lock().unwrap().clone()).unwrap();
+    assert!(logs.contains("4 byte BLOB in column data of row 2 truncated to 2 bytes"));
+
+    // With a BLOB directory the oversized BLOB is written there instead.
+    let blobs = t.out.join("blobs");
+    let mut config = config;
+    config.overwrite = true;
+    config.blob_dir = Some(blobs.to_str().unwrap().to_string());
+    assert_eq!(
+        t.dump(&config, "b", "b.csv").await,
+        "data\n0102\nblobs/b/data/2.bin\n"
+    );
+    assert_eq!(
+        std::fs::read(blobs.join("b/data/2.bin")).unwrap(),
+        [1, 2, 3, 4]
+    );
+}
+
+#[tokio::test]
+async fn blob_dir_writes_blobs_to_files_above_the_inline_threshold() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE files (name TEXT, data BLOB);
+         INSERT INTO files VALUES ('small', X'01'), ('big', X'01020304'), ('none', NULL);",
+    );
+    let blobs = t.path("blobs");
+    let config = t
+        .builder()
+        .blob_dir(Some(blobs.to_str().unwrap().to_string()))
+        .blob_inline_threshold(2)
+        .build()
+        .unwrap();
+    assert_eq!(
+        t.dump(&config, "files", "files.csv").await,
+        "name,data\nsmall,01\nbig,../blobs/files/data/2.bin\nnone,null\n"
+    );
+    assert_eq!(
+        std::fs::read(blobs.join("files/data/2.bin")).unwrap(),
+        [1, 2, 3, 4]
+    );
+    assert_eq!(
+        std::fs::read_dir(blobs.join("files/data")).unwrap().count(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn output_pattern_names_and_places_the_files() {
+    let t = TestDb::new();
+    let config = t
+        .builder()
+        .output_pattern("{date}/{table}_{datetime}.{ext}")
+        .output_format(sqlitedumper::OutputFormat::Jsonl)
+        .build()
+        .unwrap();
+    sqlitedumper::dump_table(&config, "other").await.unwrap();
+
+    let date = chrono::Utc::now().format("%Y%m%d").to_string();
+    let names: Vec<String> = std::fs::read_dir(t.out.join(&date))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    assert_eq!(names.len(), 1);
+    let name = names[0]
+        .strip_prefix(&format!("other_{date}T"))
+        .and_then(|name| name.strip_suffix("Z.jsonl"))
+        .unwrap();
+    assert_eq!(name.len(), 6);
+    assert!(name.bytes().all(|b| b.is_ascii_digit()));
+}
+
+#[tokio::test]
+async fn rows_per_file_splits_tables_into_parts_with_headers() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE n (i INTEGER); INSERT INTO n VALUES (1), (2), (3), (4), (5);
+         CREATE TABLE m (i INTEGER); INSERT INTO m VALUES (1), (2);",
+    );
+    let config = t.builder().rows_per_file(Some(2)).build().unwrap();
+    let stats = sqlitedumper::dump_database(&config).await.unwrap();
+    let n = stats
+        .tables
+        .iter()
+        .find(|table| table.table == "n")
+        .unwrap();
+    assert_eq!(n.rows, Some(5));
+    // A table filling its last part exactly gets no empty part after it.
+    assert_eq!(
+        t.files(),
+        [
+            "m_part000.csv",
+            "n_part000.csv",
+            "n_part001.csv",
+            "n_part002.csv"
+        ]
+    );
+    assert_eq!(t.read("n_part000.csv"), "i\n1\n2\n");
+    assert_eq!(t.read("n_part002.csv"), "i\n5\n");
+
+    let result = t.builder().rows_per_file(Some(0)).build();
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::InvalidConfig(_))
+    ));
+}
+
+#[tokio::test]
+async fn single_file_writes_all_tables_with_a_table_column() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE a (id INTEGER, x TEXT); INSERT INTO a VALUES (1, 'p');
+         CREATE TABLE b (id INTEGER, y TEXT); INSERT INTO b VALUES (2, 'q'), (3, 'r');",
+    );
+    let path = t.path("all.csv");
+    let config = t
+        .builder()
+        .single_file(Some(path.to_str().unwrap().to_string()))
+        .tables_filter(sqlitedumper::TableFilter {
+            include: vec!["a".to_string(), "b".to_string()],
+            ..Default::default()
+        })
+        .build()
+        .unwrap();
+    let stats = sqlitedumper::dump_database(&config).await.unwrap();
+    let rows: Vec<Option<u64>> = stats.tables.iter().map(|table| table.rows).collect();
+    assert_eq!(rows, [Some(1), Some(2)]);
+    // Columns missing from a table are null in its rows.
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "_table,id,x,y\na,1,p,null\nb,2,null,q\nb,3,null,r\n"
+    );
+    assert!(!t.out.exists());
+}
+
+#[tokio::test]
+async fn stats_file_reports_rows_and_times_per_table() {
+    let t = TestDb::new();
+    let config = t.builder().build().unwrap();
+    let stats = sqlitedumper::dump_database(&config).await.unwrap();
+    let path = t.path("stats.json");
+    stats.write(path.to_str().unwrap()).unwrap();
+
+    let report: serde_json::Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    assert!(report["elapsed_ms"].is_u64());
+    let mut tables: Vec<(&str, u64)> = report["tables"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|table| {
+            assert!(table["elapsed_ms"].is_u64());
+            assert!(table["error"].is_null());
+            (
+                table["table"].as_str().unwrap(),
+                table["rows"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    tables.sort();
+    assert_eq!(tables, [("other", 1), ("stats", 2)]);
+
+    let output = t.run(&["--overwrite", "--stats-file", path.to_str().unwrap()]);
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!(report["tables"].as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn checksum_file_lists_the_digest_of_every_output_file() {
+    use sha2::Digest;
+
+    let t = TestDb::new();
+    let config = t
+        .builder()
+        .checksum(Some(sqlitedumper::ChecksumAlgorithm::Sha512))
+        .rows_per_file(Some(1))
+        .build()
+        .unwrap();
+    sqlitedumper::dump_database(&config).await.unwrap();
+
+    let mut lines: Vec<String> = t
+        .read("checksums.sha512")
+        .lines()
+        .map(str::to_string)
+        .collect();
+    lines.sort_by_key(|line| line.split_once("  ").unwrap().1.to_string());
+    let expected: Vec<String> = [
+        "other_part000.csv",
+        "stats_part000.csv",
+        "stats_part001.csv",
+    ]
+    .iter()
+    .map(|name| {
+        let digest = hex::encode(sha2::Sha512::digest(t.read_bytes(name)));
+        format!("{digest}  {name}")
+    })
+    .collect();
+    assert_eq!(lines, expected);
+}
+
+#[test]
+fn verify_prints_a_line_per_table_and_fails_on_a_mismatch() {
+    let t = TestDb::new();
+    let manifest = t.path("manifest.json");
+    let manifest = manifest.to_str().unwrap();
+    assert!(
+        t.run(&["--output-manifest", manifest, "--limit", "1"])
+            .status
+            .success()
+    );
+
+    let output = t.run(&["--verify", manifest]);
+    assert!(output.status.success());
+    let mut lines: Vec<String> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(str::to_string)
+        .collect();
+    lines.sort();
+    assert_eq!(
+        lines,
+        [
+            "PASS other: files only, rows were filtered and not counted",
+            "PASS stats: files only, rows were filtered and not counted",
+        ]
+    );
+
+    std::fs::write(t.out.join("stats.csv"), "").unwrap();
+    let output = t.run(&["--verify", manifest]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("FAIL stats: "));
+}
+
+#[test]
+fn verify_finds_the_files_relative_to_the_manifest() {
+    let t = TestDb::new();
+    let run = |dir: &std::path::Path, args: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_sqlitedumper"))
+            .current_dir(dir)
+            .args(["-f", &t.db])
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let dump = run(
+        t.path("").as_path(),
+        &["-d", "out", "--output-manifest", "out/m.json"],
+    );
+    assert!(dump.status.success());
+    let entries: Vec<sqlitedumper::ManifestEntry> =
+        serde_json::from_slice(&t.read_bytes("m.json")).unwrap();
+    assert!(entries.iter().all(|entry| !entry.path.starts_with("out")));
+
+    let parent = t.path("").parent().unwrap().to_path_buf();
+    let manifest = t
+        .out
+        .join("m.json")
+        .strip_prefix(&parent)
+        .unwrap()
+        .to_path_buf();
+    let output = run(&parent, &["--verify", manifest.to_str().unwrap()]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .matches("PASS")
+            .count(),
+        2
+    );
+}
+
+#[test]
+fn verify_checks_only_the_files_of_queries_and_deltas() {
+    let t = TestDb::new();
+    let manifest = t.path("manifest.json");
+    let manifest = manifest.to_str().unwrap();
+    let query = "q=SELECT k FROM other";
+    assert!(
+        t.run(&["--output-manifest", manifest, "--query", query])
+            .status
+            .success()
+    );
+    let output = t.run(&["--verify", manifest]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "PASS q: files only, rows were filtered and not counted\n"
+    );
+
+    let old = t.path("old.sqlite");
+    std::fs::copy(&t.db, &old).unwrap();
+    let args = ["--output-manifest", manifest, "--overwrite", "--compare-db"];
+    assert!(
+        t.run(&[&args[..], &[old.to_str().unwrap()]].concat())
+            .status
+            .success()
+    );
+    let output = t.run(&["--verify", manifest]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("PASS stats_added: files only"));
+    assert!(stdout.contains("PASS stats_deleted: files only"));
+}
+
+#[test]
+fn threads_sets_the_worker_threads_of_the_runtime() {
+    let t = TestDb::new();
+    let output = t.run(&["--threads", "2", "-l", "debug"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Starting runtime with 2 worker threads"));
+    assert_eq!(t.read("other.csv"), "k,v\nx,3\n");
+
+    assert!(!t.run(&["--threads", "0", "--overwrite"]).status.success());
+}
+
+#[tokio::test]
+async fn sequential_dumps_the_tables_one_after_another() {
+    let t = TestDb::new();
+    let config = t
+        .builder()
+        .sequential(true)
+        .tables_filter(sqlitedumper::TableFilter {
+            include: vec!["stats".to_string(), "other".to_string()],
+            ..Default::default()
+        })
+        .build()
+        .unwrap();
+    let (_guard, logs) = capture_logs();
+    sqlitedumper::dump_database(&config).await.unwrap();
+
+    // No task is spawned, every table is dumped on this thread in order.
+    let logs = String::from_utf8(logs.lock().unwrap().clone()).unwrap();
+    let messages: Vec<String> = logs
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .map(|event| event["fields"]["message"].as_str().unwrap().to_string())
+        .filter(|message| message.starts_with("Dumping table"))
+        .collect();
+    assert_eq!(messages, ["Dumping table stats", "Dumping table other"]);
+
+    let output = t.run(&["--no-parallel", "--overwrite"]);
+    assert!(output.status.success());
+    assert!(!t.run(&["--no-parallel", "--threads", "2"]).status.success());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn parallel_tables_dumps_every_table_and_must_not_be_zero() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE a (x); CREATE TABLE b (x); CREATE TABLE c (x); INSERT INTO c VALUES (1);",
+    );
+    let config = t.builder().parallel_tables(Some(1)).build().unwrap();
+    let stats = sqlitedumper::dump_database(&config).await.unwrap();
+    assert!(stats.tables.iter().all(|table| table.error.is_none()));
+    assert_eq!(t.files(), ["a.csv", "b.csv", "c.csv"]);
+    assert_eq!(t.read("c.csv"), "x\n1\n");
+
+    let result = t.builder().parallel_tables(Some(0)).build();
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::InvalidConfig(_))
+    ));
+    assert!(
+        !t.run(&["--parallel-tables", "2", "--no-parallel"])
+            .status
+            .success()
+    );
+}
+
+#[tokio::test]
+async fn query_file_queries_are_dumped_to_files_named_after_them() {
+    let t = TestDb::new();
+    let path = t.path("queries.sql");
+    std::fs::write(
+        &path,
+        "-- name: totals\nSELECT COUNT(*) AS n, SUM(value) AS total FROM stats;\n\
+         /* not; a query */ SELECT k || ';' AS k FROM other;\n",
+    )
+    .unwrap();
+    let queries = sqlitedumper::read_query_file(path.to_str().unwrap()).unwrap();
+    let names: Vec<&str> = queries.iter().map(|query| query.name.as_str()).collect();
+    assert_eq!(names, ["totals", "query_2"]);
+    assert_eq!(
+        queries[1].sql,
+        "/* not; a query */ SELECT k || ';' AS k FROM other"
+    );
+
+    let config = t.builder().queries(queries).build().unwrap();
+    sqlitedumper::dump_database(&config).await.unwrap();
+    assert_eq!(t.files(), ["query_2.csv", "totals.csv"]);
+    assert_eq!(t.read("totals.csv"), "n,total\n2,1.75\n");
+    assert_eq!(t.read("query_2.csv"), "k\nx;\n");
+}
+
+#[tokio::test]
+async fn named_queries_must_be_valid_read_only_selects() {
+    let t = TestDb::new();
+    let query =
+        sqlitedumper::parse_named_query("big = SELECT id FROM stats WHERE value > 1").unwrap();
+    assert_eq!(query.name, "big");
+    assert_eq!(query.sql, "SELECT id FROM stats WHERE value > 1");
+    let config = t.builder().queries(vec![query]).build().unwrap();
+    sqlitedumper::dump_database(&config).await.unwrap();
+    assert_eq!(t.read("big.csv"), "id\n1\n");
+
+    for arg in ["SELECT 1", "big=", "../x=SELECT 1"] {
+        assert!(sqlitedumper::parse_named_query(arg).is_err(), "{arg}");
+    }
+    let dump = |sql: &str| {
+        let query = sqlitedumper::parse_named_query(&format!("q={sql}")).unwrap();
+        let config = t
+            .builder()
+            .queries(vec![query])
+            .overwrite(true)
+            .build()
+            .unwrap();
+        async move { sqlitedumper::dump_database(&config).await }
+    };
+    assert!(matches!(
+        dump("DELETE FROM stats").await,
+        Err(sqlitedumper::DumperError::QueryNotReadOnly(name)) if name == "q"
+    ));
+    assert!(matches!(
+        dump("SELECT FROM").await,
+        Err(sqlitedumper::DumperError::InvalidQuery { name, .. }) if name == "q"
+    ));
+    assert!(!t.out.join("q.csv").exists());
+}
+
+#[tokio::test]
+async fn pragmas_are_set_on_the_dump_connection() {
+    let t = TestDb::new();
+    let config = t
+        .builder()
+        .where_clause(Some("k LIKE 'X'".to_string()))
+        .build()
+        .unwrap();
+    assert_eq!(t.dump(&config, "other", "other.csv").await, "k,v\nx,3\n");
+
+    let mut config = config;
+    config.overwrite = true;
+    config.pragmas = vec![("case_sensitive_like".to_string(), "ON".to_string())];
+    assert_eq!(t.dump(&config, "other", "other.csv").await, "k,v\n");
+
+    let result = t
+        .builder()
+        .pragmas(vec![(
+            "query_only".to_string(),
+            "1; DROP TABLE x".to_string(),
+        )])
+        .build();
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::UnsafeSqlFragment(_))
+    ));
+    // The connection is read-only.
+    config.pragmas = vec![("user_version".to_string(), "5".to_string())];
+    let result = sqlitedumper::dump_table(&config, "other").await;
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::PragmaFailed { .. })
+    ));
+}
+
+#[tokio::test]
+async fn mmap_size_is_set_on_the_dump_connection() {
+    let t = TestDb::new();
+    let query = sqlitedumper::parse_named_query("mmap=PRAGMA mmap_size").unwrap();
+    let config = t
+        .builder()
+        .mmap_size(1 << 20)
+        .queries(vec![query])
+        .build()
+        .unwrap();
+    sqlitedumper::dump_database(&config).await.unwrap();
+    assert_eq!(t.read("mmap.csv"), "mmap_size\n1048576\n");
+}
+
+#[tokio::test]
+async fn cache_size_is_set_on_the_dump_connection() {
+    let t = TestDb::new();
+    let query = sqlitedumper::parse_named_query("cache=PRAGMA cache_size").unwrap();
+    let config = t
+        .builder()
+        .cache_size(Some(-4096))
+        .queries(vec![query])
+        .build()
+        .unwrap();
+    sqlitedumper::dump_database(&config).await.unwrap();
+    assert_eq!(t.read("cache.csv"), "cache_size\n-4096\n");
+}
+
+#[test]
+fn checkpoint_wal_moves_the_wal_into_the_database_file() {
+    let t = TestDb::new();
+    let writer = t.conn();
+    writer
+        .execute_batch(
+            "PRAGMA journal_mode = WAL;
+             PRAGMA wal_autocheckpoint = 0;
+             INSERT INTO other VALUES ('wal', 4);",
+        )
+        .unwrap();
+    // Rows in the database file alone, without its WAL.
+    let rows_in_file = || {
+        let copy = t.path("copy.sqlite");
+        std::fs::copy(&t.db, &copy).unwrap();
+        let count = rusqlite::Connection::open(&copy)
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM other", [], |row| row.get::<_, i64>(0))
+            .unwrap();
+        std::fs::remove_file(&copy).unwrap();
+        count
+    };
+    assert_eq!(rows_in_file(), 1);
+
+    let config = t.builder().build().unwrap();
+    sqlitedumper::checkpoint_wal(&config).unwrap();
+    assert_eq!(rows_in_file(), 2);
+    drop(writer);
+
+    // A database in rollback journal mode is left alone.
+    let t = TestDb::new();
+    sqlitedumper::checkpoint_wal(&t.builder().build().unwrap()).unwrap();
+    assert!(!t.path("test.sqlite-wal").exists());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn several_databases_are_dumped_into_directories_of_their_own() {
+    let t = TestDb::new();
+    let second = t.path("second.sqlite");
+    rusqlite::Connection::open(&second)
+        .unwrap()
+        .execute_batch("CREATE TABLE other (k TEXT); INSERT INTO other VALUES ('second');")
+        .unwrap();
+    let db_paths = [t.db.clone(), second.to_str().unwrap().to_string()];
+    let config = t.builder().build().unwrap();
+    let stats = sqlitedumper::dump_databases(&config, &db_paths)
+        .await
+        .unwrap();
+
+    let mut tables: Vec<&str> = stats
+        .tables
+        .iter()
+        .map(|table| table.table.as_str())
+        .collect();
+    tables.sort();
+    assert_eq!(tables, ["second/other", "test/other", "test/stats"]);
+    assert_eq!(t.read("test/other.csv"), "k,v\nx,3\n");
+    assert_eq!(t.read("second/other.csv"), "k\nsecond\n");
+
+    // Two files with the same stem would share a directory.
+    let same = [t.db.clone(), t.db.clone()];
+    let result = sqlitedumper::dump_databases(&config, &same).await;
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::InvalidConfig(_))
+    ));
+
+    // Glob patterns among the --file arguments are expanded.
+    let pattern = t.path("*.sqlite");
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_sqlitedumper"))
+        .args([
+            "-f",
+            pattern.to_str().unwrap(),
+            "-d",
+            t.path("glob").to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(t.path("glob/second/other.csv").exists());
+    assert!(t.path("glob/test/stats.csv").exists());
+}
+
+#[tokio::test]
+async fn attached_databases_can_be_joined_in_queries() {
+    let t = TestDb::new();
+    let names = t.path("names.sqlite");
+    rusqlite::Connection::open(&names)
+        .unwrap()
+        .execute_batch(
+            "CREATE TABLE labels (id INTEGER, label TEXT); INSERT INTO labels VALUES (2, 'two');",
+        )
+        .unwrap();
+    let query = sqlitedumper::parse_named_query(
+        "joined=SELECT s.id, l.label FROM stats s JOIN names.labels l ON l.id = s.id",
+    )
+    .unwrap();
+    let config = t
+        .builder()
+        .attach(vec![(
+            "names".to_string(),
+            names.to_str().unwrap().to_string(),
+        )])
+        .queries(vec![query])
+        .build()
+        .unwrap();
+    sqlitedumper::dump_database(&config).await.unwrap();
+    assert_eq!(t.read("joined.csv"), "id,label\n2,two\n");
+
+    for alias in ["main", "bad-alias", ""] {
+        let result = t
+            .builder()
+            .attach(vec![(alias.to_string(), "x.sqlite".to_string())])
+            .build();
+        assert!(
+            matches!(result, Err(sqlitedumper::DumperError::InvalidConfig(_))),
+            "{alias}"
+        );
+    }
+    let missing = t.path("missing.sqlite").to_str().unwrap().to_string();
+    let config = t
+        .builder()
+        .attach(vec![("missing".to_string(), missing.clone())])
+        .overwrite(true)
+        .build()
+        .unwrap();
+    let result = sqlitedumper::dump_table(&config, "other").await;
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::AttachFailed { path, .. }) if path == missing
+    ));
+}
+
+#[tokio::test]
+async fn table_list_file_selects_tables_and_skips_missing_ones() {
+    let t = TestDb::new();
+    let list = t.path("tables.txt");
+    std::fs::write(&list, "# tables to dump\nstats\n\n  missing  \nstats\n").unwrap();
+    let listed = sqlitedumper::read_table_list(list.to_str().unwrap()).unwrap();
+    assert_eq!(listed, ["stats", "missing", "stats"]);
+
+    let config = t
+        .builder()
+        .tables_filter(sqlitedumper::TableFilter {
+            listed,
+            ..Default::default()
+        })
+        .build()
+        .unwrap();
+    let (_guard, logs) = capture_logs();
+    let stats = sqlitedumper::dump_database(&config).await.unwrap();
+    assert_eq!(stats.tables.len(), 1);
+    assert_eq!(t.files(), ["stats.csv"]);
+    let logs = String::from_utf8(logs.lock().unwrap().clone()).unwrap();
+    assert!(logs.contains("Listed table missing does not exist, skipping it"));
+
+    let result = sqlitedumper::read_table_list(t.path("none.txt").to_str().unwrap());
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::InvalidConfig(_))
+    ));
+}
+
+#[tokio::test]
+async fn since_rowid_and_the_rowid_state_file_dump_new_rows_only() {
+    let t = TestDb::new();
+    let config = t
+        .builder()
+        .since_rowid([("stats".to_string(), 1)].into())
+        .build()
+        .unwrap();
+    let csv = t.dump(&config, "stats", "stats.csv").await;
+    assert_eq!(csv.lines().count(), 2);
+    assert!(csv.contains("\n2,1700000060,"));
+
+    let state = t.path("rowids.json");
+    let mut config = t
+        .builder()
+        .rowid_state_file(Some(state.to_str().unwrap().to_string()))
+        .tables_filter(sqlitedumper::TableFilter {
+            include: vec!["stats".to_string()],
+            ..Default::default()
+        })
+        .build()
+        .unwrap();
+    config.overwrite = true;
+    sqlitedumper::dump_database(&config).await.unwrap();
+    assert_eq!(t.read("stats.csv").lines().count(), 3);
+    let saved: serde_json::Value = serde_json::from_slice(&std::fs::read(&state).unwrap()).unwrap();
+    assert_eq!(saved, serde_json::json!({"stats": 2}));
+
+    t.conn()
+        .execute("INSERT INTO stats (id, name) VALUES (7, 'new')", [])
+        .unwrap();
+    sqlitedumper::dump_database(&config).await.unwrap();
+    let csv = t.read("stats.csv");
+    assert_eq!(csv.lines().count(), 2);
+    assert!(csv.ends_with("\n7,null,,new,null\n"));
+    let saved: serde_json::Value = serde_json::from_slice(&std::fs::read(&state).unwrap()).unwrap();
+    assert_eq!(saved, serde_json::json!({"stats": 7}));
+}
+
+#[tokio::test]
+async fn since_timestamp_and_the_timestamp_state_file_dump_new_rows_only() {
+    let t = TestDb::new();
+    let since = sqlitedumper::parse_since_timestamp("2023-11-14T22:13:20").unwrap();
+    let config = t.builder().since_timestamp(Some(since)).build().unwrap();
+    let csv = t.dump(&config, "stats", "stats.csv").await;
+    assert_eq!(csv.lines().count(), 2);
+    assert!(csv.contains("\n2,1700000060,"));
+    // Tables without a timestamp column are dumped whole.
+    assert_eq!(t.dump(&config, "other", "other.csv").await, "k,v\nx,3\n");
+
+    let state = t.path("timestamps.json");
+    let mut config = t
+        .builder()
+        .timestamp_state_file(Some(state.to_str().unwrap().to_string()))
+        .build()
+        .unwrap();
+    config.overwrite = true;
+    sqlitedumper::dump_database(&config).await.unwrap();
+    let saved: serde_json::Value = serde_json::from_slice(&std::fs::read(&state).unwrap()).unwrap();
+    assert_eq!(saved, serde_json::json!({"stats": 1700000060}));
+
+    t.conn()
+        .execute(
+            "INSERT INTO stats (sm_timestamp) VALUES (1700000030), (1700000120)",
+            [],
+        )
+        .unwrap();
+    sqlitedumper::dump_database(&config).await.unwrap();
+    let csv = t.read("stats.csv");
+    assert_eq!(csv.lines().count(), 2);
+    assert!(csv.contains(",1700000120,"));
+
+    assert!(sqlitedumper::parse_since_timestamp("yesterday").is_err());
+}
+
+#[tokio::test]
+async fn sample_dumps_that_many_rows_repeatably_with_a_seed() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE n (i INTEGER);
+         WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 100)
+         INSERT INTO n SELECT i FROM n;",
+    );
+    let config = t
+        .builder()
+        .sample(Some(10))
+        .sample_seed(Some(42))
+        .overwrite(true)
+        .build()
+        .unwrap();
+    let first = t.dump(&config, "n", "n.csv").await;
+    assert_eq!(first.lines().count(), 11);
+    assert_eq!(t.dump(&config, "n", "n.csv").await, first);
+
+    let mut config = config;
+    config.sample = Some(1000);
+    config.sample_seed = None;
+    assert_eq!(t.dump(&config, "n", "n.csv").await.lines().count(), 101);
+
+    for builder in [
+        t.builder().sample(Some(0)),
+        t.builder().sample(Some(5)).limit(Some(2)),
+        t.builder().sample_seed(Some(1)),
+    ] {
+        assert!(matches!(
+            builder.build(),
+            Err(sqlitedumper::DumperError::InvalidConfig(_))
+        ));
+    }
+}
+
+#[test]
+fn system_tables_are_listed_only_when_included() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE events (id INTEGER PRIMARY KEY AUTOINCREMENT, v TEXT);
+         INSERT INTO events (v) VALUES ('a');
+         CREATE TABLE sqlitedata (x);",
+    );
+    let config = t.builder().build().unwrap();
+    let mut tables = sqlitedumper::get_tables(&config).unwrap();
+    tables.sort();
+    assert_eq!(tables, ["events", "sqlitedata"]);
+
+    let config = t.builder().include_system_tables(true).build().unwrap();
+    let mut tables = sqlitedumper::get_tables(&config).unwrap();
+    tables.sort();
+    assert_eq!(tables, ["events", "sqlite_sequence", "sqlitedata"]);
+}
+
+#[tokio::test]
+async fn include_indexes_writes_the_indexes_and_their_columns() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE t (a INTEGER UNIQUE, b TEXT);
+         CREATE INDEX t_b_lower ON t (b, lower(b));",
+    );
+    let config = t.builder().include_indexes(true).build().unwrap();
+    sqlitedumper::dump_database(&config).await.unwrap();
+    assert_eq!(t.files(), ["_index_columns.csv", "_indexes.csv", "t.csv"]);
+    assert_eq!(
+        t.read("_indexes.csv"),
+        "index,table,sql,unique\n\
+         sqlite_autoindex_t_1,t,,1\n\
+         t_b_lower,t,\"CREATE INDEX t_b_lower ON t (b, lower(b))\",0\n"
+    );
+    // The expression column has no name.
+    assert_eq!(
+        t.read("_index_columns.csv"),
+        "index,table,seqno,cid,column\n\
+         sqlite_autoindex_t_1,t,0,0,a\n\
+         t_b_lower,t,0,1,b\n\
+         t_b_lower,t,1,-2,\n"
+    );
+}
+
+#[tokio::test]
+async fn metadata_files_are_replaced_on_append_and_not_archived() {
+    let t = TestDb::with_sql("CREATE TABLE t (a INTEGER UNIQUE);");
+    let config = t
+        .builder()
+        .include_indexes(true)
+        .include_triggers(true)
+        .include_foreign_keys(true)
+        .append(true)
+        .build()
+        .unwrap();
+    sqlitedumper::dump_database(&config).await.unwrap();
+    let indexes = t.read("_indexes.csv");
+    sqlitedumper::dump_database(&config).await.unwrap();
+    assert_eq!(t.read("_indexes.csv"), indexes);
+    assert_eq!(t.read("_triggers.csv").lines().count(), 1);
+
+    for archive in ["dump.zip", "dump.tar"] {
+        let archive = Some(t.path(archive).to_str().unwrap().to_string());
+        for builder in [
+            t.builder().include_indexes(true),
+            t.builder().include_triggers(true),
+            t.builder().include_foreign_keys(true),
+        ] {
+            let builder = match archive.as_deref().unwrap().ends_with(".zip") {
+                true => builder.output_zip(archive.clone()),
+                false => builder.output_tar(archive.clone()),
+            };
+            assert!(matches!(
+                builder.build(),
+                Err(sqlitedumper::DumperError::InvalidConfig(_))
+            ));
+        }
+    }
+}
+
+#[tokio::test]
+async fn include_foreign_keys_writes_the_foreign_keys() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE parent (id INTEGER PRIMARY KEY, a INTEGER, b INTEGER, UNIQUE (a, b));
+         CREATE TABLE child (
+             p INTEGER REFERENCES parent ON DELETE CASCADE,
+             x INTEGER, y INTEGER,
+             FOREIGN KEY (x, y) REFERENCES parent (a, b)
+         );",
+    );
+    let config = t
+        .builder()
+        .include_foreign_keys(true)
+        .tables_filter(sqlitedumper::TableFilter {
+            include: vec!["child".to_string()],
+            ..Default::default()
+        })
+        .build()
+        .unwrap();
+    sqlitedumper::dump_database(&config).await.unwrap();
+    // The key to the primary key of parent has no parent column.
+    assert_eq!(
+        t.read("_foreign_keys.csv"),
+        "id,seq,from_table,from_column,to_table,to_column,on_update,on_delete,match\n\
+         0,0,child,x,parent,a,NO ACTION,NO ACTION,NONE\n\
+         0,1,child,y,parent,b,NO ACTION,NO ACTION,NONE\n\
+         1,0,child,p,parent,,NO ACTION,CASCADE,NONE\n"
+    );
+}
+
+#[tokio::test]
+async fn row_number_counts_rows_across_parts() {
+    let t = TestDb::new();
+    let config = t.builder().row_number(true).build().unwrap();
+    assert_eq!(
+        t.dump(&config, "other", "other.csv").await,
+        "_row_number,k,v\n1,x,3\n"
+    );
+
+    let mut config = config;
+    config.rows_per_file = Some(1);
+    config.columns = [("stats".to_string(), vec!["name".to_string()])].into();
+    sqlitedumper::dump_table(&config, "stats").await.unwrap();
+    assert_eq!(t.read("stats_part000.csv"), "_row_number,name\n1,\"a,b\"\n");
+    assert_eq!(t.read("stats_part001.csv"), "_row_number,name\n2,null\n");
+}
+
+#[tokio::test]
+async fn column_order_moves_the_listed_columns_first() {
+    let t = TestDb::new();
+    let config = t
+        .builder()
+        .column_order(
+            [(
+                "stats".to_string(),
+                vec!["value".to_string(), "sm_timestamp".to_string()],
+            )]
+            .into(),
+        )
+        .build()
+        .unwrap();
+    // The parsed timestamp stays next to its column, the rest keep their order.
+    let csv = t.dump(&config, "stats", "stats.csv").await;
+    assert_eq!(
+        csv.lines().take(2).collect::<Vec<_>>(),
+        [
+            "value,sm_timestamp,timestamp_parsed,id,name",
+            "1.5,1700000000,2023-11-14T22:13:20Z,1,\"a,b\"",
+        ]
+    );
+
+    let mut config = config;
+    config.column_order = [("stats".to_string(), vec!["missing".to_string()])].into();
+    let result = sqlitedumper::dump_database(&config).await;
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::ColumnNotFound { column, .. }) if column == "missing"
+    ));
+}
+
+#[tokio::test]
+async fn sort_columns_writes_columns_alphabetically() {
+    let t = TestDb::with_sql(
+        "CREATE TABLE t (zeta INTEGER, Alpha TEXT, mid REAL);
+         INSERT INTO t VALUES (1, 'a', 0.5);",
+    );
+    let config = t
+        .builder()
+        .sort_columns(true)
+        .row_number(true)
+        .build()
+        .unwrap();
+    // Case is ignored, the row number stays in front.
+    assert_eq!(
+        t.dump(&config, "t", "t.csv").await,
+        "_row_number,Alpha,mid,zeta\n1,a,0.5,1\n"
+    );
+
+    let result = t
+        .builder()
+        .sort_columns(true)
+        .column_order([("t".to_string(), vec!["mid".to_string()])].into())
+        .build();
+    assert!(matches!(
+        result,
+        Err(sqlitedumper::DumperError::InvalidConfig(_))
+    ));
+}
+
+#[tokio::test]
+async fn flatten_json_expands_objects_into_columns() {
+    let t = TestDb::with_sql(
+        r#"CREATE TABLE t (id INTEGER, attrs TEXT, note TEXT);
+           INSERT INTO t VALUES (1, '{"a": 1, "b": {"c": "x"}}', '{"n": 1}');
+           INSERT INTO t VALUES (2, '{"a": 2.5, "d": [1, 2], "e": false}', 'plain');"#,
+    );
+    let config = t
+        .builder()
+        .flatten_json(true)
+        .flatten_json_depth(2)
+        .build()
+        .unwrap();
+    // note is not JSON in every row, so it is not flattened.
+    assert_eq!(
+        t.dump(&config, "t", "t.csv").await,
+        "id,attrs,attrs__a,attrs__b__c,attrs__d,attrs__e,note\n\
+         1,\"{\"\"a\"\": 1, \"\"b\"\": {\"\"c\"\": \"\"x\"\"}}\",1,x,null,null,\"{\"\"n\"\": 1}\"\n\
+         2,\"{\"\"a\"\": 2.5, \"\"d\"\": [1, 2], \"\"e\"\": false}\",2.5,null,\"[1,2]\",false,plain\n"
+    );
+
+    // Objects below the default depth of one are written as JSON text.
+    let mut config = config;
+    config.overwrite = true;
+    config.flatten_json_depth = 1;
+    let csv = t.dump(&config, "t", "t.csv").await;
+    assert!(csv.starts_with("id,attrs,attrs__a,attrs__b,attrs__d,attrs__e,note\n"));
+    assert!(csv.contains(",1,\"{\"\"c\"\":\"\"x\"\"}\",null,"));
+}